@@ -1,8 +1,6 @@
 use gst::prelude::*;
 use gst::subclass::prelude::*;
-use gst::{gst_error, gst_log, gst_trace};
-
-use once_cell::sync::OnceCell;
+use gst::{gst_debug, gst_error, gst_log, gst_trace, gst_warning};
 
 use std::sync::atomic;
 use std::sync::Mutex;
@@ -20,11 +18,17 @@ static CAT: Lazy<gst::DebugCategory> = Lazy::new(|| {
     )
 });
 
+#[derive(Debug, Default)]
+struct Settings {
+    extra_ips: Option<String>,
+}
+
 #[derive(Debug)]
 pub struct DeviceProvider {
+    settings: Mutex<Settings>,
     thread: Mutex<Option<thread::JoinHandle<()>>>,
     current_devices: Mutex<Vec<super::Device>>,
-    find: Mutex<Option<ndi::FindInstance>>,
+    find: Mutex<Option<Box<dyn ndi::FindSource>>>,
     is_running: atomic::AtomicBool,
 }
 
@@ -36,6 +40,7 @@ impl ObjectSubclass for DeviceProvider {
 
     fn new() -> Self {
         Self {
+            settings: Mutex::new(Default::default()),
             thread: Mutex::new(None),
             current_devices: Mutex::new(vec![]),
             find: Mutex::new(None),
@@ -44,7 +49,56 @@ impl ObjectSubclass for DeviceProvider {
     }
 }
 
-impl ObjectImpl for DeviceProvider {}
+impl ObjectImpl for DeviceProvider {
+    fn properties() -> &'static [glib::ParamSpec] {
+        static PROPERTIES: Lazy<Vec<glib::ParamSpec>> = Lazy::new(|| {
+            vec![glib::ParamSpecString::new(
+                "extra-ips",
+                "Extra IPs",
+                "Comma or space separated IP addresses of senders in other subnets to discover \
+                 sources from. Takes effect the next time the provider is started",
+                None,
+                glib::ParamFlags::READWRITE,
+            )]
+        });
+
+        PROPERTIES.as_ref()
+    }
+
+    fn set_property(
+        &self,
+        obj: &Self::Type,
+        _id: usize,
+        value: &glib::Value,
+        pspec: &glib::ParamSpec,
+    ) {
+        match pspec.name() {
+            "extra-ips" => {
+                let mut settings = self.settings.lock().unwrap();
+                let extra_ips = value.get().unwrap();
+                gst_debug!(
+                    CAT,
+                    obj: obj,
+                    "Changing extra-ips from {:?} to {:?}",
+                    settings.extra_ips,
+                    extra_ips,
+                );
+                settings.extra_ips = extra_ips;
+            }
+            _ => unimplemented!(),
+        }
+    }
+
+    fn property(&self, _obj: &Self::Type, _id: usize, pspec: &glib::ParamSpec) -> glib::Value {
+        match pspec.name() {
+            "extra-ips" => {
+                let settings = self.settings.lock().unwrap();
+                settings.extra_ips.to_value()
+            }
+            _ => unimplemented!(),
+        }
+    }
+}
 
 impl GstObjectImpl for DeviceProvider {}
 
@@ -70,6 +124,10 @@ impl DeviceProviderImpl for DeviceProvider {
     }
 
     fn start(&self, device_provider: &Self::Type) -> Result<(), gst::LoggableError> {
+        if !crate::ndi::is_initialized() {
+            return Err(gst::loggable_error!(CAT, "NDI could not be initialized"));
+        }
+
         let mut thread_guard = self.thread.lock().unwrap();
         if thread_guard.is_some() {
             gst_log!(CAT, obj: device_provider, "Device provider already started");
@@ -80,7 +138,7 @@ impl DeviceProviderImpl for DeviceProvider {
 
         let device_provider_weak = device_provider.downgrade();
         let mut first = true;
-        *thread_guard = Some(thread::spawn(move || {
+        let thread = thread::Builder::new().name("ndi-find".into()).spawn(move || {
             let device_provider = match device_provider_weak.upgrade() {
                 None => return,
                 Some(device_provider) => device_provider,
@@ -94,14 +152,29 @@ impl DeviceProviderImpl for DeviceProvider {
                     return;
                 }
 
-                let find = match ndi::FindInstance::builder().build() {
+                let extra_ips = imp.settings.lock().unwrap().extra_ips.clone();
+                let builder = ndi::FindInstance::builder();
+                let builder = match extra_ips {
+                    Some(ref extra_ips) => {
+                        gst_debug!(
+                            CAT,
+                            obj: &device_provider,
+                            "Discovering sources also from {}",
+                            extra_ips
+                        );
+                        builder.extra_ips(extra_ips)
+                    }
+                    None => builder,
+                };
+
+                let find = match builder.build() {
                     None => {
                         gst_error!(CAT, obj: &device_provider, "Failed to create Find instance");
                         return;
                     }
                     Some(find) => find,
                 };
-                *find_guard = Some(find);
+                *find_guard = Some(Box::new(find));
             }
 
             loop {
@@ -118,7 +191,15 @@ impl DeviceProviderImpl for DeviceProvider {
                 imp.poll(&device_provider, first);
                 first = false;
             }
-        }));
+
+            // Release the find instance so that the next start creates a new one with the
+            // current settings
+            if let Some(device_provider) = device_provider_weak.upgrade() {
+                let imp = DeviceProvider::from_instance(&device_provider);
+                *imp.find.lock().unwrap() = None;
+            }
+        });
+        *thread_guard = Some(thread.expect("Failed to spawn NDI find thread"));
 
         Ok(())
     }
@@ -144,8 +225,7 @@ impl DeviceProvider {
             return;
         }
 
-        let sources = find.get_current_sources();
-        let mut sources = sources.iter().map(|s| s.to_owned()).collect::<Vec<_>>();
+        let mut sources = find.current_sources();
 
         let mut current_devices_guard = self.current_devices.lock().unwrap();
         let mut expired_devices = vec![];
@@ -154,25 +234,45 @@ impl DeviceProvider {
         // First check for each device we previously knew if it's still available
         for old_device in &*current_devices_guard {
             let old_device_imp = Device::from_instance(old_device);
-            let old_source = old_device_imp.source.get().unwrap();
+            let mut old_source_guard = old_device_imp.source.lock().unwrap();
+            let old_source = old_source_guard.as_ref().unwrap();
+
+            // Sources are identified by their NDI name only so that a changed URL/address doesn't
+            // cause the device to be removed and added again
+            match sources.iter().find(|s| s.same_ndi_name(old_source)) {
+                None => {
+                    gst_log!(
+                        CAT,
+                        obj: device_provider,
+                        "Source {:?} disappeared",
+                        old_source
+                    );
+                    expired_devices.push(old_device.clone());
+                }
+                Some(source) => {
+                    // Elements created from the device from now on connect to the new
+                    // URL/address
+                    if source.url_address() != old_source.url_address() {
+                        gst_log!(
+                            CAT,
+                            obj: device_provider,
+                            "Source {} changed URL/address to {}",
+                            old_source,
+                            source.url_address()
+                        );
+                        *old_source_guard = Some(source.to_owned());
+                    }
 
-            if !sources.contains(&*old_source) {
-                gst_log!(
-                    CAT,
-                    obj: device_provider,
-                    "Source {:?} disappeared",
-                    old_source
-                );
-                expired_devices.push(old_device.clone());
-            } else {
-                // Otherwise remember that we had it before already and don't have to announce it
-                // again. After the loop we're going to remove these all from the sources vec.
-                remaining_sources.push(old_source.to_owned());
+                    // Otherwise remember that we had it before already and don't have to announce
+                    // it again. After the loop we're going to remove these all from the sources
+                    // vec.
+                    remaining_sources.push(source.to_owned());
+                }
             }
         }
 
         for remaining_source in remaining_sources {
-            sources.retain(|s| s != &remaining_source);
+            sources.retain(|s| !s.same_ndi_name(&remaining_source));
         }
 
         // Remove all expired devices from the list of cached devices
@@ -194,7 +294,8 @@ impl DeviceProvider {
 
 #[derive(Debug)]
 pub struct Device {
-    source: OnceCell<ndi::Source<'static>>,
+    // Updated if the URL/address of the source changes
+    source: Mutex<Option<ndi::Source<'static>>>,
 }
 
 #[glib::object_subclass]
@@ -205,7 +306,7 @@ impl ObjectSubclass for Device {
 
     fn new() -> Self {
         Self {
-            source: OnceCell::new(),
+            source: Mutex::new(None),
         }
     }
 }
@@ -220,11 +321,16 @@ impl DeviceImpl for Device {
         _device: &Self::Type,
         name: Option<&str>,
     ) -> Result<gst::Element, gst::LoggableError> {
-        let source_info = self.source.get().unwrap();
-        let element = glib::Object::with_type(
+        let source_info = self.source.lock().unwrap();
+        let source_info = source_info.as_ref().unwrap();
+
+        // Create an ndisrc ! ndisrcdemux bin that exposes the audio and video pads of the
+        // demuxer so that the element can directly be used like any other audio/video source
+        let bin = gst::Bin::new(name);
+
+        let src = glib::Object::with_type(
             crate::ndisrc::NdiSrc::static_type(),
             &[
-                ("name", &name),
                 ("ndi-name", &source_info.ndi_name()),
                 ("url-address", &source_info.url_address()),
             ],
@@ -232,8 +338,43 @@ impl DeviceImpl for Device {
         .unwrap()
         .dynamic_cast::<gst::Element>()
         .unwrap();
+        let demux = glib::Object::with_type(crate::ndisrcdemux::NdiSrcDemux::static_type(), &[])
+            .unwrap()
+            .dynamic_cast::<gst::Element>()
+            .unwrap();
+
+        bin.add_many(&[&src, &demux]).unwrap();
+        src.link(&demux)
+            .map_err(|_| gst::loggable_error!(CAT, "Failed to link ndisrc to ndisrcdemux"))?;
+
+        let bin_weak = bin.downgrade();
+        demux.connect_pad_added(move |_demux, pad| {
+            let bin = match bin_weak.upgrade() {
+                Some(bin) => bin,
+                None => return,
+            };
+
+            let ghost_pad = match gst::GhostPad::with_target(Some(&pad.name()), pad) {
+                Ok(ghost_pad) => ghost_pad,
+                Err(err) => {
+                    gst_warning!(CAT, obj: &bin, "Failed to create ghost pad for {:?}: {}", pad, err);
+                    return;
+                }
+            };
+            let _ = ghost_pad.set_active(true);
+            if let Err(err) = bin.add_pad(&ghost_pad) {
+                gst_warning!(CAT, obj: &bin, "Failed to add ghost pad for {:?}: {}", pad, err);
+            }
+        });
+
+        let bin_weak = bin.downgrade();
+        demux.connect_no_more_pads(move |_demux| {
+            if let Some(bin) = bin_weak.upgrade() {
+                bin.no_more_pads();
+            }
+        });
 
-        Ok(element)
+        Ok(bin.upcast())
     }
 }
 
@@ -242,10 +383,14 @@ impl super::Device {
         let display_name = source.ndi_name();
         let device_class = "Source/Audio/Video/Network";
 
+        // The created element exposes the source pads of ndisrcdemux
         let element_class =
-            glib::Class::<gst::Element>::from_type(crate::ndisrc::NdiSrc::static_type()).unwrap();
-        let templ = element_class.pad_template("src").unwrap();
-        let caps = templ.caps();
+            glib::Class::<gst::Element>::from_type(crate::ndisrcdemux::NdiSrcDemux::static_type())
+                .unwrap();
+        let mut caps = gst::Caps::new_empty();
+        for templ in ["audio", "video"] {
+            caps.merge(element_class.pad_template(templ).unwrap().caps());
+        }
 
         // Put the url-address into the extra properties
         let extra_properties = gst::Structure::builder("properties")
@@ -262,8 +407,100 @@ impl super::Device {
         .unwrap();
         let device_impl = Device::from_instance(&device);
 
-        device_impl.source.set(source.to_owned()).unwrap();
+        *device_impl.source.lock().unwrap() = Some(source.to_owned());
 
         device
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{init, FakeFind};
+
+    // Polls the fake find once and returns the NDI name and URL/address of all current devices
+    fn poll(
+        device_provider: &super::super::DeviceProvider,
+        find: &FakeFind,
+    ) -> Vec<(String, String)> {
+        let imp = DeviceProvider::from_instance(device_provider);
+        *imp.find.lock().unwrap() = Some(Box::new(find.clone()));
+        imp.poll(device_provider, true);
+
+        imp.probe(device_provider)
+            .iter()
+            .map(|device| {
+                let properties = device.properties().unwrap();
+                (
+                    properties.get::<String>("ndi-name").unwrap(),
+                    properties.get::<String>("url-address").unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    fn devices(devices: &[(&str, &str)]) -> Vec<(String, String)> {
+        devices
+            .iter()
+            .map(|(ndi_name, url_address)| (ndi_name.to_string(), url_address.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn devices_follow_sources() {
+        init();
+
+        let device_provider = glib::Object::new::<super::super::DeviceProvider>(&[]).unwrap();
+        let find = FakeFind::new(&[
+            ("STUDIO-A (Camera)", "10.0.0.1:5961"),
+            ("STUDIO-A (Slides)", "10.0.0.2:5961"),
+        ]);
+        assert_eq!(
+            poll(&device_provider, &find),
+            devices(&[
+                ("STUDIO-A (Camera)", "10.0.0.1:5961"),
+                ("STUDIO-A (Slides)", "10.0.0.2:5961"),
+            ])
+        );
+
+        // Nothing changed, so the devices stay as they are
+        assert_eq!(poll(&device_provider, &find).len(), 2);
+
+        find.set_sources(&[
+            ("STUDIO-A (Slides)", "10.0.0.2:5961"),
+            ("STUDIO-B (Camera)", "10.0.0.3:5961"),
+        ]);
+        assert_eq!(
+            poll(&device_provider, &find),
+            devices(&[
+                ("STUDIO-A (Slides)", "10.0.0.2:5961"),
+                ("STUDIO-B (Camera)", "10.0.0.3:5961"),
+            ])
+        );
+    }
+
+    #[test]
+    fn device_url_address_change() {
+        init();
+
+        let device_provider = glib::Object::new::<super::super::DeviceProvider>(&[]).unwrap();
+        let find = FakeFind::new(&[("STUDIO-A (Camera)", "10.0.0.1:5961")]);
+        poll(&device_provider, &find);
+        let device = DeviceProvider::from_instance(&device_provider)
+            .current_devices
+            .lock()
+            .unwrap()[0]
+            .clone();
+
+        // The device is kept but elements created from it connect to the new URL/address
+        find.set_sources(&[("STUDIO-A (Camera)", "10.0.0.9:5961")]);
+        poll(&device_provider, &find);
+
+        let imp = DeviceProvider::from_instance(&device_provider);
+        let current_devices = imp.current_devices.lock().unwrap();
+        assert_eq!(current_devices.len(), 1);
+        assert_eq!(current_devices[0], device);
+        let source = Device::from_instance(&device).source.lock().unwrap();
+        assert_eq!(source.as_ref().unwrap().url_address(), "10.0.0.9:5961");
+    }
+}
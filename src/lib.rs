@@ -10,7 +10,10 @@ mod ndisrc;
 mod ndisrcdemux;
 pub mod ndisrcmeta;
 pub mod ndisys;
+pub mod nditimingmeta;
 pub mod receiver;
+#[cfg(test)]
+mod testing;
 
 use crate::ndi::*;
 use crate::ndisys::*;
@@ -34,6 +37,19 @@ pub enum TimestampMode {
     Timestamp = 3,
     #[enum_value(name = "Receive Time", nick = "receive-time")]
     ReceiveTime = 4,
+    #[enum_value(name = "Running NDI Timecode", nick = "running-timecode")]
+    RunningTimecode = 5,
+    // All sources in the process map NDI timestamps with the same offset to the clock so that
+    // they stay aligned with each other, e.g. for genlocked cameras
+    #[enum_value(
+        name = "NDI Timestamp with a shared base for all sources",
+        nick = "shared-timestamp"
+    )]
+    SharedTimestamp = 6,
+    // Receive Time / Timestamp if the source provides timestamps, otherwise Receive Time /
+    // Timecode. Selected once based on the first frame.
+    #[enum_value(name = "Auto", nick = "auto")]
+    Auto = 7,
 }
 
 #[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Clone, Copy, glib::Enum)]
@@ -48,6 +64,11 @@ pub enum RecvColorFormat {
     RgbxRgba = 2,
     #[enum_value(name = "UYVY or RGBA", nick = "uyvy-rgba")]
     UyvyRgba = 3,
+    // The SDK has no receive color format that requests planar YUV. With fastest and best
+    // planar formats (I420, NV12, YV12) are passed through as-is if the sender sends them,
+    // otherwise they are converted to UYVY. Best can additionally yield 16 bit P216/PA16, which
+    // are output as I422_12LE, as well as UYVA and any of the BGRA/BGRX/RGBA/RGBX formats. All
+    // uncompressed FourCCs of the SDK are handled, so none of these fail to negotiate.
     #[enum_value(name = "Fastest", nick = "fastest")]
     Fastest = 4,
     #[enum_value(name = "Best", nick = "best")]
@@ -78,6 +99,74 @@ pub enum RecvColorFormat {
     CompressedV5WithAudio = 13,
 }
 
+#[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Clone, Copy, glib::Enum)]
+#[repr(i32)]
+#[enum_type(name = "GstNdiRecvBandwidth")]
+pub enum RecvBandwidth {
+    #[enum_value(name = "Metadata-only", nick = "metadata-only")]
+    MetadataOnly = -10,
+    #[enum_value(name = "Audio-only", nick = "audio-only")]
+    AudioOnly = 10,
+    #[enum_value(name = "Lowest", nick = "lowest")]
+    Lowest = 0,
+    #[enum_value(name = "Highest", nick = "highest")]
+    Highest = 100,
+}
+
+impl From<RecvBandwidth> for NDIlib_recv_bandwidth_e {
+    fn from(v: RecvBandwidth) -> Self {
+        match v {
+            RecvBandwidth::MetadataOnly => NDIlib_recv_bandwidth_metadata_only,
+            RecvBandwidth::AudioOnly => NDIlib_recv_bandwidth_audio_only,
+            RecvBandwidth::Lowest => NDIlib_recv_bandwidth_lowest,
+            RecvBandwidth::Highest => NDIlib_recv_bandwidth_highest,
+        }
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Clone, Copy, glib::Enum)]
+#[repr(u32)]
+#[enum_type(name = "GstNdiNameMatch")]
+pub enum NdiNameMatch {
+    #[enum_value(name = "Exact match of the full NDI name", nick = "exact")]
+    Exact = 0,
+    #[enum_value(name = "NDI name contains the given name", nick = "contains")]
+    Contains = 1,
+    #[enum_value(
+        name = "Exact match of the source part of the NDI name",
+        nick = "source-part"
+    )]
+    SourcePart = 2,
+}
+
+impl NdiNameMatch {
+    pub fn matches(self, name: &str, ndi_name: &str) -> bool {
+        match self {
+            NdiNameMatch::Exact => ndi_name == name,
+            NdiNameMatch::Contains => ndi_name.contains(name),
+            NdiNameMatch::SourcePart => {
+                ndi_name == name || ndi::split_ndi_name(ndi_name).1 == Some(name)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "sink")]
+#[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Clone, Copy, glib::Enum)]
+#[repr(u32)]
+#[enum_type(name = "GstNdiSendTimecodeMode")]
+pub enum SendTimecodeMode {
+    #[enum_value(name = "Let the NDI SDK synthesize the timecode", nick = "synthesize")]
+    Synthesize = 0,
+    #[enum_value(name = "Timecode from the buffer running time", nick = "from-pts")]
+    FromPts = 1,
+    #[enum_value(
+        name = "Timecode from the video timecode meta",
+        nick = "from-timecode-meta"
+    )]
+    FromTimecodeMeta = 2,
+}
+
 impl From<RecvColorFormat> for NDIlib_recv_color_format_e {
     fn from(v: RecvColorFormat) -> Self {
         match v {
@@ -113,11 +202,37 @@ impl From<RecvColorFormat> for NDIlib_recv_color_format_e {
     }
 }
 
-fn plugin_init(plugin: &gst::Plugin) -> Result<(), glib::BoolError> {
-    if !ndi::initialize() {
-        return Err(glib::bool_error!("Cannot initialize NDI"));
+// Machine-readable classification of the most common errors, stored as `ndi-error` field in the
+// details structure of the error messages so that monitoring systems don't have to parse the
+// free-text messages
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum ErrorCode {
+    ConnectFailed,
+    CaptureError,
+    UnsupportedFormat,
+}
+
+impl ErrorCode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ErrorCode::ConnectFailed => "connect-failed",
+            ErrorCode::CaptureError => "capture-error",
+            ErrorCode::UnsupportedFormat => "unsupported-format",
+        }
     }
 
+    pub fn details(self) -> gst::Structure {
+        gst::Structure::builder("ndi-error-details")
+            .field("ndi-error", self.as_str())
+            .build()
+    }
+}
+
+fn plugin_init(plugin: &gst::Plugin) -> Result<(), glib::BoolError> {
+    // Still register all elements if NDI can't be initialized so that they can report
+    // a proper error when they're used
+    ndi::initialize();
+
     device_provider::register(plugin)?;
 
     ndisrc::register(plugin)?;
@@ -157,3 +272,81 @@ gst::plugin_define!(
     env!("CARGO_PKG_REPOSITORY"),
     env!("BUILD_REL_DATE")
 );
+
+#[cfg(all(test, feature = "sink"))]
+mod tests {
+    use super::*;
+    use gst::prelude::*;
+
+    use std::sync::{Arc, Mutex};
+
+    // Sends a test pattern through ndisink and receives it again with ndisrc on the same host.
+    // This needs the NDI runtime and a network that allows NDI discovery, so it only runs with
+    // `cargo test -- --ignored` and passes without doing anything if the runtime can't be loaded.
+    #[test]
+    #[ignore]
+    fn loopback() {
+        testing::init();
+        plugin_register_static().unwrap();
+        if !ndi::initialize() {
+            eprintln!("NDI runtime not available, skipping loopback test");
+            return;
+        }
+
+        let ndi_name = format!("gst-plugin-ndi loopback {}", std::process::id());
+        let send = gst::parse_launch(&format!(
+            "videotestsrc is-live=true pattern=smpte \
+             ! video/x-raw,format=UYVY,width=640,height=360,framerate=30/1 \
+             ! ndisink ndi-name=\"{}\"",
+            ndi_name
+        ))
+        .unwrap();
+        let recv = gst::parse_launch(&format!(
+            "ndisrc ndi-name=\"{}\" ndi-name-match=source-part connect-timeout=20000 \
+             ! ndisrcdemux name=demux demux.video ! fakesink name=sink",
+            ndi_name
+        ))
+        .unwrap()
+        .downcast::<gst::Bin>()
+        .unwrap();
+
+        // Caps of the received video and number of buffers that arrived with them
+        let received = Arc::new(Mutex::new((None::<gst::Caps>, 0)));
+        let sink_pad = recv.by_name("sink").unwrap().static_pad("sink").unwrap();
+        let received_clone = received.clone();
+        sink_pad.add_probe(gst::PadProbeType::BUFFER, move |pad, _| {
+            let mut received = received_clone.lock().unwrap();
+            received.0 = pad.current_caps();
+            received.1 += 1;
+            gst::PadProbeReturn::Ok
+        });
+
+        send.set_state(gst::State::Playing).unwrap();
+        recv.set_state(gst::State::Playing).unwrap();
+
+        let bus = recv.bus().unwrap();
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(30);
+        while received.lock().unwrap().1 < 10 && std::time::Instant::now() < deadline {
+            if let Some(msg) = bus.timed_pop_filtered(
+                gst::ClockTime::from_mseconds(100),
+                &[gst::MessageType::Error, gst::MessageType::Eos],
+            ) {
+                panic!("Receiving failed: {:?}", msg);
+            }
+        }
+
+        recv.set_state(gst::State::Null).unwrap();
+        send.set_state(gst::State::Null).unwrap();
+
+        let (caps, buffers) = received.lock().unwrap().clone();
+        assert!(buffers >= 10, "Only received {} buffers", buffers);
+
+        // NDI compresses the video, so only the format is checked and not the content
+        let s = caps.unwrap();
+        let s = s.structure(0).unwrap();
+        assert_eq!(s.name(), "video/x-raw");
+        assert_eq!(s.get::<&str>("format").unwrap(), "UYVY");
+        assert_eq!(s.get::<i32>("width").unwrap(), 640);
+        assert_eq!(s.get::<i32>("height").unwrap(), 360);
+    }
+}
@@ -1,13 +1,159 @@
 use crate::ndisys;
 use crate::ndisys::*;
+use std::borrow::Cow;
+use std::cmp;
 use std::ffi;
+use std::fmt;
 use std::mem;
 use std::ptr;
+use std::sync::{Arc, Mutex};
+use std::time;
 
 use byte_slice_cast::*;
+use once_cell::sync::OnceCell;
+
+static LOAD_ERROR: OnceCell<String> = OnceCell::new();
+
+// Number of NdiContexts currently alive
+static CONTEXT_REFCOUNT: Mutex<usize> = Mutex::new(0);
+
+// Keeps the NDI library initialized for as long as the plugin is loaded
+static PLUGIN_CONTEXT: OnceCell<NdiContext> = OnceCell::new();
+
+// Reference to the initialized NDI library. The library is initialized when the first context
+// is created and destroyed again when the last one is dropped. All instances keep a context
+// alive while they exist.
+#[derive(Debug)]
+pub struct NdiContext(());
+
+impl NdiContext {
+    pub fn new() -> Result<Self, gst::ErrorMessage> {
+        let mut refcount = CONTEXT_REFCOUNT.lock().unwrap();
+
+        if *refcount == 0 {
+            if let Err(err) = ndisys::load() {
+                let _ = LOAD_ERROR.set(err);
+                return Err(initialization_error());
+            }
+
+            if !unsafe { NDIlib_initialize() } {
+                return Err(initialization_error());
+            }
+        }
+
+        *refcount += 1;
+        Ok(NdiContext(()))
+    }
+}
+
+impl Clone for NdiContext {
+    fn clone(&self) -> Self {
+        *CONTEXT_REFCOUNT.lock().unwrap() += 1;
+        NdiContext(())
+    }
+}
+
+impl Drop for NdiContext {
+    fn drop(&mut self) {
+        let mut refcount = CONTEXT_REFCOUNT.lock().unwrap();
+        *refcount -= 1;
+        if *refcount == 0 {
+            unsafe { NDIlib_destroy() };
+        }
+    }
+}
 
 pub fn initialize() -> bool {
-    unsafe { NDIlib_initialize() }
+    match NdiContext::new() {
+        Ok(context) => {
+            let _ = PLUGIN_CONTEXT.set(context);
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+pub fn is_initialized() -> bool {
+    PLUGIN_CONTEXT.get().is_some()
+}
+
+// Version string of the NDI runtime library in use, useful for bug reports
+pub fn version() -> String {
+    if !ndisys::is_loaded() {
+        return String::new();
+    }
+
+    unsafe {
+        let version = NDIlib_version();
+        if version.is_null() {
+            String::new()
+        } else {
+            ffi::CStr::from_ptr(version).to_string_lossy().into_owned()
+        }
+    }
+}
+
+// Error for elements to return from their start functions if NDI could not be initialized, e.g.
+// because the CPU is not supported by the SDK
+pub fn initialization_error() -> gst::ErrorMessage {
+    if let Some(err) = LOAD_ERROR.get() {
+        return gst::error_msg!(
+            gst::LibraryError::Init,
+            ("NDI library could not be loaded, make sure the NDI runtime is installed or set NDI_RUNTIME_DIR_V5"),
+            ["Tried {}", err]
+        );
+    }
+
+    gst::error_msg!(
+        gst::LibraryError::Init,
+        ["NDI could not be initialized, the CPU might not be supported by the NDI SDK"]
+    )
+}
+
+// NDI names are of the form "MACHINE (Source Name)". Returns the machine name and, if the name
+// follows this convention, the source name. The source name itself can contain parentheses so
+// only the first " (" and the trailing ")" are considered.
+pub fn split_ndi_name(ndi_name: &str) -> (&str, Option<&str>) {
+    match ndi_name.find(" (") {
+        Some(idx) if ndi_name.ends_with(')') => (
+            &ndi_name[..idx],
+            Some(&ndi_name[idx + 2..ndi_name.len() - 1]),
+        ),
+        _ => (ndi_name, None),
+    }
+}
+
+// Discovers NDI sources for `timeout_ms` milliseconds and returns the NDI name and
+// URL/address of all sources found so far.
+pub fn find_sources(timeout_ms: u32, groups: Option<&str>) -> Vec<(String, String)> {
+    let builder = FindInstance::builder();
+    let builder = match groups {
+        Some(groups) => builder.groups(groups),
+        None => builder,
+    };
+
+    let mut find = match builder.build() {
+        None => return vec![],
+        Some(find) => find,
+    };
+
+    let start = std::time::Instant::now();
+    loop {
+        let elapsed = start.elapsed().as_millis() as u32;
+        if elapsed >= timeout_ms || !find.wait_for_sources(timeout_ms - elapsed) {
+            break;
+        }
+    }
+
+    find.get_current_sources()
+        .iter()
+        .map(|source| {
+            (
+                source.ndi_name().to_owned(),
+                source.url_address().to_owned(),
+            )
+        })
+        .collect()
 }
 
 #[derive(Debug)]
@@ -50,8 +196,21 @@ impl<'a> FindBuilder<'a> {
     }
 
     pub fn build(self) -> Option<FindInstance> {
-        let groups = self.groups.map(|s| ffi::CString::new(s).unwrap());
-        let extra_ips = self.extra_ips.map(|s| ffi::CString::new(s).unwrap());
+        let context = NdiContext::new().ok()?;
+        let groups = self.groups.map(ffi::CString::new).transpose().ok()?;
+        // The SDK expects a comma separated list, but also allow separating by whitespace
+        let extra_ips = self
+            .extra_ips
+            .map(|s| {
+                ffi::CString::new(
+                    s.split(|c: char| c == ',' || c.is_whitespace())
+                        .filter(|ip| !ip.is_empty())
+                        .collect::<Vec<_>>()
+                        .join(","),
+                )
+            })
+            .transpose()
+            .ok()?;
 
         unsafe {
             let ptr = NDIlib_find_create_v2(&NDIlib_find_create_t {
@@ -65,14 +224,14 @@ impl<'a> FindBuilder<'a> {
             if ptr.is_null() {
                 None
             } else {
-                Some(FindInstance(ptr::NonNull::new_unchecked(ptr)))
+                Some(FindInstance(ptr::NonNull::new_unchecked(ptr), context))
             }
         }
     }
 }
 
 #[derive(Debug)]
-pub struct FindInstance(ptr::NonNull<::std::os::raw::c_void>);
+pub struct FindInstance(ptr::NonNull<::std::os::raw::c_void>, NdiContext);
 unsafe impl Send for FindInstance {}
 
 impl FindInstance {
@@ -95,17 +254,28 @@ impl FindInstance {
                 return vec![];
             }
 
+            // Malformed entries, e.g. from odd peers on the network, are skipped so that
+            // everything else can rely on the name and URL/address being available
             let mut sources = vec![];
             for i in 0..no_sources {
-                sources.push(Source::Borrowed(
+                let source = Source::Borrowed(
                     ptr::NonNull::new_unchecked(sources_ptr.add(i as usize) as *mut _),
                     self,
-                ));
+                );
+                if source.is_valid() {
+                    sources.push(source);
+                }
             }
 
             sources
         }
     }
+
+    pub fn find_source_by_name(&mut self, ndi_name: &str) -> Option<Source> {
+        self.get_current_sources()
+            .into_iter()
+            .find(|source| source.ndi_name() == ndi_name)
+    }
 }
 
 impl Drop for FindInstance {
@@ -116,6 +286,90 @@ impl Drop for FindInstance {
     }
 }
 
+// Discovers sources on the network. Implemented by FindInstance, and by fakes in the tests so
+// that everything built on top of discovery can be tested without the NDI runtime.
+pub trait FindSource: Send + fmt::Debug + 'static {
+    // Waits up to the timeout for the discovered sources to change, returns true if they did
+    fn wait_for_sources(&mut self, timeout_in_ms: u32) -> bool;
+    fn current_sources(&mut self) -> Vec<Source<'static>>;
+}
+
+impl FindSource for FindInstance {
+    fn wait_for_sources(&mut self, timeout_in_ms: u32) -> bool {
+        FindInstance::wait_for_sources(self, timeout_in_ms)
+    }
+
+    fn current_sources(&mut self) -> Vec<Source<'static>> {
+        self.get_current_sources()
+            .iter()
+            .map(|source| source.to_owned())
+            .collect()
+    }
+}
+
+// Type of the GstContext used for sharing a find instance between the elements of a pipeline.
+// The SharedFind is stored in the "find" field of the context structure.
+pub const CONTEXT_TYPE: &str = "gst.ndi.Context";
+
+#[derive(Clone, Debug, glib::SharedBoxed)]
+#[shared_boxed_type(name = "GstNdiSharedFind")]
+pub struct SharedFind(Arc<Mutex<Box<dyn FindSource>>>);
+
+impl SharedFind {
+    pub fn new(extra_ips: Option<&str>) -> Option<Self> {
+        let builder = FindInstance::builder();
+        let builder = match extra_ips {
+            Some(extra_ips) => builder.extra_ips(extra_ips),
+            None => builder,
+        };
+
+        builder.build().map(SharedFind::from_source)
+    }
+
+    pub fn from_source<F: FindSource>(find: F) -> Self {
+        SharedFind(Arc::new(Mutex::new(Box::new(find))))
+    }
+
+    pub fn from_context(context: &gst::ContextRef) -> Option<Self> {
+        if context.context_type() != CONTEXT_TYPE {
+            return None;
+        }
+
+        context.structure().get::<Self>("find").ok()
+    }
+
+    pub fn to_context(&self) -> gst::Context {
+        let mut context = gst::Context::new(CONTEXT_TYPE, true);
+        context
+            .get_mut()
+            .unwrap()
+            .structure_mut()
+            .set("find", self.clone());
+        context
+    }
+
+    // Waits up to the timeout for the discovered sources to change. The instance is only locked
+    // for short periods so that other users of it are not blocked for the whole timeout.
+    pub fn wait_for_sources(&self, timeout_in_ms: u32) -> bool {
+        let start = time::Instant::now();
+        loop {
+            let elapsed = start.elapsed().as_millis() as u32;
+            if elapsed >= timeout_in_ms {
+                return false;
+            }
+
+            let timeout = cmp::min(timeout_in_ms - elapsed, 100);
+            if self.0.lock().unwrap().wait_for_sources(timeout) {
+                return true;
+            }
+        }
+    }
+
+    pub fn current_sources(&self) -> Vec<Source<'static>> {
+        self.0.lock().unwrap().current_sources()
+    }
+}
+
 #[derive(Debug)]
 pub enum Source<'a> {
     Borrowed(ptr::NonNull<NDIlib_source_t>, &'a FindInstance),
@@ -126,30 +380,53 @@ unsafe impl<'a> Send for Source<'a> {}
 unsafe impl<'a> Sync for Source<'a> {}
 
 impl<'a> Source<'a> {
-    pub fn ndi_name(&self) -> &str {
-        unsafe {
-            let ptr = match *self {
-                Source::Borrowed(ptr, _) => &*ptr.as_ptr(),
-                Source::Owned(ref source, _, _) => source,
-            };
-
-            assert!(!ptr.p_ndi_name.is_null());
-            ffi::CStr::from_ptr(ptr.p_ndi_name).to_str().unwrap()
+    fn as_raw(&self) -> &NDIlib_source_t {
+        match *self {
+            Source::Borrowed(ptr, _) => unsafe { &*ptr.as_ptr() },
+            Source::Owned(ref source, _, _) => source,
         }
     }
 
+    // Empty if the SDK returned no name or one that is not valid UTF-8, see is_valid()
+    pub fn ndi_name(&self) -> &str {
+        unsafe { source_field(self.as_raw().p_ndi_name) }.unwrap_or_default()
+    }
+
+    // Empty if the SDK returned no URL/address or one that is not valid UTF-8, see is_valid()
     pub fn url_address(&self) -> &str {
-        unsafe {
-            let ptr = match *self {
-                Source::Borrowed(ptr, _) => &*ptr.as_ptr(),
-                Source::Owned(ref source, _, _) => source,
-            };
+        unsafe { source_field(self.as_raw().p_url_address) }.unwrap_or_default()
+    }
 
-            assert!(!ptr.p_url_address.is_null());
-            ffi::CStr::from_ptr(ptr.p_url_address).to_str().unwrap()
+    // Whether the name and URL/address are available, which is always the case for the sources
+    // returned by FindInstance
+    pub fn is_valid(&self) -> bool {
+        let raw = self.as_raw();
+        unsafe {
+            source_field(raw.p_ndi_name).is_some() && source_field(raw.p_url_address).is_some()
         }
     }
 
+    // Whether both refer to the same source, regardless of its current URL/address. The address
+    // of a source can change while it's running, e.g. when its IP address is renewed via DHCP
+    pub fn same_ndi_name(&self, other: &Source<'_>) -> bool {
+        self.ndi_name() == other.ndi_name()
+    }
+
+    // Source with the given name and URL/address that is not backed by any find instance
+    pub fn new<'b>(ndi_name: &str, url_address: &str) -> Option<Source<'b>> {
+        let ndi_name = ffi::CString::new(ndi_name).ok()?;
+        let url_address = ffi::CString::new(url_address).ok()?;
+
+        Some(Source::Owned(
+            NDIlib_source_t {
+                p_ndi_name: ndi_name.as_ptr(),
+                p_url_address: url_address.as_ptr(),
+            },
+            ndi_name,
+            url_address,
+        ))
+    }
+
     pub fn to_owned<'b>(&self) -> Source<'b> {
         unsafe {
             let (ndi_name, url_address) = match *self {
@@ -159,9 +436,15 @@ impl<'a> Source<'a> {
                 }
             };
 
-            let ndi_name = ffi::CString::new(ffi::CStr::from_ptr(ndi_name).to_bytes()).unwrap();
-            let url_address =
-                ffi::CString::new(ffi::CStr::from_ptr(url_address).to_bytes()).unwrap();
+            let to_owned = |ptr: *const ::std::os::raw::c_char| {
+                if ptr.is_null() {
+                    ffi::CString::default()
+                } else {
+                    ffi::CStr::from_ptr(ptr).to_owned()
+                }
+            };
+            let ndi_name = to_owned(ndi_name);
+            let url_address = to_owned(url_address);
 
             Source::Owned(
                 NDIlib_source_t {
@@ -175,6 +458,21 @@ impl<'a> Source<'a> {
     }
 }
 
+// String of a source field, or None if it's NULL or not valid UTF-8
+unsafe fn source_field<'b>(ptr: *const ::std::os::raw::c_char) -> Option<&'b str> {
+    if ptr.is_null() {
+        None
+    } else {
+        ffi::CStr::from_ptr(ptr).to_str().ok()
+    }
+}
+
+impl<'a> fmt::Display for Source<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} ({})", self.ndi_name(), self.url_address())
+    }
+}
+
 impl<'a> PartialEq for Source<'a> {
     fn eq(&self, other: &Source<'a>) -> bool {
         self.ndi_name() == other.ndi_name() && self.url_address() == other.url_address()
@@ -211,6 +509,7 @@ impl<'a> RecvBuilder<'a> {
     }
 
     pub fn build(self) -> Option<RecvInstance> {
+        let context = NdiContext::new().ok()?;
         unsafe {
             let ndi_recv_name = ffi::CString::new(self.ndi_recv_name).unwrap();
             let ndi_name = self
@@ -241,14 +540,14 @@ impl<'a> RecvBuilder<'a> {
             if ptr.is_null() {
                 None
             } else {
-                Some(RecvInstance(ptr::NonNull::new_unchecked(ptr)))
+                Some(RecvInstance(ptr::NonNull::new_unchecked(ptr), context))
             }
         }
     }
 }
 
 #[derive(Debug, Clone)]
-pub struct RecvInstance(ptr::NonNull<::std::os::raw::c_void>);
+pub struct RecvInstance(ptr::NonNull<::std::os::raw::c_void>, NdiContext);
 
 unsafe impl Send for RecvInstance {}
 
@@ -272,6 +571,23 @@ impl RecvInstance {
         unsafe { NDIlib_recv_set_tally(self.0.as_ptr(), &tally.0) }
     }
 
+    pub fn ptz_is_supported(&self) -> bool {
+        unsafe { NDIlib_recv_ptz_is_supported(self.0.as_ptr()) }
+    }
+
+    pub fn web_control(&self) -> Option<String> {
+        unsafe {
+            let ptr = NDIlib_recv_get_web_control(self.0.as_ptr());
+            if ptr.is_null() {
+                return None;
+            }
+
+            let url = ffi::CStr::from_ptr(ptr).to_string_lossy().into_owned();
+            NDIlib_recv_free_string(self.0.as_ptr(), ptr);
+            Some(url)
+        }
+    }
+
     pub fn send_metadata(&self, metadata: &MetadataFrame) -> bool {
         unsafe { NDIlib_recv_send_metadata(self.0.as_ptr(), metadata.as_ptr()) }
     }
@@ -310,6 +626,9 @@ impl RecvInstance {
                 NDIlib_frame_type_e::NDIlib_frame_type_metadata => Ok(Some(Frame::Metadata(
                     MetadataFrame::Borrowed(metadata_frame, self),
                 ))),
+                NDIlib_frame_type_e::NDIlib_frame_type_status_change => {
+                    Ok(Some(Frame::StatusChange))
+                }
                 NDIlib_frame_type_e::NDIlib_frame_type_error => Err(()),
                 _ => Ok(None),
             }
@@ -328,6 +647,7 @@ pub struct SendBuilder<'a> {
     ndi_name: &'a str,
     clock_audio: bool,
     clock_video: bool,
+    groups: Option<&'a str>,
 }
 
 impl<'a> SendBuilder<'a> {
@@ -345,27 +665,40 @@ impl<'a> SendBuilder<'a> {
         }
     }
 
+    pub fn groups(self, groups: Option<&'a str>) -> Self {
+        Self { groups, ..self }
+    }
+
+    // Fails for names or groups containing NUL bytes, which can't be passed to the SDK
     pub fn build(self) -> Option<SendInstance> {
+        let ndi_name = ffi::CString::new(self.ndi_name).ok()?;
+        let groups = self
+            .groups
+            .filter(|s| !s.is_empty())
+            .map(ffi::CString::new)
+            .transpose()
+            .ok()?;
+
+        let context = NdiContext::new().ok()?;
         unsafe {
-            let ndi_name = ffi::CString::new(self.ndi_name).unwrap();
             let ptr = NDIlib_send_create(&NDIlib_send_create_t {
                 p_ndi_name: ndi_name.as_ptr(),
                 clock_video: self.clock_video,
                 clock_audio: self.clock_audio,
-                p_groups: ptr::null(),
+                p_groups: groups.as_ref().map(|s| s.as_ptr()).unwrap_or(ptr::null()),
             });
 
             if ptr.is_null() {
                 None
             } else {
-                Some(SendInstance(ptr::NonNull::new_unchecked(ptr)))
+                Some(SendInstance(ptr::NonNull::new_unchecked(ptr), context))
             }
         }
     }
 }
 
 #[derive(Debug)]
-pub struct SendInstance(ptr::NonNull<::std::os::raw::c_void>);
+pub struct SendInstance(ptr::NonNull<::std::os::raw::c_void>, NdiContext);
 
 unsafe impl Send for SendInstance {}
 
@@ -375,6 +708,7 @@ impl SendInstance {
             ndi_name,
             clock_video: false,
             clock_audio: false,
+            groups: None,
         }
     }
 
@@ -389,6 +723,79 @@ impl SendInstance {
             NDIlib_send_send_audio_v3(self.0.as_ptr(), frame.as_ptr());
         }
     }
+
+    pub fn send_metadata(&mut self, metadata: &MetadataFrame) {
+        unsafe {
+            NDIlib_send_send_metadata(self.0.as_ptr(), metadata.as_ptr());
+        }
+    }
+
+    pub fn add_connection_metadata(&mut self, metadata: &MetadataFrame) {
+        unsafe {
+            NDIlib_send_add_connection_metadata(self.0.as_ptr(), metadata.as_ptr());
+        }
+    }
+
+    pub fn clear_connection_metadata(&mut self) {
+        unsafe {
+            NDIlib_send_clear_connection_metadata(self.0.as_ptr());
+        }
+    }
+
+    // Metadata sent by receivers to this sender, e.g. PTZ or recording requests
+    pub fn capture_metadata(&mut self, timeout_in_ms: u32) -> Option<String> {
+        unsafe {
+            let mut metadata_frame = mem::zeroed();
+
+            let res = NDIlib_send_capture(self.0.as_ptr(), &mut metadata_frame, timeout_in_ms);
+            if res != NDIlib_frame_type_e::NDIlib_frame_type_metadata {
+                return None;
+            }
+
+            let metadata = if metadata_frame.p_data.is_null() {
+                String::new()
+            } else {
+                ffi::CStr::from_ptr(metadata_frame.p_data)
+                    .to_string_lossy()
+                    .into_owned()
+            };
+            NDIlib_send_free_metadata(self.0.as_ptr(), &metadata_frame);
+
+            Some(metadata)
+        }
+    }
+}
+
+// Sends frames to receivers. Implemented by SendInstance, and by fakes in the tests so that the
+// sink can be tested without the NDI runtime or any receivers.
+pub trait FrameSink: Send + 'static {
+    fn send_video(&mut self, frame: &VideoFrame);
+    fn send_audio(&mut self, frame: &AudioFrame);
+    fn send_metadata(&mut self, metadata: &MetadataFrame);
+    fn add_connection_metadata(&mut self, metadata: &MetadataFrame);
+    fn capture_metadata(&mut self, timeout_in_ms: u32) -> Option<String>;
+}
+
+impl FrameSink for SendInstance {
+    fn send_video(&mut self, frame: &VideoFrame) {
+        SendInstance::send_video(self, frame)
+    }
+
+    fn send_audio(&mut self, frame: &AudioFrame) {
+        SendInstance::send_audio(self, frame)
+    }
+
+    fn send_metadata(&mut self, metadata: &MetadataFrame) {
+        SendInstance::send_metadata(self, metadata)
+    }
+
+    fn add_connection_metadata(&mut self, metadata: &MetadataFrame) {
+        SendInstance::add_connection_metadata(self, metadata)
+    }
+
+    fn capture_metadata(&mut self, timeout_in_ms: u32) -> Option<String> {
+        SendInstance::capture_metadata(self, timeout_in_ms)
+    }
 }
 
 impl Drop for SendInstance {
@@ -432,11 +839,15 @@ pub enum Frame<'a> {
     Video(VideoFrame<'a>),
     Audio(AudioFrame<'a>),
     Metadata(MetadataFrame<'a>),
+    // The PTZ, recording or web control capabilities of the source changed
+    StatusChange,
 }
 
 #[derive(Debug)]
 pub enum VideoFrame<'a> {
-    //Owned(NDIlib_video_frame_v2_t, Option<ffi::CString>, Option<Vec<u8>>),
+    // Frame data owned by the frame itself, e.g. canned frames in tests
+    #[cfg_attr(not(test), allow(dead_code))]
+    Owned(NDIlib_video_frame_v2_t, Vec<u8>),
     BorrowedRecv(NDIlib_video_frame_v2_t, &'a RecvInstance),
     BorrowedGst(
         NDIlib_video_frame_v2_t,
@@ -447,57 +858,65 @@ pub enum VideoFrame<'a> {
 impl<'a> VideoFrame<'a> {
     pub fn xres(&self) -> i32 {
         match self {
-            VideoFrame::BorrowedRecv(ref frame, _) | VideoFrame::BorrowedGst(ref frame, _) => {
-                frame.xres
-            }
+            VideoFrame::BorrowedRecv(ref frame, _)
+            | VideoFrame::BorrowedGst(ref frame, _)
+            | VideoFrame::Owned(ref frame, _) => frame.xres,
         }
     }
 
     pub fn yres(&self) -> i32 {
         match self {
-            VideoFrame::BorrowedRecv(ref frame, _) | VideoFrame::BorrowedGst(ref frame, _) => {
-                frame.yres
-            }
+            VideoFrame::BorrowedRecv(ref frame, _)
+            | VideoFrame::BorrowedGst(ref frame, _)
+            | VideoFrame::Owned(ref frame, _) => frame.yres,
         }
     }
 
     pub fn fourcc(&self) -> NDIlib_FourCC_video_type_e {
         match self {
-            VideoFrame::BorrowedRecv(ref frame, _) | VideoFrame::BorrowedGst(ref frame, _) => {
-                frame.FourCC
-            }
+            VideoFrame::BorrowedRecv(ref frame, _)
+            | VideoFrame::BorrowedGst(ref frame, _)
+            | VideoFrame::Owned(ref frame, _) => frame.FourCC,
         }
     }
 
+    // Sources with a variable frame rate, e.g. screen captures, signal a frame rate of zero. This
+    // is returned as 0/1 like in GStreamer caps.
     pub fn frame_rate(&self) -> (i32, i32) {
         match self {
-            VideoFrame::BorrowedRecv(ref frame, _) | VideoFrame::BorrowedGst(ref frame, _) => {
-                (frame.frame_rate_N, frame.frame_rate_D)
+            VideoFrame::BorrowedRecv(ref frame, _)
+            | VideoFrame::BorrowedGst(ref frame, _)
+            | VideoFrame::Owned(ref frame, _) => {
+                if frame.frame_rate_N <= 0 || frame.frame_rate_D <= 0 {
+                    (0, 1)
+                } else {
+                    (frame.frame_rate_N, frame.frame_rate_D)
+                }
             }
         }
     }
 
     pub fn picture_aspect_ratio(&self) -> f32 {
         match self {
-            VideoFrame::BorrowedRecv(ref frame, _) | VideoFrame::BorrowedGst(ref frame, _) => {
-                frame.picture_aspect_ratio
-            }
+            VideoFrame::BorrowedRecv(ref frame, _)
+            | VideoFrame::BorrowedGst(ref frame, _)
+            | VideoFrame::Owned(ref frame, _) => frame.picture_aspect_ratio,
         }
     }
 
     pub fn frame_format_type(&self) -> NDIlib_frame_format_type_e {
         match self {
-            VideoFrame::BorrowedRecv(ref frame, _) | VideoFrame::BorrowedGst(ref frame, _) => {
-                frame.frame_format_type
-            }
+            VideoFrame::BorrowedRecv(ref frame, _)
+            | VideoFrame::BorrowedGst(ref frame, _)
+            | VideoFrame::Owned(ref frame, _) => frame.frame_format_type,
         }
     }
 
     pub fn timecode(&self) -> i64 {
         match self {
-            VideoFrame::BorrowedRecv(ref frame, _) | VideoFrame::BorrowedGst(ref frame, _) => {
-                frame.timecode
-            }
+            VideoFrame::BorrowedRecv(ref frame, _)
+            | VideoFrame::BorrowedGst(ref frame, _)
+            | VideoFrame::Owned(ref frame, _) => frame.timecode,
         }
     }
 
@@ -519,26 +938,76 @@ impl<'a> VideoFrame<'a> {
         ]
         .contains(&fourcc)
         {
-            // FIXME: Unclear if this is correct. Needs to be validated against an actual
-            // interlaced stream
-            let frame_size = if self.frame_format_type()
+            use std::convert::TryFrom;
+
+            // The dimensions and stride come from the sender and are only trusted as far as
+            // they describe a valid frame. All sizes are calculated in 64 bits, which can't
+            // overflow for positive i32 values.
+            if self.xres() <= 0 || self.yres() <= 0 || self.line_stride_or_data_size_in_bytes() <= 0
+            {
+                return None;
+            }
+
+            // Separate fields have the yres of the whole frame but only contain every second
+            // line, with the same line stride as the whole frame
+            let height = if self.frame_format_type()
                 == NDIlib_frame_format_type_e::NDIlib_frame_format_type_field_0
                 || self.frame_format_type()
                     == NDIlib_frame_format_type_e::NDIlib_frame_format_type_field_1
             {
-                self.yres() * self.line_stride_or_data_size_in_bytes() / 2
+                (self.yres() as u64 + 1) / 2
+            } else {
+                self.yres() as u64
+            };
+            let width = self.xres() as u64;
+            let stride = self.line_stride_or_data_size_in_bytes() as u64;
+
+            let (min_stride, frame_size) = if fourcc == NDIlib_FourCC_video_type_NV12 {
+                // Y plane followed by an interleaved UV plane of half the height
+                (width, height * stride + (height + 1) / 2 * stride)
+            } else if [NDIlib_FourCC_video_type_YV12, NDIlib_FourCC_video_type_I420]
+                .contains(&fourcc)
+            {
+                // Y plane followed by two chroma planes of half the height and half the stride
+                (
+                    width,
+                    height * stride + 2 * ((height + 1) / 2 * (stride / 2)),
+                )
+            } else if fourcc == NDIlib_FourCC_video_type_P216 {
+                // 16 bit Y plane followed by an interleaved 16 bit UV plane of the same height
+                (2 * width, 2 * height * stride)
+            } else if fourcc == NDIlib_FourCC_video_type_PA16 {
+                // Like P216 but followed by a 16 bit alpha plane
+                (2 * width, 3 * height * stride)
+            } else if fourcc == NDIlib_FourCC_video_type_UYVA {
+                // UYVY followed by an 8 bit alpha plane with half the line stride
+                (2 * width, height * stride + height * (stride / 2))
+            } else if fourcc == NDIlib_FourCC_video_type_UYVY {
+                (2 * width, height * stride)
             } else {
-                self.yres() * self.line_stride_or_data_size_in_bytes()
+                (4 * width, height * stride)
             };
 
+            if stride < min_stride {
+                return None;
+            }
+
+            // Slices can't be larger than isize::MAX
+            let frame_size = usize::try_from(frame_size)
+                .ok()
+                .filter(|size| *size <= isize::MAX as usize)?;
+
             return unsafe {
                 use std::slice;
                 match self {
                     VideoFrame::BorrowedRecv(ref frame, _)
-                    | VideoFrame::BorrowedGst(ref frame, _) => Some(slice::from_raw_parts(
-                        frame.p_data as *const u8,
-                        frame_size as usize,
-                    )),
+                    | VideoFrame::BorrowedGst(ref frame, _)
+                    | VideoFrame::Owned(ref frame, _) => {
+                        if frame.p_data.is_null() {
+                            return None;
+                        }
+                        Some(slice::from_raw_parts(frame.p_data as *const u8, frame_size))
+                    }
                 }
             };
         }
@@ -558,10 +1027,16 @@ impl<'a> VideoFrame<'a> {
                 use std::slice;
                 match self {
                     VideoFrame::BorrowedRecv(ref frame, _)
-                    | VideoFrame::BorrowedGst(ref frame, _) => Some(slice::from_raw_parts(
-                        frame.p_data as *const u8,
-                        frame.line_stride_or_data_size_in_bytes as usize,
-                    )),
+                    | VideoFrame::BorrowedGst(ref frame, _)
+                    | VideoFrame::Owned(ref frame, _) => {
+                        if frame.p_data.is_null() || frame.line_stride_or_data_size_in_bytes <= 0 {
+                            return None;
+                        }
+                        Some(slice::from_raw_parts(
+                            frame.p_data as *const u8,
+                            frame.line_stride_or_data_size_in_bytes as usize,
+                        ))
+                    }
                 }
             };
         }
@@ -594,7 +1069,12 @@ impl<'a> VideoFrame<'a> {
             }
 
             let data = match self {
-                VideoFrame::BorrowedRecv(ref frame, _) | VideoFrame::BorrowedGst(ref frame, _) => {
+                VideoFrame::BorrowedRecv(ref frame, _)
+                | VideoFrame::BorrowedGst(ref frame, _)
+                | VideoFrame::Owned(ref frame, _) => {
+                    if frame.p_data.is_null() || frame.line_stride_or_data_size_in_bytes <= 0 {
+                        return None;
+                    }
                     slice::from_raw_parts(
                         frame.p_data as *const u8,
                         frame.line_stride_or_data_size_in_bytes as usize,
@@ -644,7 +1124,9 @@ impl<'a> VideoFrame<'a> {
 
     pub fn line_stride_or_data_size_in_bytes(&self) -> i32 {
         match self {
-            VideoFrame::BorrowedRecv(ref frame, _) | VideoFrame::BorrowedGst(ref frame, _) => {
+            VideoFrame::BorrowedRecv(ref frame, _)
+            | VideoFrame::BorrowedGst(ref frame, _)
+            | VideoFrame::Owned(ref frame, _) => {
                 let stride = frame.line_stride_or_data_size_in_bytes;
 
                 if stride != 0 {
@@ -664,21 +1146,24 @@ impl<'a> VideoFrame<'a> {
                     | ndisys::NDIlib_FourCC_video_type_RGBA
                     | ndisys::NDIlib_FourCC_video_type_RGBX => xres,
                     ndisys::NDIlib_FourCC_video_type_P216
-                    | ndisys::NDIlib_FourCC_video_type_PA16 => 2 * xres,
+                    | ndisys::NDIlib_FourCC_video_type_PA16 => xres.saturating_mul(2),
                     _ => 0,
                 }
             }
         }
     }
 
-    pub fn metadata(&self) -> Option<&str> {
+    // Invalid UTF-8 from the source is replaced instead of failing
+    pub fn metadata(&self) -> Option<Cow<'_, str>> {
         unsafe {
             match self {
-                VideoFrame::BorrowedRecv(ref frame, _) | VideoFrame::BorrowedGst(ref frame, _) => {
+                VideoFrame::BorrowedRecv(ref frame, _)
+                | VideoFrame::BorrowedGst(ref frame, _)
+                | VideoFrame::Owned(ref frame, _) => {
                     if frame.p_metadata.is_null() {
                         None
                     } else {
-                        Some(ffi::CStr::from_ptr(frame.p_metadata).to_str().unwrap())
+                        Some(ffi::CStr::from_ptr(frame.p_metadata).to_string_lossy())
                     }
                 }
             }
@@ -687,15 +1172,17 @@ impl<'a> VideoFrame<'a> {
 
     pub fn timestamp(&self) -> i64 {
         match self {
-            VideoFrame::BorrowedRecv(ref frame, _) | VideoFrame::BorrowedGst(ref frame, _) => {
-                frame.timestamp
-            }
+            VideoFrame::BorrowedRecv(ref frame, _)
+            | VideoFrame::BorrowedGst(ref frame, _)
+            | VideoFrame::Owned(ref frame, _) => frame.timestamp,
         }
     }
 
     pub fn as_ptr(&self) -> *const NDIlib_video_frame_v2_t {
         match self {
-            VideoFrame::BorrowedRecv(ref frame, _) | VideoFrame::BorrowedGst(ref frame, _) => frame,
+            VideoFrame::BorrowedRecv(ref frame, _)
+            | VideoFrame::BorrowedGst(ref frame, _)
+            | VideoFrame::Owned(ref frame, _) => frame,
         }
     }
 
@@ -760,6 +1247,7 @@ impl<'a> VideoFrame<'a> {
 
                 ndisys::NDIlib_FourCC_video_type_YV12
             }
+            // The alpha channel is sent as-is with BGRA/RGBA
             gst_video::VideoFormat::Bgra => ndisys::NDIlib_FourCC_video_type_BGRA,
             gst_video::VideoFormat::Bgrx => ndisys::NDIlib_FourCC_video_type_BGRX,
             gst_video::VideoFormat::Rgba => ndisys::NDIlib_FourCC_video_type_RGBA,
@@ -767,6 +1255,40 @@ impl<'a> VideoFrame<'a> {
             _ => return Err(()),
         };
 
+        Self::try_from_video_frame_with_fourcc(frame, format, timecode)
+    }
+
+    // Frame in NDI's UYVA format for a UYVY frame whose buffer contains an alpha plane with one
+    // byte per pixel and half the line stride directly after the lines of the UYVY plane
+    pub fn try_from_uyva_video_frame(
+        frame: &'a gst_video::VideoFrameRef<&'a gst::BufferRef>,
+        timecode: i64,
+    ) -> Result<Self, ()> {
+        if frame.format() != gst_video::VideoFormat::Uyvy {
+            return Err(());
+        }
+
+        let stride = frame.plane_stride()[0] as usize;
+        let size = stride
+            .checked_mul(frame.height() as usize)
+            .and_then(|size| size.checked_add(stride / 2 * frame.height() as usize));
+        match size {
+            Some(size) if frame.buffer().size() >= size => (),
+            _ => return Err(()),
+        }
+
+        Self::try_from_video_frame_with_fourcc(
+            frame,
+            ndisys::NDIlib_FourCC_video_type_UYVA,
+            timecode,
+        )
+    }
+
+    fn try_from_video_frame_with_fourcc(
+        frame: &'a gst_video::VideoFrameRef<&'a gst::BufferRef>,
+        format: NDIlib_FourCC_video_type_e,
+        timecode: i64,
+    ) -> Result<Self, ()> {
         let frame_format_type = match frame.info().interlace_mode() {
             gst_video::VideoInterlaceMode::Progressive => {
                 NDIlib_frame_format_type_e::NDIlib_frame_format_type_progressive
@@ -774,16 +1296,21 @@ impl<'a> VideoFrame<'a> {
             gst_video::VideoInterlaceMode::Interleaved => {
                 NDIlib_frame_format_type_e::NDIlib_frame_format_type_interleaved
             }
-            // FIXME: Is this correct?
+            // With alternate fields every buffer carries a single field and is flagged as
+            // either the top or the bottom field. NDI's field 0 is the top (even) field.
             #[cfg(feature = "interlaced-fields")]
             gst_video::VideoInterlaceMode::Alternate
-                if frame.flags().contains(gst_video::VideoFrameFlags::TFF) =>
+                if frame
+                    .flags()
+                    .contains(gst_video::VideoFrameFlags::TOP_FIELD) =>
             {
                 NDIlib_frame_format_type_e::NDIlib_frame_format_type_field_0
             }
             #[cfg(feature = "interlaced-fields")]
             gst_video::VideoInterlaceMode::Alternate
-                if !frame.flags().contains(gst_video::VideoFrameFlags::TFF) =>
+                if frame
+                    .flags()
+                    .contains(gst_video::VideoFrameFlags::BOTTOM_FIELD) =>
             {
                 NDIlib_frame_format_type_e::NDIlib_frame_format_type_field_1
             }
@@ -805,6 +1332,8 @@ impl<'a> VideoFrame<'a> {
             frame_format_type,
             timecode,
             p_data: frame.plane_data(0).unwrap().as_ptr() as *const ::std::os::raw::c_char,
+            // The actual stride of the first plane, which can be bigger than e.g. 4 * width for
+            // BGRA if upstream pads the lines
             line_stride_or_data_size_in_bytes: frame.plane_stride()[0],
             p_metadata: ptr::null(),
             timestamp: 0,
@@ -885,10 +1414,23 @@ impl<'a> AudioFrame<'a> {
             if [NDIlib_FourCC_audio_type_FLTp].contains(&fourcc) {
                 return match self {
                     AudioFrame::BorrowedRecv(ref frame, _) | AudioFrame::Owned(ref frame, _, _) => {
+                        if frame.p_data.is_null()
+                            || frame.no_channels <= 0
+                            || frame.channel_stride_or_data_size_in_bytes <= 0
+                        {
+                            return None;
+                        }
+
+                        // Can't overflow for positive i32 values
+                        let size = frame.no_channels as u64
+                            * frame.channel_stride_or_data_size_in_bytes as u64;
+                        if size > isize::MAX as u64 {
+                            return None;
+                        }
+
                         Some(slice::from_raw_parts(
                             frame.p_data as *const u8,
-                            (frame.no_channels * frame.channel_stride_or_data_size_in_bytes)
-                                as usize,
+                            size as usize,
                         ))
                     }
                 };
@@ -898,6 +1440,10 @@ impl<'a> AudioFrame<'a> {
             if [NDIlib_FourCC_audio_type_Opus].contains(&fourcc) {
                 return match self {
                     AudioFrame::BorrowedRecv(ref frame, _) | AudioFrame::Owned(ref frame, _, _) => {
+                        if frame.p_data.is_null() || frame.channel_stride_or_data_size_in_bytes <= 0
+                        {
+                            return None;
+                        }
                         Some(slice::from_raw_parts(
                             frame.p_data as *const u8,
                             frame.channel_stride_or_data_size_in_bytes as usize,
@@ -925,6 +1471,9 @@ impl<'a> AudioFrame<'a> {
 
             let data = match self {
                 AudioFrame::BorrowedRecv(ref frame, _) | AudioFrame::Owned(ref frame, _, _) => {
+                    if frame.p_data.is_null() || frame.channel_stride_or_data_size_in_bytes <= 0 {
+                        return None;
+                    }
                     slice::from_raw_parts(
                         frame.p_data as *const u8,
                         frame.channel_stride_or_data_size_in_bytes as usize,
@@ -980,14 +1529,15 @@ impl<'a> AudioFrame<'a> {
         }
     }
 
-    pub fn metadata(&self) -> Option<&str> {
+    // Invalid UTF-8 from the source is replaced instead of failing
+    pub fn metadata(&self) -> Option<Cow<'_, str>> {
         unsafe {
             match self {
                 AudioFrame::BorrowedRecv(ref frame, _) | AudioFrame::Owned(ref frame, _, _) => {
                     if frame.p_metadata.is_null() {
                         None
                     } else {
-                        Some(ffi::CStr::from_ptr(frame.p_metadata).to_str().unwrap())
+                        Some(ffi::CStr::from_ptr(frame.p_metadata).to_string_lossy())
                     }
                 }
             }
@@ -1012,33 +1562,52 @@ impl<'a> AudioFrame<'a> {
         info: &gst_audio::AudioInfo,
         buffer: &gst::BufferRef,
         timecode: i64,
+        reference_level: i32,
     ) -> Result<Self, ()> {
-        if info.format() != gst_audio::AUDIO_FORMAT_F32 {
-            return Err(());
-        }
-
         let map = buffer.map_readable().map_err(|_| ())?;
-        let src_data = map.as_slice_of::<f32>().map_err(|_| ())?;
 
-        let no_samples = src_data.len() as i32 / info.channels() as i32;
-        let channel_stride_or_data_size_in_bytes = no_samples * mem::size_of::<f32>() as i32;
-        let mut dest_data =
-            Vec::<f32>::with_capacity(no_samples as usize * info.channels() as usize);
+        // NDI only handles planar F32 audio so convert S16 here instead of requiring an
+        // audioconvert in front of the sink
+        let mut dest_data;
+        let no_samples;
+        if info.format() == gst_audio::AUDIO_FORMAT_F32 {
+            let src_data = map.as_slice_of::<f32>().map_err(|_| ())?;
+            no_samples = src_data.len() as i32 / info.channels() as i32;
+            dest_data = Vec::<f32>::with_capacity(no_samples as usize * info.channels() as usize);
 
-        assert_eq!(dest_data.capacity(), src_data.len());
+            assert_eq!(dest_data.capacity(), src_data.len());
 
-        unsafe {
-            let dest_ptr = dest_data.as_mut_ptr();
+            unsafe {
+                let dest_ptr = dest_data.as_mut_ptr();
+
+                for (i, samples) in src_data.chunks_exact(info.channels() as usize).enumerate() {
+                    for (c, sample) in samples.iter().enumerate() {
+                        ptr::write(dest_ptr.add(c * no_samples as usize + i), *sample);
+                    }
+                }
+
+                dest_data.set_len(no_samples as usize * info.channels() as usize);
+            }
+        } else if info.format() == gst_audio::AUDIO_FORMAT_S16 {
+            let src_data = map.as_slice_of::<i16>().map_err(|_| ())?;
+            no_samples = src_data.len() as i32 / info.channels() as i32;
+            dest_data = vec![0.0f32; no_samples as usize * info.channels() as usize];
+
+            // The reference level gives how many dB above +4 dBu full range 16 bit audio is,
+            // while 1.0 corresponds to +4 dBu for NDI
+            let scale = 10.0f32.powf(reference_level as f32 / 20.0) / 32768.0;
 
             for (i, samples) in src_data.chunks_exact(info.channels() as usize).enumerate() {
                 for (c, sample) in samples.iter().enumerate() {
-                    ptr::write(dest_ptr.add(c * no_samples as usize + i), *sample);
+                    dest_data[c * no_samples as usize + i] = *sample as f32 * scale;
                 }
             }
-
-            dest_data.set_len(no_samples as usize * info.channels() as usize);
+        } else {
+            return Err(());
         }
 
+        let channel_stride_or_data_size_in_bytes = no_samples * mem::size_of::<f32>() as i32;
+
         let dest = NDIlib_audio_frame_v3_t {
             sample_rate: info.rate() as i32,
             no_channels: info.channels() as i32,
@@ -1109,34 +1678,28 @@ impl<'a> MetadataFrame<'a> {
         }
     }
 
-    pub fn metadata(&self) -> Option<&str> {
+    // Raw metadata without the NUL terminator. Sources are supposed to send UTF-8 XML but
+    // nothing guarantees that.
+    pub fn metadata_bytes(&self) -> Option<&[u8]> {
         unsafe {
             match self {
-                MetadataFrame::Owned(_, ref metadata) => {
-                    metadata.as_ref().map(|s| s.to_str().unwrap())
-                }
+                MetadataFrame::Owned(_, ref metadata) => metadata.as_ref().map(|s| s.as_bytes()),
                 MetadataFrame::Borrowed(ref frame, _) => {
                     if frame.p_data.is_null() || frame.length == 0 {
                         None
-                    } else if frame.length != 0 {
-                        use std::slice;
-
-                        Some(
-                            ffi::CStr::from_bytes_with_nul_unchecked(slice::from_raw_parts(
-                                frame.p_data as *const u8,
-                                frame.length as usize,
-                            ))
-                            .to_str()
-                            .unwrap(),
-                        )
                     } else {
-                        Some(ffi::CStr::from_ptr(frame.p_data).to_str().unwrap())
+                        Some(ffi::CStr::from_ptr(frame.p_data).to_bytes())
                     }
                 }
             }
         }
     }
 
+    // Invalid UTF-8 from the source is replaced instead of failing
+    pub fn metadata(&self) -> Option<Cow<'_, str>> {
+        self.metadata_bytes().map(String::from_utf8_lossy)
+    }
+
     pub fn as_ptr(&self) -> *const NDIlib_metadata_frame_t {
         match self {
             MetadataFrame::Owned(ref frame, _) => frame,
@@ -1171,6 +1734,16 @@ impl<'a> Drop for MetadataFrame<'a> {
 #[derive(Debug, Clone)]
 pub struct Queue(NDIlib_recv_queue_t);
 
+impl Default for Queue {
+    fn default() -> Self {
+        Queue(NDIlib_recv_queue_t {
+            video_frames: 0,
+            audio_frames: 0,
+            metadata_frames: 0,
+        })
+    }
+}
+
 impl Queue {
     pub fn audio_frames(&self) -> i32 {
         self.0.audio_frames
@@ -1182,3 +1755,312 @@ impl Queue {
         self.0.metadata_frames
     }
 }
+
+// Number of frames dropped from the count every minute except every tenth minute for drop-frame
+// timecodes, or None if the framerate doesn't use drop-frame timecodes
+fn drop_frames(fps: gst::Fraction) -> Option<u64> {
+    match (fps.numer(), fps.denom()) {
+        (30000, 1001) => Some(2),
+        (60000, 1001) => Some(4),
+        _ => None,
+    }
+}
+
+// Converts a number of frames into the frame number shown by a drop-frame timecode, which skips
+// the given number of frame numbers at the start of every minute except every tenth minute
+fn drop_frame_number(frames: u64, nominal_fps: u64, drop: u64) -> u64 {
+    // 10 minutes have 9 minutes with dropped frames
+    let frames_per_minute = nominal_fps * 60 - drop;
+    let frames_per_10_minutes = nominal_fps * 600 - 9 * drop;
+    let tens = frames / frames_per_10_minutes;
+    let remainder = frames % frames_per_10_minutes;
+
+    let mut frames = frames + 9 * drop * tens;
+    if remainder > drop {
+        frames += drop * ((remainder - drop) / frames_per_minute);
+    }
+    frames
+}
+
+// Converts an NDI timecode in 100ns units into a SMPTE timecode at the given framerate. For
+// 29.97 and 59.94 fps this uses drop-frame timecodes so that they stay in sync with the
+// wall-clock time.
+pub fn timecode_to_video_time_code(
+    timecode: i64,
+    fps: gst::Fraction,
+) -> Option<gst_video::ValidVideoTimeCode> {
+    if timecode < 0 || fps.numer() <= 0 || fps.denom() <= 0 {
+        return None;
+    }
+
+    let nominal_fps = (fps.numer() as u64 + fps.denom() as u64 / 2) / fps.denom() as u64;
+    let mut frames =
+        (timecode as u128 * fps.numer() as u128 / (fps.denom() as u128 * 10_000_000)) as u64;
+
+    let mut flags = gst_video::VideoTimeCodeFlags::empty();
+    if let Some(drop) = drop_frames(fps) {
+        flags |= gst_video::VideoTimeCodeFlags::DROP_FRAME;
+        frames = drop_frame_number(frames, nominal_fps, drop);
+    }
+
+    let frame = frames % nominal_fps;
+    let seconds = frames / nominal_fps;
+
+    gst_video::ValidVideoTimeCode::new(
+        fps,
+        None,
+        flags,
+        ((seconds / 3600) % 24) as u32,
+        ((seconds / 60) % 60) as u32,
+        (seconds % 60) as u32,
+        frame as u32,
+        0,
+    )
+    .ok()
+}
+
+// Converts a SMPTE timecode into an NDI timecode in 100ns units, taking the dropped frame
+// numbers of drop-frame timecodes into account
+pub fn video_time_code_to_timecode(tc: &gst_video::ValidVideoTimeCode) -> i64 {
+    let fps = tc.fps();
+    if fps.numer() <= 0 || fps.denom() <= 0 {
+        return NDIlib_send_timecode_synthesize;
+    }
+
+    let nominal_fps = (fps.numer() as u64 + fps.denom() as u64 / 2) / fps.denom() as u64;
+    let minutes = tc.hours() as u64 * 60 + tc.minutes() as u64;
+    let mut frames = (minutes * 60 + tc.seconds() as u64) * nominal_fps + tc.frames() as u64;
+
+    if tc
+        .flags()
+        .contains(gst_video::VideoTimeCodeFlags::DROP_FRAME)
+    {
+        if let Some(drop) = drop_frames(fps) {
+            frames -= drop * (minutes - minutes / 10);
+        }
+    }
+
+    (frames as u128 * fps.denom() as u128 * 10_000_000 / fps.numer() as u128) as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Hours, minutes, seconds and frames shown for the frame number
+    fn label(frame_number: u64, nominal_fps: u64) -> (u64, u64, u64, u64) {
+        let seconds = frame_number / nominal_fps;
+        (
+            seconds / 3600,
+            (seconds / 60) % 60,
+            seconds % 60,
+            frame_number % nominal_fps,
+        )
+    }
+
+    #[test]
+    fn drop_frame_number_2997() {
+        let drop_frame = |frames| label(drop_frame_number(frames, 30, 2), 30);
+
+        assert_eq!(drop_frame(0), (0, 0, 0, 0));
+        assert_eq!(drop_frame(1799), (0, 0, 59, 29));
+        // ;00 and ;01 are dropped at the start of every minute
+        assert_eq!(drop_frame(1800), (0, 1, 0, 2));
+        assert_eq!(drop_frame(3597), (0, 1, 59, 29));
+        assert_eq!(drop_frame(3598), (0, 2, 0, 2));
+        // ...except for every tenth minute
+        assert_eq!(drop_frame(17981), (0, 9, 59, 29));
+        assert_eq!(drop_frame(17982), (0, 10, 0, 0));
+        assert_eq!(drop_frame(17983), (0, 10, 0, 1));
+        assert_eq!(drop_frame(19781), (0, 10, 59, 29));
+        assert_eq!(drop_frame(19782), (0, 11, 0, 2));
+        assert_eq!(drop_frame(107_892), (1, 0, 0, 0));
+    }
+
+    #[test]
+    fn drop_frame_number_5994() {
+        let drop_frame = |frames| label(drop_frame_number(frames, 60, 4), 60);
+
+        assert_eq!(drop_frame(3599), (0, 0, 59, 59));
+        assert_eq!(drop_frame(3600), (0, 1, 0, 4));
+        assert_eq!(drop_frame(35_963), (0, 9, 59, 59));
+        assert_eq!(drop_frame(35_964), (0, 10, 0, 0));
+        assert_eq!(drop_frame(215_784), (1, 0, 0, 0));
+    }
+
+    // NDI timecode of the start of the given frame at 30000/1001 fps
+    fn timecode_2997(frames: i64) -> i64 {
+        (frames * 1001 * 10_000_000 + 29_999) / 30_000
+    }
+
+    #[test]
+    fn video_time_code_drop_frame() {
+        gst::init().unwrap();
+
+        let fps = gst::Fraction::new(30000, 1001);
+        for (frames, (hours, minutes, seconds, frame)) in [
+            (1799, (0, 0, 59, 29)),
+            (1800, (0, 1, 0, 2)),
+            (17_982, (0, 10, 0, 0)),
+            (107_892, (1, 0, 0, 0)),
+        ] {
+            let tc = timecode_to_video_time_code(timecode_2997(frames), fps).unwrap();
+            assert!(tc
+                .flags()
+                .contains(gst_video::VideoTimeCodeFlags::DROP_FRAME));
+            assert_eq!(
+                (tc.hours(), tc.minutes(), tc.seconds(), tc.frames()),
+                (hours, minutes, seconds, frame)
+            );
+
+            assert_eq!(
+                video_time_code_to_timecode(&tc),
+                frames * 1001 * 10_000_000 / 30_000
+            );
+        }
+    }
+
+    #[test]
+    fn video_time_code_non_drop_frame() {
+        gst::init().unwrap();
+
+        let fps = gst::Fraction::new(25, 1);
+        let tc = timecode_to_video_time_code(10_000_000 * 3661 + 400_000 * 3, fps).unwrap();
+        assert!(tc.flags().is_empty());
+        assert_eq!(
+            (tc.hours(), tc.minutes(), tc.seconds(), tc.frames()),
+            (1, 1, 1, 3)
+        );
+        assert_eq!(
+            video_time_code_to_timecode(&tc),
+            10_000_000 * 3661 + 400_000 * 3
+        );
+    }
+
+    // Size of the data of a 6x9 frame with the given line stride
+    fn video_data_size(
+        fourcc: NDIlib_FourCC_video_type_e,
+        frame_format_type: NDIlib_frame_format_type_e,
+        line_stride: i32,
+    ) -> Option<usize> {
+        let data = vec![0u8; 1024];
+        let frame = VideoFrame::Owned(
+            NDIlib_video_frame_v2_t {
+                xres: 6,
+                yres: 9,
+                FourCC: fourcc,
+                frame_rate_N: 30,
+                frame_rate_D: 1,
+                picture_aspect_ratio: 6.0 / 9.0,
+                frame_format_type,
+                timecode: 0,
+                p_data: data.as_ptr() as *const _,
+                line_stride_or_data_size_in_bytes: line_stride,
+                p_metadata: ptr::null(),
+                timestamp: 0,
+            },
+            data,
+        );
+
+        frame.data().map(|data| data.len())
+    }
+
+    #[test]
+    fn video_data_size_fields() {
+        use NDIlib_frame_format_type_e::*;
+
+        for frame_format_type in [
+            NDIlib_frame_format_type_progressive,
+            NDIlib_frame_format_type_interleaved,
+        ] {
+            assert_eq!(
+                video_data_size(NDIlib_FourCC_video_type_UYVY, frame_format_type, 16),
+                Some(9 * 16)
+            );
+            assert_eq!(
+                video_data_size(NDIlib_FourCC_video_type_NV12, frame_format_type, 8),
+                Some(9 * 8 + 5 * 8)
+            );
+            assert_eq!(
+                video_data_size(NDIlib_FourCC_video_type_I420, frame_format_type, 8),
+                Some(9 * 8 + 2 * 5 * 4)
+            );
+        }
+
+        // Fields contain every second line, rounded up, with the line stride of the frame
+        for frame_format_type in [
+            NDIlib_frame_format_type_field_0,
+            NDIlib_frame_format_type_field_1,
+        ] {
+            assert_eq!(
+                video_data_size(NDIlib_FourCC_video_type_UYVY, frame_format_type, 16),
+                Some(5 * 16)
+            );
+            assert_eq!(
+                video_data_size(NDIlib_FourCC_video_type_NV12, frame_format_type, 8),
+                Some(5 * 8 + 3 * 8)
+            );
+            assert_eq!(
+                video_data_size(NDIlib_FourCC_video_type_I420, frame_format_type, 8),
+                Some(5 * 8 + 2 * 3 * 4)
+            );
+            // The stride is still checked against the width
+            assert_eq!(
+                video_data_size(NDIlib_FourCC_video_type_UYVY, frame_format_type, 11),
+                None
+            );
+        }
+    }
+
+    #[test]
+    fn shared_find_sources() {
+        use crate::testing::FakeFind;
+
+        let fake = FakeFind::new(&[("HOST (Camera 1)", "10.0.0.1:5961")]);
+        let find = SharedFind::from_source(fake.clone());
+
+        assert!(find.wait_for_sources(100));
+        assert_eq!(
+            find.current_sources(),
+            [Source::new("HOST (Camera 1)", "10.0.0.1:5961").unwrap()]
+        );
+        // Nothing changed since the last wait
+        assert!(!find.wait_for_sources(10));
+
+        // Changes are noticed by waits that are already in progress
+        let changer = std::thread::spawn(move || {
+            std::thread::sleep(time::Duration::from_millis(50));
+            fake.set_sources(&[]);
+        });
+        assert!(find.wait_for_sources(5000));
+        assert!(find.current_sources().is_empty());
+        changer.join().unwrap();
+    }
+
+    #[test]
+    fn shared_find_context() {
+        crate::testing::init();
+
+        let find = SharedFind::from_source(crate::testing::FakeFind::new(&[(
+            "HOST (Camera 1)",
+            "10.0.0.1:5961",
+        )]));
+        let context = find.to_context();
+        assert_eq!(context.context_type(), CONTEXT_TYPE);
+
+        // Both refer to the same find instance
+        let shared = SharedFind::from_context(&context).unwrap();
+        assert!(Arc::ptr_eq(&find.0, &shared.0));
+        assert!(SharedFind::from_context(&gst::Context::new("other", true)).is_none());
+    }
+
+    #[test]
+    fn send_builder_nul() {
+        // Fails instead of panicking, independent of whether the NDI runtime is available
+        assert!(SendInstance::builder("test")
+            .groups(Some("Public\0Private"))
+            .build()
+            .is_none());
+        assert!(SendInstance::builder("te\0st").build().is_none());
+    }
+}
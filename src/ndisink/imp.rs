@@ -1,15 +1,20 @@
 use glib::subclass::prelude::*;
 use gst::prelude::*;
 use gst::subclass::prelude::*;
-use gst::{gst_debug, gst_error, gst_info, gst_trace};
+use gst::{gst_debug, gst_error, gst_info, gst_trace, gst_warning};
 use gst_base::prelude::*;
 use gst_base::subclass::prelude::*;
 
-use std::sync::Mutex;
+use std::cmp;
+use std::collections::VecDeque;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time;
 
 use once_cell::sync::Lazy;
 
-use crate::ndi::SendInstance;
+use crate::ndi::{FrameSink, MetadataFrame, SendInstance};
+use crate::SendTimecodeMode;
 
 static DEFAULT_SENDER_NDI_NAME: Lazy<String> = Lazy::new(|| {
     format!(
@@ -22,25 +27,133 @@ static DEFAULT_SENDER_NDI_NAME: Lazy<String> = Lazy::new(|| {
 #[derive(Debug)]
 struct Settings {
     ndi_name: String,
+    groups: Option<String>,
+    product_name: Option<String>,
+    manufacturer: Option<String>,
+    connection_metadata: Option<String>,
+    timecode_mode: SendTimecodeMode,
+    reference_level: i32,
+    keepalive_interval: u32,
+    clock_audio: bool,
+    clock_video: bool,
+    send_queue_length: u32,
+    send_queue_leaky: bool,
+    control_requests: bool,
 }
 
 impl Default for Settings {
     fn default() -> Self {
         Settings {
             ndi_name: DEFAULT_SENDER_NDI_NAME.clone(),
+            groups: None,
+            product_name: None,
+            manufacturer: None,
+            connection_metadata: None,
+            timecode_mode: SendTimecodeMode::FromPts,
+            reference_level: 0,
+            keepalive_interval: 0,
+            clock_audio: false,
+            clock_video: false,
+            send_queue_length: 0,
+            send_queue_leaky: false,
+            control_requests: false,
         }
     }
 }
 
 struct State {
-    send: SendInstance,
+    send: Box<dyn FrameSink>,
     video_info: Option<gst_video::VideoInfo>,
     audio_info: Option<gst_audio::AudioInfo>,
+    last_send: time::Instant,
+    // Name and clocking the send instance was created with
+    ndi_name: String,
+    clock_audio: bool,
+    clock_video: bool,
+    // Pool for copying planar frames whose planes are not contiguous in memory, together with
+    // the contiguous video info its buffers are used with
+    copy_pool: Option<(gst_video::VideoInfo, gst::BufferPool)>,
+}
+
+// Video buffers waiting to be sent by the pacer, together with the info and timecode to send them
+// with
+#[derive(Default)]
+struct SendQueue {
+    items: VecDeque<(gst::Buffer, gst_video::VideoInfo, i64)>,
+    // The pacer popped a buffer and is sending it right now
+    in_flight: bool,
+    flushing: bool,
+    // Error from sending a queued buffer, returned from the next render call
+    error: Option<gst::FlowError>,
+}
+
+// Thread that calls a function in regular intervals until it's stopped or the function returns
+// false
+struct Worker {
+    shutdown: Arc<(Mutex<bool>, Condvar)>,
+    thread: thread::JoinHandle<()>,
+}
+
+impl Worker {
+    fn spawn<F>(element: &super::NdiSink, name: &str, interval: time::Duration, mut func: F) -> Self
+    where
+        F: FnMut(&super::NdiSink) -> bool + Send + 'static,
+    {
+        let shutdown = Arc::new((Mutex::new(false), Condvar::new()));
+        let shutdown_clone = shutdown.clone();
+        let element_weak = element.downgrade();
+
+        let thread = thread::Builder::new()
+            .name(format!("{}-{}", name, element.name()))
+            .spawn(move || {
+                let (lock, cond) = &*shutdown_clone;
+                let mut shutdown = lock.lock().unwrap();
+                let mut next = time::Instant::now() + interval;
+                loop {
+                    let now = time::Instant::now();
+                    if now < next && !*shutdown {
+                        shutdown = cond.wait_timeout(shutdown, next - now).unwrap().0;
+                        continue;
+                    }
+                    if *shutdown {
+                        break;
+                    }
+                    // Stay on the interval's grid instead of drifting by the time the function
+                    // takes, but don't try to catch up on calls that were missed
+                    next = cmp::max(next + interval, now);
+
+                    let element = match element_weak.upgrade() {
+                        Some(element) => element,
+                        None => break,
+                    };
+
+                    if !func(&element) {
+                        break;
+                    }
+                }
+            })
+            .expect("Failed to spawn worker thread");
+
+        Worker { shutdown, thread }
+    }
+
+    fn stop(self) {
+        let (lock, cond) = &*self.shutdown;
+        *lock.lock().unwrap() = true;
+        cond.notify_one();
+        let _ = self.thread.join();
+    }
 }
 
 pub struct NdiSink {
     settings: Mutex<Settings>,
     state: Mutex<Option<State>>,
+    keepalive: Mutex<Option<Worker>>,
+    control: Mutex<Option<Worker>>,
+    send_queue: Mutex<SendQueue>,
+    send_queue_cond: Condvar,
+    // Sends one queued video buffer per frame duration, the interval it was started with
+    pacer: Mutex<Option<(time::Duration, Worker)>>,
 }
 
 static CAT: Lazy<gst::DebugCategory> = Lazy::new(|| {
@@ -57,6 +170,11 @@ impl ObjectSubclass for NdiSink {
         Self {
             settings: Mutex::new(Default::default()),
             state: Mutex::new(Default::default()),
+            keepalive: Mutex::new(None),
+            control: Mutex::new(None),
+            send_queue: Mutex::new(SendQueue::default()),
+            send_queue_cond: Condvar::new(),
+            pacer: Mutex::new(None),
         }
     }
 }
@@ -64,31 +182,233 @@ impl ObjectSubclass for NdiSink {
 impl ObjectImpl for NdiSink {
     fn properties() -> &'static [glib::ParamSpec] {
         static PROPERTIES: Lazy<Vec<glib::ParamSpec>> = Lazy::new(|| {
-            vec![glib::ParamSpecString::new(
-                "ndi-name",
-                "NDI Name",
-                "NDI Name to use",
-                Some(DEFAULT_SENDER_NDI_NAME.as_ref()),
-                glib::ParamFlags::READWRITE,
-            )]
+            vec![
+                glib::ParamSpecString::new(
+                    "ndi-name",
+                    "NDI Name",
+                    "NDI Name to use. Control characters and surrounding whitespace are removed",
+                    Some(DEFAULT_SENDER_NDI_NAME.as_ref()),
+                    glib::ParamFlags::READWRITE | gst::PARAM_FLAG_MUTABLE_READY,
+                ),
+                glib::ParamSpecString::new(
+                    "groups",
+                    "Groups",
+                    "Comma separated list of NDI groups to place this source in",
+                    None,
+                    glib::ParamFlags::READWRITE,
+                ),
+                glib::ParamSpecString::new(
+                    "product-name",
+                    "Product Name",
+                    "Product name to advertise to receivers in the connection metadata",
+                    None,
+                    glib::ParamFlags::READWRITE,
+                ),
+                glib::ParamSpecString::new(
+                    "manufacturer",
+                    "Manufacturer",
+                    "Manufacturer to advertise to receivers in the connection metadata",
+                    None,
+                    glib::ParamFlags::READWRITE,
+                ),
+                glib::ParamSpecString::new(
+                    "connection-metadata",
+                    "Connection Metadata",
+                    "Raw XML connection metadata to send to every receiver that connects",
+                    None,
+                    glib::ParamFlags::READWRITE,
+                ),
+                glib::ParamSpecEnum::new(
+                    "timecode-mode",
+                    "Timecode Mode",
+                    "How the timecode of the sent frames is generated",
+                    SendTimecodeMode::static_type(),
+                    SendTimecodeMode::FromPts as i32,
+                    glib::ParamFlags::READWRITE,
+                ),
+                glib::ParamSpecInt::new(
+                    "reference-level",
+                    "Reference Level",
+                    "dB above the +4 dBu reference level that full range 16 bit audio corresponds to",
+                    -100,
+                    100,
+                    0,
+                    glib::ParamFlags::READWRITE,
+                ),
+                glib::ParamSpecUInt::new(
+                    "keepalive-interval",
+                    "Keepalive Interval",
+                    "Send metadata to receivers if no frames were sent for this many ms (0 = disabled)",
+                    0,
+                    u32::MAX,
+                    0,
+                    glib::ParamFlags::READWRITE,
+                ),
+                glib::ParamSpecBoolean::new(
+                    "clock-audio",
+                    "Clock Audio",
+                    "Let the NDI SDK rate-limit sending to the audio sample rate. Changes while \
+                     running take effect on the next PAUSED to PLAYING state change by \
+                     recreating the sender, usually together with sync=false",
+                    false,
+                    glib::ParamFlags::READWRITE,
+                ),
+                glib::ParamSpecBoolean::new(
+                    "clock-video",
+                    "Clock Video",
+                    "Let the NDI SDK rate-limit sending to the video frame rate. Changes while \
+                     running take effect on the next PAUSED to PLAYING state change by \
+                     recreating the sender, usually together with sync=false",
+                    false,
+                    glib::ParamFlags::READWRITE,
+                ),
+                glib::ParamSpecUInt::new(
+                    "send-queue-length",
+                    "Send Queue Length",
+                    "Number of video frames to queue for sending them evenly paced at the frame \
+                     rate of the caps, smoothing out bursty input (0 = disabled)",
+                    0,
+                    u32::MAX,
+                    0,
+                    glib::ParamFlags::READWRITE | gst::PARAM_FLAG_MUTABLE_READY,
+                ),
+                glib::ParamSpecBoolean::new(
+                    "send-queue-leaky",
+                    "Send Queue Leaky",
+                    "Drop the oldest queued video frame instead of waiting when the send queue \
+                     is full",
+                    false,
+                    glib::ParamFlags::READWRITE | gst::PARAM_FLAG_MUTABLE_READY,
+                ),
+                glib::ParamSpecBoolean::new(
+                    "control-requests",
+                    "Control Requests",
+                    "Receive PTZ and recording requests from receivers and post them as \
+                     ptz-request and recording-request element messages",
+                    false,
+                    glib::ParamFlags::READWRITE | gst::PARAM_FLAG_MUTABLE_READY,
+                ),
+                glib::ParamSpecString::new(
+                    "ndi-lib-version",
+                    "NDI Library Version",
+                    "Version of the NDI runtime library in use",
+                    None,
+                    glib::ParamFlags::READABLE,
+                ),
+            ]
         });
 
         PROPERTIES.as_ref()
     }
 
+    fn signals() -> &'static [glib::subclass::Signal] {
+        static SIGNALS: Lazy<Vec<glib::subclass::Signal>> = Lazy::new(|| {
+            vec![glib::subclass::Signal::builder(
+                "send-metadata",
+                &[String::static_type().into()],
+                bool::static_type().into(),
+            )
+            .action()
+            .class_handler(|_, args| {
+                let element = args[0].get::<super::NdiSink>().expect("signal arg");
+                let metadata = args[1].get::<String>().expect("signal arg");
+                let imp = NdiSink::from_instance(&element);
+
+                Some(imp.send_metadata(&element, &metadata).to_value())
+            })
+            .build()]
+        });
+
+        SIGNALS.as_ref()
+    }
+
     fn set_property(
         &self,
-        _obj: &Self::Type,
+        obj: &Self::Type,
         _id: usize,
         value: &glib::Value,
         pspec: &glib::ParamSpec,
     ) {
         match pspec.name() {
             "ndi-name" => {
+                // The name is only used when creating the send instance
+                if self.state.lock().unwrap().is_some() {
+                    gst_warning!(CAT, obj: obj, "Can't change NDI name while running");
+                    return;
+                }
+
+                let ndi_name = match value.get::<Option<String>>().unwrap() {
+                    None => DEFAULT_SENDER_NDI_NAME.clone(),
+                    Some(name) => match sanitize_ndi_name(&name) {
+                        None => {
+                            gst_warning!(CAT, obj: obj, "Ignoring invalid NDI name {:?}", name);
+                            return;
+                        }
+                        Some(sanitized) => {
+                            if sanitized != name {
+                                gst_warning!(
+                                    CAT,
+                                    obj: obj,
+                                    "Sanitized NDI name {:?} to {:?}",
+                                    name,
+                                    sanitized
+                                );
+                            }
+                            sanitized
+                        }
+                    },
+                };
+
+                let mut settings = self.settings.lock().unwrap();
+                settings.ndi_name = ndi_name;
+            }
+            "groups" => {
+                let mut settings = self.settings.lock().unwrap();
+                settings.groups = value.get().unwrap();
+            }
+            "product-name" => {
+                let mut settings = self.settings.lock().unwrap();
+                settings.product_name = value.get().unwrap();
+            }
+            "manufacturer" => {
                 let mut settings = self.settings.lock().unwrap();
-                settings.ndi_name = value
-                    .get::<String>()
-                    .unwrap_or_else(|_| DEFAULT_SENDER_NDI_NAME.clone());
+                settings.manufacturer = value.get().unwrap();
+            }
+            "connection-metadata" => {
+                let mut settings = self.settings.lock().unwrap();
+                settings.connection_metadata = value.get().unwrap();
+            }
+            "timecode-mode" => {
+                let mut settings = self.settings.lock().unwrap();
+                settings.timecode_mode = value.get().unwrap();
+            }
+            "reference-level" => {
+                let mut settings = self.settings.lock().unwrap();
+                settings.reference_level = value.get().unwrap();
+            }
+            "keepalive-interval" => {
+                let mut settings = self.settings.lock().unwrap();
+                settings.keepalive_interval = value.get().unwrap();
+            }
+            "clock-audio" => {
+                let mut settings = self.settings.lock().unwrap();
+                settings.clock_audio = value.get().unwrap();
+            }
+            "clock-video" => {
+                let mut settings = self.settings.lock().unwrap();
+                settings.clock_video = value.get().unwrap();
+            }
+            "send-queue-length" => {
+                let mut settings = self.settings.lock().unwrap();
+                settings.send_queue_length = value.get().unwrap();
+            }
+            "send-queue-leaky" => {
+                let mut settings = self.settings.lock().unwrap();
+                settings.send_queue_leaky = value.get().unwrap();
+            }
+            "control-requests" => {
+                let mut settings = self.settings.lock().unwrap();
+                settings.control_requests = value.get().unwrap();
             }
             _ => unimplemented!(),
         };
@@ -100,6 +420,55 @@ impl ObjectImpl for NdiSink {
                 let settings = self.settings.lock().unwrap();
                 settings.ndi_name.to_value()
             }
+            "groups" => {
+                let settings = self.settings.lock().unwrap();
+                settings.groups.to_value()
+            }
+            "product-name" => {
+                let settings = self.settings.lock().unwrap();
+                settings.product_name.to_value()
+            }
+            "manufacturer" => {
+                let settings = self.settings.lock().unwrap();
+                settings.manufacturer.to_value()
+            }
+            "connection-metadata" => {
+                let settings = self.settings.lock().unwrap();
+                settings.connection_metadata.to_value()
+            }
+            "timecode-mode" => {
+                let settings = self.settings.lock().unwrap();
+                settings.timecode_mode.to_value()
+            }
+            "reference-level" => {
+                let settings = self.settings.lock().unwrap();
+                settings.reference_level.to_value()
+            }
+            "keepalive-interval" => {
+                let settings = self.settings.lock().unwrap();
+                settings.keepalive_interval.to_value()
+            }
+            "clock-audio" => {
+                let settings = self.settings.lock().unwrap();
+                settings.clock_audio.to_value()
+            }
+            "clock-video" => {
+                let settings = self.settings.lock().unwrap();
+                settings.clock_video.to_value()
+            }
+            "send-queue-length" => {
+                let settings = self.settings.lock().unwrap();
+                settings.send_queue_length.to_value()
+            }
+            "send-queue-leaky" => {
+                let settings = self.settings.lock().unwrap();
+                settings.send_queue_leaky.to_value()
+            }
+            "control-requests" => {
+                let settings = self.settings.lock().unwrap();
+                settings.control_requests.to_value()
+            }
+            "ndi-lib-version" => crate::ndi::version().to_value(),
             _ => unimplemented!(),
         }
     }
@@ -123,37 +492,55 @@ impl ElementImpl for NdiSink {
 
     fn pad_templates() -> &'static [gst::PadTemplate] {
         static PAD_TEMPLATES: Lazy<Vec<gst::PadTemplate>> = Lazy::new(|| {
-            let caps = gst::Caps::builder_full()
+            let video_structure = gst::Structure::builder("video/x-raw")
+                .field(
+                    "format",
+                    &gst::List::new(&[
+                        &gst_video::VideoFormat::Uyvy.to_str(),
+                        &gst_video::VideoFormat::I420.to_str(),
+                        &gst_video::VideoFormat::Nv12.to_str(),
+                        &gst_video::VideoFormat::Nv21.to_str(),
+                        &gst_video::VideoFormat::Yv12.to_str(),
+                        &gst_video::VideoFormat::Bgra.to_str(),
+                        &gst_video::VideoFormat::Bgrx.to_str(),
+                        &gst_video::VideoFormat::Rgba.to_str(),
+                        &gst_video::VideoFormat::Rgbx.to_str(),
+                        &gst_video::VideoFormat::Ayuv.to_str(),
+                        &gst_video::VideoFormat::Vuya.to_str(),
+                    ]),
+                )
+                .field("width", &gst::IntRange::<i32>::new(1, std::i32::MAX))
+                .field("height", &gst::IntRange::<i32>::new(1, std::i32::MAX))
+                .field(
+                    "framerate",
+                    &gst::FractionRange::new(
+                        gst::Fraction::new(0, 1),
+                        gst::Fraction::new(std::i32::MAX, 1),
+                    ),
+                )
+                .build();
+
+            let caps = gst::Caps::builder_full().structure(video_structure.clone());
+            // Alternate fields are only negotiated with the format:Interlaced caps feature
+            #[cfg(feature = "interlaced-fields")]
+            let caps = caps.structure_with_features(
+                {
+                    let mut s = video_structure;
+                    s.set("interlace-mode", "alternate");
+                    s
+                },
+                gst::CapsFeatures::new(&[*gst_video::CAPS_FEATURE_FORMAT_INTERLACED]),
+            );
+            let caps = caps
                 .structure(
-                    gst::Structure::builder("video/x-raw")
+                    gst::Structure::builder("audio/x-raw")
                         .field(
                             "format",
                             &gst::List::new(&[
-                                &gst_video::VideoFormat::Uyvy.to_str(),
-                                &gst_video::VideoFormat::I420.to_str(),
-                                &gst_video::VideoFormat::Nv12.to_str(),
-                                &gst_video::VideoFormat::Nv21.to_str(),
-                                &gst_video::VideoFormat::Yv12.to_str(),
-                                &gst_video::VideoFormat::Bgra.to_str(),
-                                &gst_video::VideoFormat::Bgrx.to_str(),
-                                &gst_video::VideoFormat::Rgba.to_str(),
-                                &gst_video::VideoFormat::Rgbx.to_str(),
+                                &gst_audio::AUDIO_FORMAT_F32.to_str(),
+                                &gst_audio::AUDIO_FORMAT_S16.to_str(),
                             ]),
                         )
-                        .field("width", &gst::IntRange::<i32>::new(1, std::i32::MAX))
-                        .field("height", &gst::IntRange::<i32>::new(1, std::i32::MAX))
-                        .field(
-                            "framerate",
-                            &gst::FractionRange::new(
-                                gst::Fraction::new(0, 1),
-                                gst::Fraction::new(std::i32::MAX, 1),
-                            ),
-                        )
-                        .build(),
-                )
-                .structure(
-                    gst::Structure::builder("audio/x-raw")
-                        .field("format", &gst_audio::AUDIO_FORMAT_F32.to_str())
                         .field("rate", &gst::IntRange::<i32>::new(1, i32::MAX))
                         .field("channels", &gst::IntRange::<i32>::new(1, i32::MAX))
                         .field("layout", &"interleaved")
@@ -173,34 +560,75 @@ impl ElementImpl for NdiSink {
 
         PAD_TEMPLATES.as_ref()
     }
+
+    fn change_state(
+        &self,
+        element: &Self::Type,
+        transition: gst::StateChange,
+    ) -> Result<gst::StateChangeSuccess, gst::StateChangeError> {
+        if transition == gst::StateChange::PausedToPlaying {
+            self.update_clocking(element).map_err(|err| {
+                element.post_error_message(err);
+                gst::StateChangeError
+            })?;
+        }
+
+        self.parent_change_state(element, transition)
+    }
 }
 
 impl BaseSinkImpl for NdiSink {
     fn start(&self, element: &Self::Type) -> Result<(), gst::ErrorMessage> {
+        if !crate::ndi::is_initialized() {
+            return Err(crate::ndi::initialization_error());
+        }
+
         let mut state_storage = self.state.lock().unwrap();
         let settings = self.settings.lock().unwrap();
 
-        let send = SendInstance::builder(&settings.ndi_name)
-            .build()
-            .ok_or_else(|| {
-                gst::error_msg!(
-                    gst::ResourceError::OpenWrite,
-                    ["Could not create send instance"]
-                )
-            })?;
+        let send = self.create_send(element, &settings, &settings.ndi_name)?;
 
         let state = State {
             send,
             video_info: None,
             audio_info: None,
+            last_send: time::Instant::now(),
+            ndi_name: settings.ndi_name.clone(),
+            clock_audio: settings.clock_audio,
+            clock_video: settings.clock_video,
+            copy_pool: None,
         };
         *state_storage = Some(state);
+
+        if settings.keepalive_interval > 0 {
+            *self.keepalive.lock().unwrap() = Some(self.start_keepalive(
+                element,
+                time::Duration::from_millis(settings.keepalive_interval as u64),
+            ));
+        }
+
+        // Polls for requests, so only started if anybody is interested in them
+        if settings.control_requests {
+            *self.control.lock().unwrap() = Some(self.start_control(element));
+        }
+
         gst_info!(CAT, obj: element, "Started");
 
         Ok(())
     }
 
     fn stop(&self, element: &Self::Type) -> Result<(), gst::ErrorMessage> {
+        if let Some(keepalive) = self.keepalive.lock().unwrap().take() {
+            keepalive.stop();
+        }
+        if let Some(control) = self.control.lock().unwrap().take() {
+            control.stop();
+        }
+        if let Some((_, pacer)) = self.pacer.lock().unwrap().take() {
+            pacer.stop();
+        }
+        *self.send_queue.lock().unwrap() = SendQueue::default();
+
         let mut state_storage = self.state.lock().unwrap();
 
         *state_storage = None;
@@ -210,10 +638,36 @@ impl BaseSinkImpl for NdiSink {
     }
 
     fn unlock(&self, _element: &Self::Type) -> Result<(), gst::ErrorMessage> {
+        let mut queue = self.send_queue.lock().unwrap();
+        queue.flushing = true;
+        queue.items.clear();
+        self.send_queue_cond.notify_all();
         Ok(())
     }
 
+    fn event(&self, element: &Self::Type, event: gst::Event) -> bool {
+        use gst::EventView;
+
+        match event.view() {
+            EventView::CustomDownstream(ev) => {
+                if let Some(s) = ev.structure().filter(|s| s.name() == "ndi-metadata") {
+                    if let Ok(metadata) = s.get::<&str>("metadata") {
+                        self.send_metadata(element, metadata);
+                        return true;
+                    }
+                }
+            }
+            EventView::Eos(_) => self.drain_send_queue(element),
+            _ => (),
+        }
+
+        self.parent_event(element, event)
+    }
+
     fn unlock_stop(&self, _element: &Self::Type) -> Result<(), gst::ErrorMessage> {
+        let mut queue = self.send_queue.lock().unwrap();
+        queue.flushing = false;
+        queue.error = None;
         Ok(())
     }
 
@@ -227,19 +681,25 @@ impl BaseSinkImpl for NdiSink {
         };
 
         let s = caps.structure(0).unwrap();
-        if s.name() == "video/x-raw" {
+        let frame_duration = if s.name() == "video/x-raw" {
             let info = gst_video::VideoInfo::from_caps(caps)
                 .map_err(|_| gst::loggable_error!(CAT, "Couldn't parse caps {}", caps))?;
 
+            let frame_duration = frame_duration(&info);
             state.video_info = Some(info);
             state.audio_info = None;
+            frame_duration
         } else {
             let info = gst_audio::AudioInfo::from_caps(caps)
                 .map_err(|_| gst::loggable_error!(CAT, "Couldn't parse caps {}", caps))?;
 
             state.audio_info = Some(info);
             state.video_info = None;
-        }
+            None
+        };
+        drop(state_storage);
+
+        self.update_pacer(element, frame_duration);
 
         Ok(())
     }
@@ -249,115 +709,1153 @@ impl BaseSinkImpl for NdiSink {
         element: &Self::Type,
         buffer: &gst::Buffer,
     ) -> Result<gst::FlowSuccess, gst::FlowError> {
+        if self.queue_video(element, buffer)? {
+            return Ok(gst::FlowSuccess::Ok);
+        }
+
         let mut state_storage = self.state.lock().unwrap();
         let state = match &mut *state_storage {
             None => return Err(gst::FlowError::Error),
             Some(ref mut state) => state,
         };
 
-        if let Some(ref info) = state.video_info {
-            if let Some(audio_meta) = buffer.meta::<crate::ndisinkmeta::NdiSinkAudioMeta>() {
-                for (buffer, info, timecode) in audio_meta.buffers() {
-                    let frame = crate::ndi::AudioFrame::try_from_buffer(info, buffer, *timecode)
-                        .map_err(|_| {
-                            gst_error!(CAT, obj: element, "Unsupported audio frame");
-                            gst::FlowError::NotNegotiated
-                        })?;
+        let reference_level = self.settings.lock().unwrap().reference_level;
+        self.render_buffer(element, state, buffer, reference_level)?;
 
-                    gst_trace!(
-                        CAT,
-                        obj: element,
-                        "Sending audio buffer {:?} with timecode {} and format {:?}",
-                        buffer,
-                        if *timecode < 0 {
-                            gst::ClockTime::NONE.display()
-                        } else {
-                            Some(gst::ClockTime::from_nseconds(*timecode as u64 * 100)).display()
-                        },
-                        info,
-                    );
-                    state.send.send_audio(&frame);
-                }
+        Ok(gst::FlowSuccess::Ok)
+    }
+
+    // Sends all buffers of the list with a single lock of the state instead of going through
+    // render() for each of them. With pacing enabled video buffers are queued one by one as usual.
+    fn render_list(
+        &self,
+        element: &Self::Type,
+        list: &gst::BufferList,
+    ) -> Result<gst::FlowSuccess, gst::FlowError> {
+        if self.pacer.lock().unwrap().is_some() {
+            for buffer in list.iter_owned() {
+                self.render(element, &buffer)?;
             }
+            return Ok(gst::FlowSuccess::Ok);
+        }
 
-            // Skip empty/gap buffers from ndisinkcombiner
-            if buffer.size() != 0 {
-                let timecode = element
-                    .segment()
-                    .downcast::<gst::ClockTime>()
-                    .ok()
-                    .and_then(|segment| {
-                        segment
-                            .to_running_time(buffer.pts())
-                            .zip(element.base_time())
-                    })
-                    .and_then(|(running_time, base_time)| running_time.checked_add(base_time))
-                    .map(|time| (time.nseconds() / 100) as i64)
-                    .unwrap_or(crate::ndisys::NDIlib_send_timecode_synthesize);
-
-                let frame = gst_video::VideoFrameRef::from_buffer_ref_readable(buffer, info)
-                    .map_err(|_| {
-                        gst_error!(CAT, obj: element, "Failed to map buffer");
-                        gst::FlowError::Error
-                    })?;
+        let mut state_storage = self.state.lock().unwrap();
+        let state = match &mut *state_storage {
+            None => return Err(gst::FlowError::Error),
+            Some(ref mut state) => state,
+        };
+
+        let reference_level = self.settings.lock().unwrap().reference_level;
+
+        gst_trace!(CAT, obj: element, "Rendering buffer list of {} buffers", list.len());
+        for buffer in list.iter_owned() {
+            self.render_buffer(element, state, &buffer, reference_level)?;
+        }
+
+        Ok(gst::FlowSuccess::Ok)
+    }
+}
+
+impl NdiSink {
+    // Sends a single buffer according to the currently configured caps
+    fn render_buffer(
+        &self,
+        element: &super::NdiSink,
+        state: &mut State,
+        buffer: &gst::Buffer,
+        reference_level: i32,
+    ) -> Result<(), gst::FlowError> {
+        if let Some(info) = state.video_info.clone() {
+            let timecode = self.timecode(element, buffer);
+            self.send_video_buffer(element, state, buffer, &info, timecode, reference_level)?;
+        } else if let Some(ref info) = state.audio_info {
+            let timecode = self.timecode(element, buffer);
 
-                let frame = crate::ndi::VideoFrame::try_from_video_frame(&frame, timecode)
+            let frame =
+                crate::ndi::AudioFrame::try_from_buffer(info, buffer, timecode, reference_level)
                     .map_err(|_| {
-                        gst_error!(CAT, obj: element, "Unsupported video frame");
+                        gst_error!(CAT, obj: element, "Unsupported audio frame");
                         gst::FlowError::NotNegotiated
                     })?;
 
+            gst_trace!(
+                CAT,
+                obj: element,
+                "Sending audio buffer {:?} with timecode {} and format {:?}",
+                buffer,
+                if timecode < 0 {
+                    gst::ClockTime::NONE.display()
+                } else {
+                    Some(gst::ClockTime::from_nseconds(timecode as u64 * 100)).display()
+                },
+                info,
+            );
+            state.send.send_audio(&frame);
+            state.last_send = time::Instant::now();
+        } else {
+            return Err(gst::FlowError::Error);
+        }
+
+        Ok(())
+    }
+
+    // Sends the video buffer and any audio attached to it by ndisinkcombiner
+    fn send_video_buffer(
+        &self,
+        element: &super::NdiSink,
+        state: &mut State,
+        buffer: &gst::Buffer,
+        info: &gst_video::VideoInfo,
+        timecode: i64,
+        reference_level: i32,
+    ) -> Result<(), gst::FlowError> {
+        if let Some(audio_meta) = buffer.meta::<crate::ndisinkmeta::NdiSinkAudioMeta>() {
+            for (buffer, info, timecode) in audio_meta.buffers() {
+                let frame = crate::ndi::AudioFrame::try_from_buffer(
+                    info,
+                    buffer,
+                    *timecode,
+                    reference_level,
+                )
+                .map_err(|_| {
+                    gst_error!(CAT, obj: element, "Unsupported audio frame");
+                    gst::FlowError::NotNegotiated
+                })?;
+
                 gst_trace!(
                     CAT,
                     obj: element,
-                    "Sending video buffer {:?} with timecode {} and format {:?}",
+                    "Sending audio buffer {:?} with timecode {} and format {:?}",
                     buffer,
-                    if timecode < 0 {
+                    if *timecode < 0 {
                         gst::ClockTime::NONE.display()
                     } else {
-                        Some(gst::ClockTime::from_nseconds(timecode as u64 * 100)).display()
+                        Some(gst::ClockTime::from_nseconds(*timecode as u64 * 100)).display()
                     },
-                    info
+                    info,
                 );
-                state.send.send_video(&frame);
+                state.send.send_audio(&frame);
+                state.last_send = time::Instant::now();
             }
-        } else if let Some(ref info) = state.audio_info {
-            let timecode = element
-                .segment()
-                .downcast::<gst::ClockTime>()
-                .ok()
-                .and_then(|segment| {
-                    segment
-                        .to_running_time(buffer.pts())
-                        .zip(element.base_time())
-                })
-                .and_then(|(running_time, base_time)| running_time.checked_add(base_time))
-                .map(|time| (time.nseconds() / 100) as i64)
-                .unwrap_or(crate::ndisys::NDIlib_send_timecode_synthesize);
+        }
 
-            let frame =
-                crate::ndi::AudioFrame::try_from_buffer(info, buffer, timecode).map_err(|_| {
-                    gst_error!(CAT, obj: element, "Unsupported audio frame");
-                    gst::FlowError::NotNegotiated
+        // Skip empty/gap buffers from ndisinkcombiner
+        if buffer.size() != 0 {
+            let blended = self.blend_overlays(element, buffer, info);
+            let buffer = blended.as_ref().unwrap_or(buffer);
+
+            let gst_frame = gst_video::VideoFrameRef::from_buffer_ref_readable(buffer, info)
+                .map_err(|_| {
+                    gst_error!(CAT, obj: element, "Failed to map buffer");
+                    gst::FlowError::Error
                 })?;
 
+            let copied;
+            let copied_frame;
+            let frame = match crate::ndi::VideoFrame::try_from_video_frame(&gst_frame, timecode) {
+                Ok(frame) => frame,
+                // NDI has no format with alpha for YUV other than UYVA, which is UYVY with an
+                // additional alpha plane
+                Err(_)
+                    if [gst_video::VideoFormat::Ayuv, gst_video::VideoFormat::Vuya]
+                        .contains(&info.format()) =>
+                {
+                    let uyva_info = uyva_video_info(info).ok_or_else(|| {
+                        gst_error!(CAT, obj: element, "Unsupported video frame");
+                        gst::FlowError::NotNegotiated
+                    })?;
+
+                    copied =
+                        self.pack_uyva(element, &mut state.copy_pool, &gst_frame, &uyva_info)?;
+                    copied_frame = gst_video::VideoFrameRef::from_buffer_ref_readable(
+                        copied.as_ref(),
+                        &uyva_info,
+                    )
+                    .map_err(|_| {
+                        gst_error!(CAT, obj: element, "Failed to map buffer");
+                        gst::FlowError::Error
+                    })?;
+
+                    crate::ndi::VideoFrame::try_from_uyva_video_frame(&copied_frame, timecode)
+                        .map_err(|_| {
+                            gst_error!(CAT, obj: element, "Unsupported video frame");
+                            gst::FlowError::NotNegotiated
+                        })?
+                }
+                Err(_) => {
+                    // The NDI SDK requires the planes of planar formats to directly follow
+                    // each other, which is often not the case for buffers from upstream
+                    let contiguous_info = contiguous_video_info(info).ok_or_else(|| {
+                        gst_error!(CAT, obj: element, "Unsupported video frame");
+                        gst::FlowError::NotNegotiated
+                    })?;
+
+                    gst_debug!(
+                        CAT,
+                        obj: element,
+                        "Planes of buffer {:?} are not contiguous, copying",
+                        buffer
+                    );
+                    copied = self.copy_contiguous(
+                        element,
+                        &mut state.copy_pool,
+                        &gst_frame,
+                        &contiguous_info,
+                    )?;
+                    copied_frame = gst_video::VideoFrameRef::from_buffer_ref_readable(
+                        copied.as_ref(),
+                        &contiguous_info,
+                    )
+                    .map_err(|_| {
+                        gst_error!(CAT, obj: element, "Failed to map buffer");
+                        gst::FlowError::Error
+                    })?;
+
+                    crate::ndi::VideoFrame::try_from_video_frame(&copied_frame, timecode).map_err(
+                        |_| {
+                            gst_error!(CAT, obj: element, "Unsupported video frame");
+                            gst::FlowError::NotNegotiated
+                        },
+                    )?
+                }
+            };
+
             gst_trace!(
                 CAT,
                 obj: element,
-                "Sending audio buffer {:?} with timecode {} and format {:?}",
+                "Sending video buffer {:?} with timecode {} and format {:?}",
                 buffer,
                 if timecode < 0 {
                     gst::ClockTime::NONE.display()
                 } else {
                     Some(gst::ClockTime::from_nseconds(timecode as u64 * 100)).display()
                 },
-                info,
+                info
             );
-            state.send.send_audio(&frame);
-        } else {
-            return Err(gst::FlowError::Error);
+            state.send.send_video(&frame);
+            state.last_send = time::Instant::now();
         }
 
-        Ok(gst::FlowSuccess::Ok)
+        Ok(())
+    }
+
+    // Queues the video buffer for the pacer if pacing is enabled, waiting while the queue is full
+    // unless it's leaky. Returns false if the buffer has to be sent directly.
+    fn queue_video(
+        &self,
+        element: &super::NdiSink,
+        buffer: &gst::Buffer,
+    ) -> Result<bool, gst::FlowError> {
+        if self.pacer.lock().unwrap().is_none() {
+            return Ok(false);
+        }
+
+        let info = match *self.state.lock().unwrap() {
+            Some(State {
+                video_info: Some(ref info),
+                ..
+            }) => info.clone(),
+            _ => return Ok(false),
+        };
+        let timecode = self.timecode(element, buffer);
+
+        let (length, leaky) = {
+            let settings = self.settings.lock().unwrap();
+            (
+                settings.send_queue_length as usize,
+                settings.send_queue_leaky,
+            )
+        };
+
+        let mut queue = self.send_queue.lock().unwrap();
+        loop {
+            if let Some(err) = queue.error.take() {
+                return Err(err);
+            }
+            if queue.flushing {
+                return Err(gst::FlowError::Flushing);
+            }
+            if queue.items.len() < length {
+                break;
+            }
+            if leaky {
+                let (dropped, _, _) = queue.items.pop_front().unwrap();
+                gst_debug!(CAT, obj: element, "Send queue full, dropping {:?}", dropped);
+                break;
+            }
+            queue = self.send_queue_cond.wait(queue).unwrap();
+        }
+
+        gst_trace!(CAT, obj: element, "Queueing video buffer {:?}", buffer);
+        queue.items.push_back((buffer.clone(), info, timecode));
+
+        Ok(true)
+    }
+
+    // Waits until the pacer has sent all queued video buffers, e.g. before EOS
+    fn drain_send_queue(&self, element: &super::NdiSink) {
+        if self.pacer.lock().unwrap().is_none() {
+            return;
+        }
+
+        let mut queue = self.send_queue.lock().unwrap();
+        if !queue.items.is_empty() {
+            gst_debug!(
+                CAT,
+                obj: element,
+                "Draining {} queued video buffers",
+                queue.items.len()
+            );
+        }
+        while (!queue.items.is_empty() || queue.in_flight)
+            && !queue.flushing
+            && queue.error.is_none()
+        {
+            queue = self.send_queue_cond.wait(queue).unwrap();
+        }
+    }
+
+    // Starts, restarts or stops the pacer for the frame duration of new caps. Without a frame
+    // duration, e.g. for variable frame rates, buffers are sent directly and any queued ones are
+    // sent right away.
+    fn update_pacer(&self, element: &super::NdiSink, frame_duration: Option<time::Duration>) {
+        let frame_duration =
+            frame_duration.filter(|_| self.settings.lock().unwrap().send_queue_length > 0);
+
+        let mut pacer = self.pacer.lock().unwrap();
+        if pacer.as_ref().map(|(interval, _)| *interval) == frame_duration {
+            return;
+        }
+
+        if let Some((_, worker)) = pacer.take() {
+            worker.stop();
+        }
+
+        match frame_duration {
+            Some(interval) => {
+                gst_debug!(CAT, obj: element, "Pacing video frames every {:?}", interval);
+                *pacer = Some((interval, self.start_pacer(element, interval)));
+            }
+            None => {
+                let items = std::mem::take(&mut self.send_queue.lock().unwrap().items);
+                if items.is_empty() {
+                    return;
+                }
+
+                let reference_level = self.settings.lock().unwrap().reference_level;
+                let mut state_storage = self.state.lock().unwrap();
+                if let Some(ref mut state) = *state_storage {
+                    for (buffer, info, timecode) in items {
+                        let _ = self.send_video_buffer(
+                            element,
+                            state,
+                            &buffer,
+                            &info,
+                            timecode,
+                            reference_level,
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    // Sends one queued video buffer per interval so that bursts from upstream are smoothed out
+    fn start_pacer(&self, element: &super::NdiSink, interval: time::Duration) -> Worker {
+        Worker::spawn(element, "ndi-pacer", interval, |element| {
+            let imp = NdiSink::from_instance(element);
+
+            let (buffer, info, timecode) = {
+                let mut queue = imp.send_queue.lock().unwrap();
+                match queue.items.pop_front() {
+                    Some(item) => {
+                        queue.in_flight = true;
+                        item
+                    }
+                    None => return true,
+                }
+            };
+
+            let reference_level = imp.settings.lock().unwrap().reference_level;
+            let res = {
+                let mut state_storage = imp.state.lock().unwrap();
+                state_storage.as_mut().map(|state| {
+                    imp.send_video_buffer(element, state, &buffer, &info, timecode, reference_level)
+                })
+            };
+
+            // Only wake up waiters once the buffer is sent so that draining includes it
+            let mut queue = imp.send_queue.lock().unwrap();
+            queue.in_flight = false;
+            if let Some(Err(err)) = res {
+                queue.error = Some(err);
+            }
+            imp.send_queue_cond.notify_all();
+
+            res.is_some()
+        })
+    }
+
+    // Sends a metadata frame to all connected receivers. This does not require any audio or video,
+    // so together with async=false the sink can be used as a metadata-only sender
+    fn send_metadata(&self, element: &super::NdiSink, metadata: &str) -> bool {
+        let mut state_storage = self.state.lock().unwrap();
+        let state = match &mut *state_storage {
+            Some(ref mut state) => state,
+            None => {
+                gst_warning!(CAT, obj: element, "Can't send metadata before starting");
+                return false;
+            }
+        };
+
+        gst_debug!(CAT, obj: element, "Sending metadata {}", metadata);
+        state.send.send_metadata(&MetadataFrame::new(
+            crate::ndisys::NDIlib_send_timecode_synthesize,
+            Some(metadata),
+        ));
+        state.last_send = time::Instant::now();
+
+        true
+    }
+
+    fn create_send(
+        &self,
+        element: &super::NdiSink,
+        settings: &Settings,
+        ndi_name: &str,
+    ) -> Result<Box<dyn FrameSink>, gst::ErrorMessage> {
+        let mut builder = SendInstance::builder(ndi_name).groups(settings.groups.as_deref());
+        if settings.clock_audio {
+            builder = builder.clock_audio();
+        }
+        if settings.clock_video {
+            builder = builder.clock_video();
+        }
+        let mut send: Box<dyn FrameSink> = Box::new(builder.build().ok_or_else(|| {
+            gst::error_msg!(
+                gst::ResourceError::OpenWrite,
+                ["Could not create send instance"]
+            )
+        })?);
+        self.add_connection_metadata(element, settings, &mut *send);
+
+        Ok(send)
+    }
+
+    fn add_connection_metadata(
+        &self,
+        element: &super::NdiSink,
+        settings: &Settings,
+        send: &mut dyn FrameSink,
+    ) {
+        if settings.product_name.is_some() || settings.manufacturer.is_some() {
+            let mut product = String::from("<ndi_product");
+            if let Some(ref product_name) = settings.product_name {
+                product.push_str(&format!(" long_name=\"{}\"", xml_escape(product_name)));
+            }
+            if let Some(ref manufacturer) = settings.manufacturer {
+                product.push_str(&format!(" manufacturer=\"{}\"", xml_escape(manufacturer)));
+            }
+            product.push_str("/>");
+
+            gst_debug!(CAT, obj: element, "Adding connection metadata {}", product);
+            send.add_connection_metadata(&MetadataFrame::new(0, Some(&product)));
+        }
+
+        if let Some(ref connection_metadata) = settings.connection_metadata {
+            gst_debug!(
+                CAT,
+                obj: element,
+                "Adding connection metadata {}",
+                connection_metadata
+            );
+            send.add_connection_metadata(&MetadataFrame::new(0, Some(connection_metadata)));
+        }
+    }
+
+    // The SDK only allows configuring the clocking when creating the send instance, so it is
+    // recreated with the same name if clock-audio or clock-video changed since then
+    fn update_clocking(&self, element: &super::NdiSink) -> Result<(), gst::ErrorMessage> {
+        let mut state_storage = self.state.lock().unwrap();
+        let settings = self.settings.lock().unwrap();
+
+        match *state_storage {
+            Some(ref state)
+                if state.clock_audio != settings.clock_audio
+                    || state.clock_video != settings.clock_video => {}
+            _ => return Ok(()),
+        }
+
+        // Release the old instance first so that the name is free again
+        let State {
+            send,
+            video_info,
+            audio_info,
+            ndi_name,
+            ..
+        } = state_storage.take().unwrap();
+        drop(send);
+
+        gst_info!(
+            CAT,
+            obj: element,
+            "Recreating send instance {} with clock-audio={} clock-video={}",
+            ndi_name,
+            settings.clock_audio,
+            settings.clock_video,
+        );
+        let send = self.create_send(element, &settings, &ndi_name)?;
+
+        *state_storage = Some(State {
+            send,
+            video_info,
+            audio_info,
+            last_send: time::Instant::now(),
+            ndi_name,
+            clock_audio: settings.clock_audio,
+            clock_video: settings.clock_video,
+            copy_pool: None,
+        });
+
+        Ok(())
+    }
+
+    // Sends metadata to the receivers whenever no frame was sent for the given interval so that
+    // they don't consider the source gone while upstream is paused
+    fn start_keepalive(&self, element: &super::NdiSink, interval: time::Duration) -> Worker {
+        Worker::spawn(element, "ndi-keepalive", interval, move |element| {
+            let imp = NdiSink::from_instance(element);
+
+            let mut state_storage = imp.state.lock().unwrap();
+            let state = match &mut *state_storage {
+                Some(ref mut state) => state,
+                None => return false,
+            };
+
+            if state.last_send.elapsed() >= interval {
+                gst_trace!(CAT, obj: element, "Sending keepalive");
+                state.send.send_metadata(&MetadataFrame::new(
+                    crate::ndisys::NDIlib_send_timecode_synthesize,
+                    Some("<ndi_keepalive/>"),
+                ));
+                state.last_send = time::Instant::now();
+            }
+
+            true
+        })
+    }
+
+    // Receives the metadata receivers send to this source and posts PTZ and recording requests
+    // as element messages, e.g. ptz-request with command=zoom and zoom=0.5 for
+    // <ntk_ptz_zoom zoom="0.5"/>, so that the application can act on them
+    fn start_control(&self, element: &super::NdiSink) -> Worker {
+        Worker::spawn(
+            element,
+            "ndi-control",
+            time::Duration::from_millis(100),
+            |element| {
+                let imp = NdiSink::from_instance(element);
+
+                let mut requests = Vec::new();
+                {
+                    let mut state_storage = imp.state.lock().unwrap();
+                    let state = match &mut *state_storage {
+                        Some(ref mut state) => state,
+                        None => return false,
+                    };
+
+                    while let Some(metadata) = state.send.capture_metadata(0) {
+                        requests.push(metadata);
+                    }
+                }
+
+                for metadata in requests {
+                    gst_debug!(CAT, obj: element, "Received metadata {}", metadata);
+                    if let Some(s) = control_request(&metadata) {
+                        let _ = element
+                            .post_message(gst::message::Element::builder(s).src(element).build());
+                    }
+                }
+
+                true
+            },
+        )
+    }
+
+    // NDI can't transport overlay compositions separately from the video, so any that are
+    // attached to the buffer are blended into a copy of it. Returns None if there are none.
+    fn blend_overlays(
+        &self,
+        element: &super::NdiSink,
+        buffer: &gst::Buffer,
+        info: &gst_video::VideoInfo,
+    ) -> Option<gst::Buffer> {
+        let compositions = buffer
+            .iter_meta::<gst_video::VideoOverlayCompositionMeta>()
+            .map(|meta| meta.overlay_owned())
+            .collect::<Vec<_>>();
+        if compositions.is_empty() {
+            return None;
+        }
+
+        gst_trace!(
+            CAT,
+            obj: element,
+            "Blending {} overlay compositions",
+            compositions.len()
+        );
+
+        let mut blended = match buffer.copy_deep() {
+            Ok(blended) => blended,
+            Err(_) => {
+                gst_warning!(
+                    CAT,
+                    obj: element,
+                    "Failed to copy buffer, dropping overlay compositions"
+                );
+                return None;
+            }
+        };
+
+        {
+            let blended = blended.get_mut().unwrap();
+            let mut frame = match gst_video::VideoFrameRef::from_buffer_ref_writable(blended, info)
+            {
+                Ok(frame) => frame,
+                Err(_) => {
+                    gst_warning!(
+                        CAT,
+                        obj: element,
+                        "Failed to map buffer, dropping overlay compositions"
+                    );
+                    return None;
+                }
+            };
+
+            for composition in compositions {
+                if composition.blend(&mut frame).is_err() {
+                    gst_warning!(CAT, obj: element, "Failed to blend overlay composition");
+                }
+            }
+        }
+
+        Some(blended)
+    }
+
+    // Buffer for the given layout from the copy pool. The pool is reused as long as the layout
+    // stays the same.
+    fn acquire_copy_buffer(
+        &self,
+        element: &super::NdiSink,
+        copy_pool: &mut Option<(gst_video::VideoInfo, gst::BufferPool)>,
+        info: &gst_video::VideoInfo,
+    ) -> Result<gst::Buffer, gst::FlowError> {
+        let pool = match *copy_pool {
+            Some((ref pool_info, ref pool)) if pool_info == info => pool.clone(),
+            _ => {
+                if let Some((_, pool)) = copy_pool.take() {
+                    let _ = pool.set_active(false);
+                }
+
+                gst_debug!(CAT, obj: element, "Creating copy pool for {:?}", info);
+                let pool = gst::BufferPool::new();
+                let mut config = pool.config();
+                config.set_params(None, info.size() as u32, 0, 0);
+                pool.set_config(config)
+                    .and_then(|_| pool.set_active(true))
+                    .map_err(|err| {
+                        gst_error!(CAT, obj: element, "Failed to set up copy pool: {}", err);
+                        gst::FlowError::Error
+                    })?;
+
+                *copy_pool = Some((info.clone(), pool.clone()));
+                pool
+            }
+        };
+
+        pool.acquire_buffer(None)
+    }
+
+    // Copies the frame into a buffer from the copy pool with the given contiguous layout
+    fn copy_contiguous(
+        &self,
+        element: &super::NdiSink,
+        copy_pool: &mut Option<(gst_video::VideoInfo, gst::BufferPool)>,
+        frame: &gst_video::VideoFrameRef<&gst::BufferRef>,
+        info: &gst_video::VideoInfo,
+    ) -> Result<gst::Buffer, gst::FlowError> {
+        let mut buffer = self.acquire_copy_buffer(element, copy_pool, info)?;
+        {
+            let buffer = buffer.get_mut().unwrap();
+            // Keep the interlacing flags
+            buffer.set_flags(frame.buffer().flags());
+
+            let mut dest = gst_video::VideoFrameRef::from_buffer_ref_writable(buffer, info)
+                .map_err(|_| {
+                    gst_error!(CAT, obj: element, "Failed to map buffer");
+                    gst::FlowError::Error
+                })?;
+            frame.copy(&mut dest).map_err(|err| {
+                gst_error!(CAT, obj: element, "Failed to copy frame: {}", err);
+                gst::FlowError::Error
+            })?;
+        }
+
+        Ok(buffer)
+    }
+
+    // Packs an AYUV or VUYA frame into a buffer from the copy pool with the UYVA layout of the
+    // given info. The chroma of each pair of pixels is averaged.
+    fn pack_uyva(
+        &self,
+        element: &super::NdiSink,
+        copy_pool: &mut Option<(gst_video::VideoInfo, gst::BufferPool)>,
+        frame: &gst_video::VideoFrameRef<&gst::BufferRef>,
+        info: &gst_video::VideoInfo,
+    ) -> Result<gst::Buffer, gst::FlowError> {
+        // Byte offsets of the alpha, Y, U and V components
+        let (a, y, u, v) = match frame.format() {
+            gst_video::VideoFormat::Ayuv => (0, 1, 2, 3),
+            gst_video::VideoFormat::Vuya => (3, 2, 1, 0),
+            _ => unreachable!(),
+        };
+
+        let mut buffer = self.acquire_copy_buffer(element, copy_pool, info)?;
+        {
+            let buffer = buffer.get_mut().unwrap();
+            // Keep the interlacing flags
+            buffer.set_flags(frame.buffer().flags());
+
+            let mut map = buffer.map_writable().map_err(|_| {
+                gst_error!(CAT, obj: element, "Failed to map buffer");
+                gst::FlowError::Error
+            })?;
+
+            let width = info.width() as usize;
+            let height = info.height() as usize;
+            let src = frame.plane_data(0).unwrap();
+            let src_stride = frame.plane_stride()[0] as usize;
+            let dest_stride = info.stride()[0] as usize;
+            let (uyvy, alpha) = map.split_at_mut(dest_stride * height);
+
+            for ((src_line, dest_line), alpha_line) in src
+                .chunks(src_stride)
+                .zip(uyvy.chunks_exact_mut(dest_stride))
+                .zip(alpha.chunks_exact_mut(dest_stride / 2))
+            {
+                let src_line = &src_line[..4 * width];
+
+                // The last pixel is repeated for odd widths
+                for (dest, src) in dest_line.chunks_exact_mut(4).zip(src_line.chunks(8)) {
+                    let (p0, p1) = src.split_at(4);
+                    let p1 = if p1.is_empty() { p0 } else { p1 };
+
+                    dest[0] = ((p0[u] as u16 + p1[u] as u16) / 2) as u8;
+                    dest[1] = p0[y];
+                    dest[2] = ((p0[v] as u16 + p1[v] as u16) / 2) as u8;
+                    dest[3] = p1[y];
+                }
+
+                for (dest, src) in alpha_line.iter_mut().zip(src_line.chunks_exact(4)) {
+                    *dest = src[a];
+                }
+            }
+        }
+
+        Ok(buffer)
+    }
+
+    // Timecode in 100ns units for the frame of the given buffer according to the timecode mode
+    fn timecode(&self, element: &super::NdiSink, buffer: &gst::BufferRef) -> i64 {
+        let timecode_mode = self.settings.lock().unwrap().timecode_mode;
+
+        if timecode_mode == SendTimecodeMode::Synthesize {
+            return crate::ndisys::NDIlib_send_timecode_synthesize;
+        }
+
+        if timecode_mode == SendTimecodeMode::FromTimecodeMeta {
+            if let Some(meta) = buffer.meta::<gst_video::VideoTimeCodeMeta>() {
+                return crate::ndi::video_time_code_to_timecode(&meta.tc());
+            }
+        }
+
+        element
+            .segment()
+            .downcast::<gst::ClockTime>()
+            .ok()
+            .and_then(|segment| {
+                segment
+                    .to_running_time(buffer.pts())
+                    .zip(element.base_time())
+            })
+            .and_then(|(running_time, base_time)| running_time.checked_add(base_time))
+            .map(|time| (time.nseconds() / 100) as i64)
+            .unwrap_or(crate::ndisys::NDIlib_send_timecode_synthesize)
+    }
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+// Removes characters that can't be part of an NDI name, including the NUL characters that
+// can't be passed to the SDK, or returns None if nothing usable is left
+fn sanitize_ndi_name(name: &str) -> Option<String> {
+    let name = name
+        .chars()
+        .filter(|c| !c.is_control())
+        .collect::<String>()
+        .trim()
+        .to_string();
+
+    if name.is_empty() {
+        None
+    } else {
+        Some(name)
+    }
+}
+
+// Duration of one buffer for pacing, i.e. of a field for alternate interlacing, or None for
+// variable frame rates
+fn frame_duration(info: &gst_video::VideoInfo) -> Option<time::Duration> {
+    let fps = info.fps();
+    if fps.numer() <= 0 || fps.denom() <= 0 {
+        return None;
+    }
+
+    let mut duration =
+        gst::ClockTime::SECOND.mul_div_floor(fps.denom() as u64, fps.numer() as u64)?;
+    if info.interlace_mode() == gst_video::VideoInterlaceMode::Alternate {
+        duration /= 2;
+    }
+
+    Some(time::Duration::from_nanos(duration.nseconds()))
+}
+
+// Video info with the strides of the given one but all planes directly following each other, as
+// the NDI SDK requires for the planar formats. None for packed formats.
+fn contiguous_video_info(info: &gst_video::VideoInfo) -> Option<gst_video::VideoInfo> {
+    let height = info.height() as usize;
+    let chroma_height = (height + 1) / 2;
+    let stride = info.stride();
+
+    let (offset, size) = match info.format() {
+        gst_video::VideoFormat::Nv12 | gst_video::VideoFormat::Nv21 => {
+            let offset_1 = height * stride[0] as usize;
+            (
+                vec![0, offset_1],
+                offset_1 + chroma_height * stride[1] as usize,
+            )
+        }
+        gst_video::VideoFormat::I420 | gst_video::VideoFormat::Yv12 => {
+            let offset_1 = height * stride[0] as usize;
+            let offset_2 = offset_1 + chroma_height * stride[1] as usize;
+            (
+                vec![0, offset_1, offset_2],
+                offset_2 + chroma_height * stride[2] as usize,
+            )
+        }
+        _ => return None,
+    };
+
+    let colorimetry = info.colorimetry();
+    gst_video::VideoInfo::builder(info.format(), info.width(), info.height())
+        .interlace_mode(info.interlace_mode())
+        .flags(info.flags())
+        .field_order(info.field_order())
+        .par(info.par())
+        .fps(info.fps())
+        .colorimetry(&colorimetry)
+        .chroma_site(info.chroma_site())
+        .stride(stride)
+        .offset(&offset)
+        .size(size)
+        .build()
+        .ok()
+}
+
+// Video info for NDI's UYVA layout with the same dimensions as the given one: a UYVY plane
+// directly followed by an alpha plane with one byte per pixel and half the line stride. NDI
+// derives the alpha stride from the UYVY one, so odd widths have one byte of padding per line.
+fn uyva_video_info(info: &gst_video::VideoInfo) -> Option<gst_video::VideoInfo> {
+    let width = info.width() as usize;
+    let height = info.height() as usize;
+    let stride = (width + width % 2) * 2;
+
+    let colorimetry = info.colorimetry();
+    gst_video::VideoInfo::builder(gst_video::VideoFormat::Uyvy, info.width(), info.height())
+        .interlace_mode(info.interlace_mode())
+        .flags(info.flags())
+        .field_order(info.field_order())
+        .par(info.par())
+        .fps(info.fps())
+        .colorimetry(&colorimetry)
+        .chroma_site(info.chroma_site())
+        .stride(&[stride as i32])
+        .offset(&[0])
+        .size(
+            stride
+                .checked_mul(height)?
+                .checked_add((stride / 2).checked_mul(height)?)?,
+        )
+        .build()
+        .ok()
+}
+
+// Structure for a PTZ or recording request sent by a receiver, or None for other metadata
+fn control_request(metadata: &str) -> Option<gst::Structure> {
+    let name = metadata.trim_start().strip_prefix('<')?;
+    let name = &name[..name.find(|c: char| c.is_whitespace() || c == '/' || c == '>')?];
+
+    let (structure_name, command) = if let Some(command) = name.strip_prefix("ntk_ptz_") {
+        ("ptz-request", command)
+    } else if let Some(command) = name.strip_prefix("ntk_record_") {
+        ("recording-request", command)
+    } else {
+        return None;
+    };
+
+    let mut s = gst::Structure::builder(structure_name)
+        .field("command", command.replace('_', "-"))
+        .field("metadata", metadata);
+    for (name, value) in crate::receiver::parse_xml_attributes(metadata, name).unwrap_or_default() {
+        s = s.field(&name.replace('_', "-"), value);
+    }
+
+    Some(s.build())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{init, FakeSender, SentFrame};
+
+    // Started sink that sends through a fake sender
+    fn sink() -> (super::super::NdiSink, FakeSender) {
+        init();
+
+        let element = glib::Object::new::<super::super::NdiSink>(&[]).unwrap();
+        let sender = FakeSender::default();
+        *NdiSink::from_instance(&element).state.lock().unwrap() = Some(State {
+            send: Box::new(sender.clone()),
+            video_info: None,
+            audio_info: None,
+            last_send: time::Instant::now(),
+            ndi_name: String::from("test"),
+            clock_audio: false,
+            clock_video: false,
+            copy_pool: None,
+        });
+
+        (element, sender)
+    }
+
+    #[test]
+    fn connection_metadata() {
+        let (element, mut sender) = sink();
+
+        let settings = Settings {
+            product_name: Some(String::from("Mixer \"Pro\"")),
+            manufacturer: Some(String::from("A & B")),
+            connection_metadata: Some(String::from("<ndi_capabilities ntk_ptz=\"true\"/>")),
+            ..Default::default()
+        };
+        NdiSink::from_instance(&element).add_connection_metadata(&element, &settings, &mut sender);
+
+        assert_eq!(
+            sender.connection_metadata(),
+            [
+                "<ndi_product long_name=\"Mixer &quot;Pro&quot;\" manufacturer=\"A &amp; B\"/>",
+                "<ndi_capabilities ntk_ptz=\"true\"/>",
+            ]
+        );
+    }
+
+    #[test]
+    fn send_metadata_signal() {
+        let (element, sender) = sink();
+
+        assert!(element.emit_by_name::<bool>("send-metadata", &[&"<hello/>"]));
+        assert_eq!(
+            sender.sent(),
+            [SentFrame::Metadata(String::from("<hello/>"))]
+        );
+    }
+
+    #[test]
+    fn keepalive() {
+        let (element, sender) = sink();
+        let imp = NdiSink::from_instance(&element);
+
+        let keepalive = imp.start_keepalive(&element, time::Duration::from_millis(10));
+        thread::sleep(time::Duration::from_millis(100));
+        keepalive.stop();
+
+        let sent = sender.sent();
+        assert!(!sent.is_empty());
+        assert!(sent
+            .iter()
+            .all(|frame| *frame == SentFrame::Metadata(String::from("<ndi_keepalive/>"))));
+    }
+
+    #[test]
+    fn control_requests() {
+        let (element, sender) = sink();
+        let imp = NdiSink::from_instance(&element);
+        let bus = gst::Bus::new();
+        element.set_bus(Some(&bus));
+
+        sender.push_request("<ntk_ptz_zoom zoom=\"0.5\"/>");
+        sender.push_request("<ndi_tally_echo on_program=\"true\"/>");
+        sender.push_request("<ntk_record_start/>");
+        let control = imp.start_control(&element);
+
+        let mut requests = Vec::new();
+        while requests.len() < 2 {
+            let msg = bus
+                .timed_pop_filtered(
+                    gst::ClockTime::from_seconds(5),
+                    &[gst::MessageType::Element],
+                )
+                .expect("no request posted");
+            requests.push(msg.structure().unwrap().to_owned());
+        }
+        control.stop();
+
+        assert_eq!(requests[0].name(), "ptz-request");
+        assert_eq!(requests[0].get::<&str>("command").unwrap(), "zoom");
+        assert_eq!(requests[0].get::<&str>("zoom").unwrap(), "0.5");
+        assert_eq!(requests[1].name(), "recording-request");
+        assert_eq!(requests[1].get::<&str>("command").unwrap(), "start");
+        // Metadata that is no request is not posted
+        assert!(bus.pop().is_none());
+    }
+
+    #[test]
+    fn uyva_odd_width() {
+        let (element, sender) = sink();
+        let imp = NdiSink::from_instance(&element);
+
+        let info = gst_video::VideoInfo::builder(gst_video::VideoFormat::Ayuv, 3, 2)
+            .fps(gst::Fraction::new(30, 1))
+            .build()
+            .unwrap();
+        imp.state.lock().unwrap().as_mut().unwrap().video_info = Some(info.clone());
+
+        // AYUV pixels with alpha 10 * line + x, luma 100 + x and chroma depending on the pixel
+        let mut data = vec![0u8; info.size()];
+        for (line, data) in data.chunks_exact_mut(info.stride()[0] as usize).enumerate() {
+            for (x, pixel) in data.chunks_exact_mut(4).take(3).enumerate() {
+                pixel.copy_from_slice(&[
+                    10 * line as u8 + x as u8,
+                    100 + x as u8,
+                    2 * x as u8,
+                    4 * x as u8,
+                ]);
+            }
+        }
+        imp.render(&element, &gst::Buffer::from_mut_slice(data))
+            .unwrap();
+
+        let sent = sender.sent();
+        let (fourcc, xres, yres, line_stride, data) = match &sent[..] {
+            [SentFrame::Video {
+                fourcc,
+                xres,
+                yres,
+                line_stride,
+                data,
+                ..
+            }] => (*fourcc, *xres, *yres, *line_stride as usize, data),
+            frame => panic!("unexpected frames {:?}", frame),
+        };
+        assert_eq!(fourcc, crate::ndisys::NDIlib_FourCC_video_type_UYVA);
+        assert_eq!((xres, yres), (3, 2));
+
+        // The UYVY lines are padded to an even width, and the alpha plane has half their stride
+        assert_eq!(line_stride, 8);
+        assert_eq!(data.len(), 2 * 8 + 2 * 4);
+
+        let (uyvy, alpha) = data.split_at(2 * line_stride);
+        for (line, (uyvy, alpha)) in uyvy
+            .chunks_exact(line_stride)
+            .zip(alpha.chunks_exact(line_stride / 2))
+            .enumerate()
+        {
+            // The chroma of each pair is averaged and the last pixel repeated for the odd width
+            assert_eq!(uyvy, [1, 100, 2, 101, 4, 102, 8, 102]);
+            assert_eq!(
+                &alpha[..3],
+                [10 * line as u8, 10 * line as u8 + 1, 10 * line as u8 + 2]
+            );
+        }
+    }
+
+    #[cfg(feature = "interlaced-fields")]
+    #[test]
+    fn alternate_field_types() {
+        use crate::ndisys::NDIlib_frame_format_type_e;
+        use gst_video::prelude::*;
+
+        let (element, sender) = sink();
+        let imp = NdiSink::from_instance(&element);
+
+        let info = gst_video::VideoInfo::builder(gst_video::VideoFormat::Uyvy, 8, 4)
+            .interlace_mode(gst_video::VideoInterlaceMode::Alternate)
+            .fps(gst::Fraction::new(30, 1))
+            .build()
+            .unwrap();
+        imp.state.lock().unwrap().as_mut().unwrap().video_info = Some(info.clone());
+
+        let field = |flags| {
+            let mut buffer = gst::Buffer::with_size(info.size()).unwrap();
+            buffer.get_mut().unwrap().set_video_flags(flags);
+            buffer
+        };
+
+        // Each buffer carries a single field of half the lines, sent with the yres of the frame
+        for (flags, frame_format_type) in [
+            (
+                gst_video::VideoBufferFlags::TOP_FIELD,
+                NDIlib_frame_format_type_e::NDIlib_frame_format_type_field_0,
+            ),
+            (
+                gst_video::VideoBufferFlags::BOTTOM_FIELD,
+                NDIlib_frame_format_type_e::NDIlib_frame_format_type_field_1,
+            ),
+        ] {
+            imp.render(
+                &element,
+                &field(gst_video::VideoBufferFlags::INTERLACED | flags),
+            )
+            .unwrap();
+
+            match sender.sent().last().unwrap() {
+                SentFrame::Video {
+                    frame_format_type: sent_format_type,
+                    yres,
+                    line_stride,
+                    data,
+                    ..
+                } => {
+                    assert_eq!(*sent_format_type, frame_format_type);
+                    assert_eq!(*yres, 4);
+                    assert_eq!(*line_stride, 16);
+                    assert_eq!(data.len(), 2 * 16);
+                }
+                frame => panic!("unexpected frame {:?}", frame),
+            }
+        }
+
+        // Buffers that are flagged as neither field are refused
+        assert_eq!(
+            imp.render(&element, &field(gst_video::VideoBufferFlags::INTERLACED)),
+            Err(gst::FlowError::NotNegotiated)
+        );
+        assert_eq!(sender.sent().len(), 2);
     }
 }
@@ -6,6 +6,8 @@ use gst::{gst_debug, gst_error, gst_trace, gst_warning};
 use gst_base::prelude::*;
 use gst_base::subclass::prelude::*;
 
+use byte_slice_cast::*;
+
 use once_cell::sync::Lazy;
 
 use std::mem;
@@ -19,18 +21,44 @@ static CAT: once_cell::sync::Lazy<gst::DebugCategory> = once_cell::sync::Lazy::n
     )
 });
 
+// By default any number of channels is accepted and passed as-is to the sink, which sends it
+// as planar float audio with a channel stride of the number of samples per channel. Mono is
+// always passed through unchanged. Many NDI receivers only handle up to 8 channels, in which
+// case max-channels can be set to 8 to either refuse more channels during caps negotiation or,
+// with downmix enabled, to mix all additional channels into the first 8 based on their positions.
+const DEFAULT_MAX_CHANNELS: u32 = 0;
+const DEFAULT_DOWNMIX: bool = false;
+
+#[derive(Debug, Clone)]
+struct Settings {
+    max_channels: u32,
+    downmix: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            max_channels: DEFAULT_MAX_CHANNELS,
+            downmix: DEFAULT_DOWNMIX,
+        }
+    }
+}
+
 struct State {
     // Note that this applies to the currently pending buffer on the pad and *not*
     // to the current_video_buffer below!
     video_info: Option<gst_video::VideoInfo>,
     audio_info: Option<gst_audio::AudioInfo>,
     current_video_buffer: Option<(gst::Buffer, gst::ClockTime)>,
+    // New video caps that only apply after the current_video_buffer was output
+    pending_video_caps: Option<gst::Caps>,
     current_audio_buffers: Vec<(gst::Buffer, gst_audio::AudioInfo, i64)>,
 }
 
 pub struct NdiSinkCombiner {
     video_pad: gst_base::AggregatorPad,
     audio_pad: Mutex<Option<gst_base::AggregatorPad>>,
+    settings: Mutex<Settings>,
     state: Mutex<Option<State>>,
 }
 
@@ -49,12 +77,89 @@ impl ObjectSubclass for NdiSinkCombiner {
         Self {
             video_pad,
             audio_pad: Mutex::new(None),
+            settings: Mutex::new(Default::default()),
             state: Mutex::new(None),
         }
     }
 }
 
 impl ObjectImpl for NdiSinkCombiner {
+    fn properties() -> &'static [glib::ParamSpec] {
+        static PROPERTIES: Lazy<Vec<glib::ParamSpec>> = Lazy::new(|| {
+            vec![
+                glib::ParamSpecUInt::new(
+                    "max-channels",
+                    "Max Channels",
+                    "Maximum number of audio channels to accept (0 = unlimited)",
+                    0,
+                    u32::MAX,
+                    DEFAULT_MAX_CHANNELS,
+                    glib::ParamFlags::READWRITE | gst::PARAM_FLAG_MUTABLE_READY,
+                ),
+                glib::ParamSpecBoolean::new(
+                    "downmix",
+                    "Downmix",
+                    "Accept more than max-channels and downmix to max-channels based on the \
+                     channel positions instead of refusing such caps",
+                    DEFAULT_DOWNMIX,
+                    glib::ParamFlags::READWRITE | gst::PARAM_FLAG_MUTABLE_READY,
+                ),
+            ]
+        });
+
+        PROPERTIES.as_ref()
+    }
+
+    fn set_property(
+        &self,
+        obj: &Self::Type,
+        _id: usize,
+        value: &glib::Value,
+        pspec: &glib::ParamSpec,
+    ) {
+        match pspec.name() {
+            "max-channels" => {
+                let mut settings = self.settings.lock().unwrap();
+                let max_channels = value.get().unwrap();
+                gst_debug!(
+                    CAT,
+                    obj: obj,
+                    "Changing max-channels from {} to {}",
+                    settings.max_channels,
+                    max_channels,
+                );
+                settings.max_channels = max_channels;
+            }
+            "downmix" => {
+                let mut settings = self.settings.lock().unwrap();
+                let downmix = value.get().unwrap();
+                gst_debug!(
+                    CAT,
+                    obj: obj,
+                    "Changing downmix from {} to {}",
+                    settings.downmix,
+                    downmix,
+                );
+                settings.downmix = downmix;
+            }
+            _ => unimplemented!(),
+        }
+    }
+
+    fn property(&self, _obj: &Self::Type, _id: usize, pspec: &glib::ParamSpec) -> glib::Value {
+        match pspec.name() {
+            "max-channels" => {
+                let settings = self.settings.lock().unwrap();
+                settings.max_channels.to_value()
+            }
+            "downmix" => {
+                let settings = self.settings.lock().unwrap();
+                settings.downmix.to_value()
+            }
+            _ => unimplemented!(),
+        }
+    }
+
     fn constructed(&self, obj: &Self::Type) {
         obj.add_pad(&self.video_pad).unwrap();
 
@@ -80,7 +185,7 @@ impl ElementImpl for NdiSinkCombiner {
 
     fn pad_templates() -> &'static [gst::PadTemplate] {
         static PAD_TEMPLATES: Lazy<Vec<gst::PadTemplate>> = Lazy::new(|| {
-            let caps = gst::Caps::builder("video/x-raw")
+            let video_structure = gst::Structure::builder("video/x-raw")
                 .field(
                     "format",
                     &gst::List::new(&[
@@ -93,6 +198,8 @@ impl ElementImpl for NdiSinkCombiner {
                         &gst_video::VideoFormat::Bgrx.to_str(),
                         &gst_video::VideoFormat::Rgba.to_str(),
                         &gst_video::VideoFormat::Rgbx.to_str(),
+                        &gst_video::VideoFormat::Ayuv.to_str(),
+                        &gst_video::VideoFormat::Vuya.to_str(),
                     ]),
                 )
                 .field("width", &gst::IntRange::<i32>::new(1, i32::MAX))
@@ -105,6 +212,20 @@ impl ElementImpl for NdiSinkCombiner {
                     ),
                 )
                 .build();
+
+            let caps = gst::Caps::builder_full().structure(video_structure.clone());
+            // Alternate fields are only negotiated with the format:Interlaced caps feature
+            #[cfg(feature = "interlaced-fields")]
+            let caps = caps.structure_with_features(
+                {
+                    let mut s = video_structure;
+                    s.set("interlace-mode", "alternate");
+                    s
+                },
+                gst::CapsFeatures::new(&[*gst_video::CAPS_FEATURE_FORMAT_INTERLACED]),
+            );
+            let caps = caps.build();
+
             let src_pad_template = gst::PadTemplate::with_gtype(
                 "src",
                 gst::PadDirection::Src,
@@ -124,7 +245,13 @@ impl ElementImpl for NdiSinkCombiner {
             .unwrap();
 
             let caps = gst::Caps::builder("audio/x-raw")
-                .field("format", &gst_audio::AUDIO_FORMAT_F32.to_str())
+                .field(
+                    "format",
+                    &gst::List::new(&[
+                        &gst_audio::AUDIO_FORMAT_F32.to_str(),
+                        &gst_audio::AUDIO_FORMAT_S16.to_str(),
+                    ]),
+                )
                 .field("rate", &gst::IntRange::<i32>::new(1, i32::MAX))
                 .field("channels", &gst::IntRange::<i32>::new(1, i32::MAX))
                 .field("layout", &"interleaved")
@@ -194,6 +321,7 @@ impl AggregatorImpl for NdiSinkCombiner {
             audio_info: None,
             video_info: None,
             current_video_buffer: None,
+            pending_video_caps: None,
             current_audio_buffers: Vec::new(),
         });
 
@@ -262,9 +390,20 @@ impl AggregatorImpl for NdiSinkCombiner {
             )
         } else if let Some(ref video_info) = state.video_info {
             if video_info.fps().numer() > 0 {
+                // With alternate fields each buffer only covers half a frame
+                #[cfg(feature = "interlaced-fields")]
+                let fields =
+                    if video_info.interlace_mode() == gst_video::VideoInterlaceMode::Alternate {
+                        2
+                    } else {
+                        1
+                    };
+                #[cfg(not(feature = "interlaced-fields"))]
+                let fields = 1;
+
                 gst::ClockTime::SECOND.mul_div_floor(
                     video_info.fps().denom() as u64,
-                    video_info.fps().numer() as u64,
+                    fields * video_info.fps().numer() as u64,
                 )
             } else {
                 gst::ClockTime::NONE
@@ -496,9 +635,25 @@ impl AggregatorImpl for NdiSinkCombiner {
                     audio_running_time_end.display(),
                     current_video_running_time_end.display(),
                 );
+                let settings = self.settings.lock().unwrap().clone();
+                let (audio_buffer, audio_info) = if settings.downmix
+                    && settings.max_channels > 0
+                    && audio_info.channels() > settings.max_channels
+                {
+                    match downmix(&audio_buffer, audio_info, settings.max_channels) {
+                        Some(downmixed) => downmixed,
+                        None => {
+                            gst_error!(CAT, obj: agg, "Failed to downmix audio buffer");
+                            return Err(gst::FlowError::Error);
+                        }
+                    }
+                } else {
+                    (audio_buffer, audio_info.clone())
+                };
+
                 state
                     .current_audio_buffers
-                    .push((audio_buffer, audio_info.clone(), timecode));
+                    .push((audio_buffer, audio_info, timecode));
                 audio_pad.drop_buffer();
 
                 // If there is still video data, wait for the next audio buffer or EOS,
@@ -519,6 +674,8 @@ impl AggregatorImpl for NdiSinkCombiner {
             crate::ndisinkmeta::NdiSinkAudioMeta::add(current_video_buffer, audio_buffers);
         }
 
+        let pending_video_caps = state.pending_video_caps.take();
+
         if let Some((video_buffer, video_running_time)) = next_video_buffer {
             state.current_video_buffer = Some((video_buffer, video_running_time));
             drop(state_storage);
@@ -534,7 +691,16 @@ impl AggregatorImpl for NdiSinkCombiner {
             "Finishing video buffer {:?}",
             current_video_buffer
         );
-        agg.finish_buffer(current_video_buffer)
+        let res = agg.finish_buffer(current_video_buffer);
+
+        // Only switch to the new caps once all buffers with the old caps are output, otherwise
+        // the sink would interpret the queued buffer with the wrong size/format
+        if let Some(caps) = pending_video_caps {
+            gst_debug!(CAT, obj: agg, "Switching to new video caps {:?}", caps);
+            agg.set_src_caps(&caps);
+        }
+
+        res
     }
 
     fn sink_event(
@@ -577,12 +743,25 @@ impl AggregatorImpl for NdiSinkCombiner {
 
                     state.video_info = Some(info);
 
+                    // The video caps are passed through as the audio is included only in a meta.
+                    // If a video buffer with the previous caps is still queued then delay the
+                    // caps until it was output.
+                    let caps = if state.current_video_buffer.is_some() {
+                        gst_debug!(CAT, obj: pad, "Delaying new caps {:?}", caps);
+                        state.pending_video_caps = Some(caps);
+                        None
+                    } else {
+                        state.pending_video_caps = None;
+                        Some(caps)
+                    };
+
                     drop(state_storage);
 
                     agg.set_latency(latency, gst::ClockTime::NONE);
 
-                    // The video caps are passed through as the audio is included only in a meta
-                    agg.set_src_caps(&caps);
+                    if let Some(caps) = caps {
+                        agg.set_src_caps(&caps);
+                    }
                 } else {
                     let info = match gst_audio::AudioInfo::from_caps(&caps) {
                         Ok(info) => info,
@@ -592,6 +771,39 @@ impl AggregatorImpl for NdiSinkCombiner {
                         }
                     };
 
+                    let settings = self.settings.lock().unwrap().clone();
+                    if settings.max_channels > 0
+                        && info.channels() > settings.max_channels
+                        && !settings.downmix
+                    {
+                        gst_error!(
+                            CAT,
+                            obj: pad,
+                            "Audio with {} channels is more than the maximum of {}",
+                            info.channels(),
+                            settings.max_channels,
+                        );
+                        return false;
+                    }
+                    if settings.max_channels > 0
+                        && info.channels() > settings.max_channels
+                        && downmix_matrix(
+                            info.positions(),
+                            info.channels() as usize,
+                            settings.max_channels as usize,
+                        )
+                        .is_none()
+                    {
+                        gst_error!(
+                            CAT,
+                            obj: pad,
+                            "Can't downmix channel layout {:?} to {} channels",
+                            info.positions(),
+                            settings.max_channels,
+                        );
+                        return false;
+                    }
+
                     state.audio_info = Some(info);
                 }
             }
@@ -621,6 +833,31 @@ impl AggregatorImpl for NdiSinkCombiner {
                 let srcpad = agg.static_pad("src").unwrap();
                 return srcpad.peer_query(query);
             }
+            QueryView::Caps(ref mut q) => {
+                let settings = self.settings.lock().unwrap().clone();
+                if settings.max_channels > 0 && !settings.downmix {
+                    let mut caps = pad.pad_template_caps();
+                    {
+                        let caps = caps.make_mut();
+                        for s in caps.iter_mut() {
+                            s.set(
+                                "channels",
+                                &gst::IntRange::<i32>::new(1, settings.max_channels as i32),
+                            );
+                        }
+                    }
+
+                    let caps = match q.filter() {
+                        Some(filter) => {
+                            filter.intersect_with_mode(&caps, gst::CapsIntersectMode::First)
+                        }
+                        None => caps,
+                    };
+                    q.set_result(&caps);
+
+                    return true;
+                }
+            }
             _ => (),
         }
 
@@ -632,3 +869,283 @@ impl AggregatorImpl for NdiSinkCombiner {
         true
     }
 }
+
+// Gain of -3 dB for mixing a channel into another one
+const MIX_GAIN: f32 = std::f32::consts::FRAC_1_SQRT_2;
+
+// Returns the weights of every input channel for every output channel, with the weight of input
+// channel i for output channel o at o * in_channels + i.
+//
+// The first out_channels input channels are kept as they are and every further channel is mixed
+// into them based on its position: center and LFE channels at -3 dB into both front left and
+// front right, and all other channels at -3 dB into the front channel of their side. This needs
+// positioned input with front left and front right among the kept channels, so unpositioned
+// layouts are not supported. These are commonly used for more than 8 channels and always for
+// more than 64 channels. Downmixing to mono averages all input channels instead, and mono input
+// is never downmixed as there is nothing less than one channel.
+fn downmix_matrix(
+    positions: Option<&[gst_audio::AudioChannelPosition]>,
+    in_channels: usize,
+    out_channels: usize,
+) -> Option<Vec<f32>> {
+    use gst_audio::AudioChannelPosition as Position;
+
+    if out_channels == 1 {
+        return Some(vec![1.0 / in_channels as f32; in_channels]);
+    }
+
+    let positions = positions?;
+    let kept = &positions[..out_channels];
+    let left = kept.iter().position(|p| *p == Position::FrontLeft)?;
+    let right = kept.iter().position(|p| *p == Position::FrontRight)?;
+
+    let mut matrix = vec![0.0; out_channels * in_channels];
+    for c in 0..out_channels {
+        matrix[c * in_channels + c] = 1.0;
+    }
+    for (c, position) in positions.iter().enumerate().skip(out_channels) {
+        match position {
+            Position::FrontCenter
+            | Position::Lfe1
+            | Position::Lfe2
+            | Position::RearCenter
+            | Position::TopFrontCenter
+            | Position::TopCenter
+            | Position::TopRearCenter
+            | Position::BottomFrontCenter => {
+                matrix[left * in_channels + c] = MIX_GAIN;
+                matrix[right * in_channels + c] = MIX_GAIN;
+            }
+            Position::RearLeft
+            | Position::FrontLeftOfCenter
+            | Position::SideLeft
+            | Position::TopFrontLeft
+            | Position::TopRearLeft
+            | Position::TopSideLeft
+            | Position::BottomFrontLeft
+            | Position::WideLeft
+            | Position::SurroundLeft => matrix[left * in_channels + c] = MIX_GAIN,
+            Position::RearRight
+            | Position::FrontRightOfCenter
+            | Position::SideRight
+            | Position::TopFrontRight
+            | Position::TopRearRight
+            | Position::TopSideRight
+            | Position::BottomFrontRight
+            | Position::WideRight
+            | Position::SurroundRight => matrix[right * in_channels + c] = MIX_GAIN,
+            _ => return None,
+        }
+    }
+
+    Some(matrix)
+}
+
+// Downmixes interleaved F32 or S16 audio to the given number of channels as described for
+// downmix_matrix()
+fn downmix(
+    buffer: &gst::Buffer,
+    info: &gst_audio::AudioInfo,
+    channels: u32,
+) -> Option<(gst::Buffer, gst_audio::AudioInfo)> {
+    let in_channels = info.channels() as usize;
+    let out_channels = channels as usize;
+
+    let matrix = downmix_matrix(info.positions(), in_channels, out_channels)?;
+
+    let builder = gst_audio::AudioInfo::builder(info.format(), info.rate(), channels);
+    let out_info = match info.positions() {
+        Some(positions) if out_channels > 1 => builder.positions(&positions[..out_channels]),
+        _ => builder,
+    }
+    .build()
+    .ok()?;
+
+    let map = buffer.map_readable().ok()?;
+    let src = if info.format() == gst_audio::AUDIO_FORMAT_S16 {
+        map.as_slice_of::<i16>()
+            .ok()?
+            .iter()
+            .map(|sample| *sample as f32)
+            .collect::<Vec<_>>()
+    } else {
+        map.as_slice_of::<f32>().ok()?.to_vec()
+    };
+    let no_samples = src.len() / in_channels;
+
+    let mut dest = vec![0.0f32; no_samples * out_channels];
+    for (src, dest) in src
+        .chunks_exact(in_channels)
+        .zip(dest.chunks_exact_mut(out_channels))
+    {
+        for (weights, sample) in matrix.chunks_exact(in_channels).zip(dest.iter_mut()) {
+            *sample = src
+                .iter()
+                .zip(weights)
+                .map(|(sample, weight)| sample * weight)
+                .sum();
+        }
+    }
+
+    let dest = if info.format() == gst_audio::AUDIO_FORMAT_S16 {
+        dest.iter()
+            .map(|sample| sample.round() as i16)
+            .collect::<Vec<_>>()
+            .as_byte_slice()
+            .to_vec()
+    } else {
+        dest.as_byte_slice().to_vec()
+    };
+
+    let mut out_buffer = gst::Buffer::from_mut_slice(dest);
+    {
+        let out_buffer = out_buffer.get_mut().unwrap();
+        out_buffer.set_pts(buffer.pts());
+        out_buffer.set_duration(buffer.duration());
+    }
+
+    Some((out_buffer, out_info))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use gst_audio::AudioChannelPosition as Position;
+
+    const SURROUND_5_1: [Position; 6] = [
+        Position::FrontLeft,
+        Position::FrontRight,
+        Position::FrontCenter,
+        Position::Lfe1,
+        Position::RearLeft,
+        Position::RearRight,
+    ];
+
+    #[test]
+    fn downmix_5_1_to_stereo() {
+        let matrix = downmix_matrix(Some(&SURROUND_5_1), 6, 2).unwrap();
+        assert_eq!(
+            matrix,
+            [
+                [1.0, 0.0, MIX_GAIN, MIX_GAIN, MIX_GAIN, 0.0],
+                [0.0, 1.0, MIX_GAIN, MIX_GAIN, 0.0, MIX_GAIN],
+            ]
+            .concat()
+        );
+    }
+
+    #[test]
+    fn downmix_5_1_to_quad() {
+        // The kept rear channels are not mixed into the front channels
+        let positions = [
+            Position::FrontLeft,
+            Position::FrontRight,
+            Position::RearLeft,
+            Position::RearRight,
+            Position::FrontCenter,
+            Position::Lfe1,
+        ];
+        let matrix = downmix_matrix(Some(&positions), 6, 4).unwrap();
+        assert_eq!(
+            matrix,
+            [
+                [1.0, 0.0, 0.0, 0.0, MIX_GAIN, MIX_GAIN],
+                [0.0, 1.0, 0.0, 0.0, MIX_GAIN, MIX_GAIN],
+                [0.0, 0.0, 1.0, 0.0, 0.0, 0.0],
+                [0.0, 0.0, 0.0, 1.0, 0.0, 0.0],
+            ]
+            .concat()
+        );
+    }
+
+    #[test]
+    fn downmix_to_mono() {
+        // Mono averages all channels, independent of their positions
+        assert_eq!(downmix_matrix(None, 4, 1).unwrap(), [0.25; 4]);
+    }
+
+    #[test]
+    fn downmix_unsupported_layouts() {
+        // Unpositioned channels can't be mixed
+        assert_eq!(downmix_matrix(None, 16, 8), None);
+        assert_eq!(downmix_matrix(Some(&[Position::None; 16]), 16, 8), None);
+        // Front left and front right have to be kept
+        assert_eq!(downmix_matrix(Some(&SURROUND_5_1[2..]), 4, 2), None);
+    }
+
+    fn video_caps(width: i32) -> gst::Caps {
+        gst_video::VideoInfo::builder(gst_video::VideoFormat::Uyvy, width as u32, 240)
+            .fps(gst::Fraction::new(30, 1))
+            .build()
+            .unwrap()
+            .to_caps()
+            .unwrap()
+    }
+
+    fn pending_video_caps(combiner: &super::super::NdiSinkCombiner) -> Option<gst::Caps> {
+        let imp = NdiSinkCombiner::from_instance(combiner);
+        let state = imp.state.lock().unwrap();
+        state.as_ref().unwrap().pending_video_caps.clone()
+    }
+
+    #[test]
+    fn video_caps_wait_for_queued_buffer() {
+        crate::testing::init();
+
+        let combiner = glib::Object::new::<super::super::NdiSinkCombiner>(&[]).unwrap();
+        let imp = NdiSinkCombiner::from_instance(&combiner);
+        imp.start(&combiner).unwrap();
+
+        // Without a queued video buffer the caps apply directly
+        assert!(imp.sink_event(
+            &combiner,
+            &imp.video_pad,
+            gst::event::Caps::new(&video_caps(320))
+        ));
+        assert_eq!(pending_video_caps(&combiner), None);
+
+        // With a queued video buffer the caps wait until it was output, and only the latest
+        // caps are kept
+        imp.state
+            .lock()
+            .unwrap()
+            .as_mut()
+            .unwrap()
+            .current_video_buffer = Some((gst::Buffer::new(), gst::ClockTime::ZERO));
+        assert!(imp.sink_event(
+            &combiner,
+            &imp.video_pad,
+            gst::event::Caps::new(&video_caps(640))
+        ));
+        assert_eq!(pending_video_caps(&combiner), Some(video_caps(640)));
+        assert!(imp.sink_event(
+            &combiner,
+            &imp.video_pad,
+            gst::event::Caps::new(&video_caps(1280))
+        ));
+        assert_eq!(pending_video_caps(&combiner), Some(video_caps(1280)));
+
+        // The video info already follows the new caps for the buffer pending on the pad
+        let state = imp.state.lock().unwrap();
+        assert_eq!(
+            state.as_ref().unwrap().video_info.as_ref().unwrap().width(),
+            1280
+        );
+        drop(state);
+
+        // Once the queued buffer is gone new caps apply directly again and drop the pending ones
+        imp.state
+            .lock()
+            .unwrap()
+            .as_mut()
+            .unwrap()
+            .current_video_buffer = None;
+        assert!(imp.sink_event(
+            &combiner,
+            &imp.video_pad,
+            gst::event::Caps::new(&video_caps(320))
+        ));
+        assert_eq!(pending_video_caps(&combiner), None);
+    }
+}
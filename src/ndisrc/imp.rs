@@ -1,22 +1,26 @@
 use gst::prelude::*;
 use gst::subclass::prelude::*;
-use gst::{gst_debug, gst_error};
+use gst::{gst_debug, gst_error, gst_info, gst_warning};
 use gst_base::prelude::*;
 use gst_base::subclass::base_src::CreateSuccess;
 use gst_base::subclass::prelude::*;
 
-use std::sync::Mutex;
+use std::sync::{Condvar, Mutex};
+use std::time;
 use std::{i32, u32};
 
 use once_cell::sync::Lazy;
 
-use crate::ndisys;
-
+use crate::connect_retry_delay;
+use crate::ndi::{SharedFind, CONTEXT_TYPE};
 use crate::ndisrcmeta;
 use crate::Buffer;
+use crate::NdiNameMatch;
 use crate::Receiver;
 use crate::ReceiverControlHandle;
 use crate::ReceiverItem;
+use crate::ReceiverSettings;
+use crate::RecvBandwidth;
 use crate::RecvColorFormat;
 use crate::TimestampMode;
 use crate::DEFAULT_RECEIVER_NDI_NAME;
@@ -32,28 +36,76 @@ static CAT: Lazy<gst::DebugCategory> = Lazy::new(|| {
 #[derive(Debug, Clone)]
 struct Settings {
     ndi_name: Option<String>,
+    ndi_name_match: NdiNameMatch,
+    source_index: i32,
     url_address: Option<String>,
+    extra_ips: Option<String>,
     connect_timeout: u32,
     timeout: u32,
+    frame_timeout: u32,
     max_queue_length: u32,
     receiver_ndi_name: String,
-    bandwidth: ndisys::NDIlib_recv_bandwidth_e,
+    bandwidth: RecvBandwidth,
     color_format: RecvColorFormat,
     timestamp_mode: TimestampMode,
+    allow_video_fields: bool,
+    hwaccel: bool,
+    reference_timestamps: bool,
+    timing_meta: bool,
+    freeze_on_loss: bool,
+    fill_audio_gaps: bool,
+    capture_retries: u32,
+    connect_retries: u32,
+    connect_retry_interval: u32,
+    reconnect_prefer_url: bool,
+    aac_adts: bool,
+    strict_metadata: bool,
+    colorimetry: Option<String>,
+    video_caps: Option<gst::Caps>,
+    preferred_formats: Option<String>,
+    preserve_stride: bool,
+    ignore_alpha: bool,
+    force_progressive: bool,
+    audio_caps: Option<gst::Caps>,
+    reference_level: i32,
 }
 
 impl Default for Settings {
     fn default() -> Self {
         Settings {
             ndi_name: None,
+            ndi_name_match: NdiNameMatch::Exact,
+            source_index: -1,
             url_address: None,
+            extra_ips: None,
             receiver_ndi_name: DEFAULT_RECEIVER_NDI_NAME.clone(),
             connect_timeout: 10000,
             timeout: 5000,
+            frame_timeout: 0,
             max_queue_length: 10,
-            bandwidth: ndisys::NDIlib_recv_bandwidth_highest,
+            bandwidth: RecvBandwidth::Highest,
             color_format: RecvColorFormat::UyvyBgra,
             timestamp_mode: TimestampMode::ReceiveTimeTimecode,
+            allow_video_fields: true,
+            hwaccel: true,
+            reference_timestamps: cfg!(feature = "reference-timestamps"),
+            timing_meta: false,
+            freeze_on_loss: false,
+            fill_audio_gaps: false,
+            aac_adts: false,
+            strict_metadata: false,
+            colorimetry: None,
+            video_caps: None,
+            preferred_formats: None,
+            preserve_stride: false,
+            ignore_alpha: false,
+            force_progressive: false,
+            audio_caps: None,
+            reference_level: 0,
+            capture_retries: 5,
+            connect_retries: 0,
+            connect_retry_interval: 1000,
+            reconnect_prefer_url: false,
         }
     }
 }
@@ -65,6 +117,10 @@ struct State {
     audio_caps: Option<gst::Caps>,
     current_latency: Option<gst::ClockTime>,
     receiver: Option<Receiver>,
+    // URL/address of the source the receiver is connected to, if known
+    source_url: Option<String>,
+    // Set by start(), the receiver is only connected from the streaming thread
+    connect_settings: Option<(Settings, ReceiverSettings)>,
 }
 
 impl Default for State {
@@ -76,14 +132,42 @@ impl Default for State {
             audio_caps: None,
             current_latency: gst::ClockTime::NONE,
             receiver: None,
+            source_url: None,
+            connect_settings: None,
         }
     }
 }
 
+// Looking up and connecting to the source can take arbitrarily long, e.g. with a connect-timeout
+// of 0 or many connect-retries, so it happens from the streaming thread where unlock() and stop()
+// can interrupt it
+#[derive(Default)]
+struct ConnectState {
+    flushing: bool,
+    // The receiver is only created once playing, so it has to be told about the state afterwards
+    playing: bool,
+}
+
+enum ConnectError {
+    Flushing,
+    Error(gst::ErrorMessage),
+}
+
+impl From<gst::ErrorMessage> for ConnectError {
+    fn from(err: gst::ErrorMessage) -> Self {
+        ConnectError::Error(err)
+    }
+}
+
 pub struct NdiSrc {
     settings: Mutex<Settings>,
     state: Mutex<State>,
     receiver_controller: Mutex<Option<ReceiverControlHandle>>,
+    find: Mutex<Option<SharedFind>>,
+    // URL/address of the source frames were last received from, kept across restarts
+    last_url: Mutex<Option<String>>,
+    connect_state: Mutex<ConnectState>,
+    connect_cond: Condvar,
 }
 
 #[glib::object_subclass]
@@ -97,6 +181,10 @@ impl ObjectSubclass for NdiSrc {
             settings: Mutex::new(Default::default()),
             state: Mutex::new(Default::default()),
             receiver_controller: Mutex::new(None),
+            find: Mutex::new(None),
+            last_url: Mutex::new(None),
+            connect_state: Mutex::new(Default::default()),
+            connect_cond: Condvar::new(),
         }
     }
 }
@@ -108,17 +196,44 @@ impl ObjectImpl for NdiSrc {
                 glib::ParamSpecString::new(
                     "ndi-name",
                     "NDI Name",
-                    "NDI stream name of the sender",
+                    "NDI stream name of the sender, only used if no URL/address is set",
                     None,
                     glib::ParamFlags::READWRITE,
                 ),
+                glib::ParamSpecEnum::new(
+                    "ndi-name-match",
+                    "NDI Name Match",
+                    "How the NDI name is matched against the \"MACHINE (Source Name)\" names of \
+                     discovered sources",
+                    NdiNameMatch::static_type(),
+                    NdiNameMatch::Exact as i32,
+                    glib::ParamFlags::READWRITE,
+                ),
+                glib::ParamSpecInt::new(
+                    "source-index",
+                    "Source Index",
+                    "Index of the source to connect to if multiple sources match the NDI name, \
+                     ordered by their URL/address (-1 = any)",
+                    -1,
+                    i32::MAX,
+                    -1,
+                    glib::ParamFlags::READWRITE,
+                ),
                 glib::ParamSpecString::new(
                     "url-address",
                     "URL/Address",
-                    "URL/address and port of the sender, e.g. 127.0.0.1:5961",
+                    "URL/address and port of the sender, e.g. 127.0.0.1:5961. Takes precedence over the NDI name",
                     None,
                     glib::ParamFlags::READWRITE,
                 ),
+                glib::ParamSpecString::new(
+                    "extra-ips",
+                    "Extra IPs",
+                    "Comma or space separated IP addresses of senders in other subnets to \
+                     discover sources from, for the find instance created by this element",
+                    None,
+                    glib::ParamFlags::READWRITE | gst::PARAM_FLAG_MUTABLE_READY,
+                ),
                 glib::ParamSpecString::new(
                     "receiver-ndi-name",
                     "Receiver NDI Name",
@@ -138,12 +253,33 @@ impl ObjectImpl for NdiSrc {
                 glib::ParamSpecUInt::new(
                     "timeout",
                     "Timeout",
-                    "Receive timeout in ms",
+                    "Deprecated, use source-timeout instead which has the same meaning",
+                    0,
+                    u32::MAX,
+                    5000,
+                    glib::ParamFlags::READWRITE | glib::ParamFlags::DEPRECATED,
+                ),
+                glib::ParamSpecUInt::new(
+                    "source-timeout",
+                    "Source Timeout",
+                    "Time in ms without any frame after which the source is considered gone and \
+                     EOS is signalled (0 = never)",
                     0,
                     u32::MAX,
                     5000,
                     glib::ParamFlags::READWRITE,
                 ),
+                glib::ParamSpecUInt::new(
+                    "frame-timeout",
+                    "Frame Timeout",
+                    "Time in ms without any frame after which a warning is posted and a gap is \
+                     output, repeatedly until frames arrive again or source-timeout is reached \
+                     (0 = disabled)",
+                    0,
+                    u32::MAX,
+                    0,
+                    glib::ParamFlags::READWRITE,
+                ),
                 glib::ParamSpecUInt::new(
                     "max-queue-length",
                     "Max Queue Length",
@@ -153,23 +289,75 @@ impl ObjectImpl for NdiSrc {
                     10,
                     glib::ParamFlags::READWRITE,
                 ),
-                glib::ParamSpecInt::new(
+                glib::ParamSpecEnum::new(
                     "bandwidth",
                     "Bandwidth",
-                    "Bandwidth, -10 metadata-only, 10 audio-only, 100 highest",
-                    -10,
-                    100,
-                    100,
+                    "Bandwidth to request from the sender, changes take effect on the next connection. \
+                     With metadata-only no audio/video is produced, only gaps and ndi-metadata \
+                     element messages. With audio-only video frames are dropped without \
+                     processing them. With lowest the sender's proxy stream is received, which \
+                     usually has a lower resolution (typically 640 pixels wide)",
+                    RecvBandwidth::static_type(),
+                    RecvBandwidth::Highest as i32,
                     glib::ParamFlags::READWRITE,
                 ),
                 glib::ParamSpecEnum::new(
                     "color-format",
                     "Color Format",
-                    "Receive color format",
+                    "Receive color format. Sources with an alpha channel are received as \
+                     BGRA/RGBA with the formats ending in -bgra/-rgba. With fastest or best \
+                     planar YUV formats are passed through without conversion if the sender \
+                     provides them. With the advanced SDK, compressed-v3 and later pass through \
+                     H.264/H.265 video as byte-stream with profile and level in the caps",
                     RecvColorFormat::static_type(),
                     RecvColorFormat::UyvyBgra as u32 as i32,
                     glib::ParamFlags::READWRITE,
                 ),
+                glib::ParamSpecString::new(
+                    "colorimetry",
+                    "Colorimetry",
+                    "Colorimetry of YUV video, e.g. bt709. By default this is taken from the \
+                     source's HDR metadata or otherwise BT.709 for HD and BT.601 for SD",
+                    None,
+                    glib::ParamFlags::READWRITE,
+                ),
+                glib::ParamSpecBoxed::new(
+                    "video-caps",
+                    "Video Caps",
+                    "Restricts the raw video formats that are produced, e.g. \
+                     video/x-raw,format=BGRA. The receive color format is selected accordingly \
+                     and frames in other formats cause a negotiation error",
+                    gst::Caps::static_type(),
+                    glib::ParamFlags::READWRITE,
+                ),
+                glib::ParamSpecString::new(
+                    "preferred-formats",
+                    "Preferred Formats",
+                    "Comma-separated raw video formats in order of preference, e.g. BGRA,UYVY. \
+                     The receive color format that provides most of them is selected. \
+                     Ignored if video-caps is set",
+                    None,
+                    glib::ParamFlags::READWRITE,
+                ),
+                glib::ParamSpecBoxed::new(
+                    "audio-caps",
+                    "Audio Caps",
+                    "Restricts the raw audio formats that are produced, e.g. \
+                     audio/x-raw,format=S16LE. The native F32 is produced if allowed, otherwise \
+                     the audio is converted to S16 with full range corresponding to \
+                     reference-level",
+                    gst::Caps::static_type(),
+                    glib::ParamFlags::READWRITE,
+                ),
+                glib::ParamSpecInt::new(
+                    "reference-level",
+                    "Reference Level",
+                    "dB above the +4 dBu reference level that full range 16 bit audio corresponds to",
+                    -100,
+                    100,
+                    0,
+                    glib::ParamFlags::READWRITE,
+                ),
                 glib::ParamSpecEnum::new(
                     "timestamp-mode",
                     "Timestamp Mode",
@@ -178,12 +366,256 @@ impl ObjectImpl for NdiSrc {
                     TimestampMode::ReceiveTimeTimecode as i32,
                     glib::ParamFlags::READWRITE,
                 ),
+                glib::ParamSpecBoolean::new(
+                    "allow-video-fields",
+                    "Allow Video Fields",
+                    "Allow receiving separate video fields instead of only full interlaced frames",
+                    true,
+                    glib::ParamFlags::READWRITE,
+                ),
+                glib::ParamSpecBoolean::new(
+                    "force-progressive",
+                    "Force Progressive",
+                    "Only receive full frames and mark them as progressive instead of \
+                     interlaced. This does not deinterlace, combine with a deinterlacer \
+                     downstream for that",
+                    false,
+                    glib::ParamFlags::READWRITE,
+                ),
+                glib::ParamSpecBoolean::new(
+                    "hwaccel",
+                    "Hardware Acceleration",
+                    "Ask the NDI SDK to use hardware accelerated decoding if available",
+                    true,
+                    glib::ParamFlags::READWRITE,
+                ),
+                glib::ParamSpecBoolean::new(
+                    "preserve-stride",
+                    "Preserve Stride",
+                    "Keep the line stride of the NDI SDK for raw video and describe it with a \
+                     video meta instead of repacking to the default stride. Requires downstream \
+                     to support video meta",
+                    false,
+                    glib::ParamFlags::READWRITE,
+                ),
+                glib::ParamSpecBoolean::new(
+                    "ignore-alpha",
+                    "Ignore Alpha",
+                    "Output BGRx/RGBx instead of BGRA/RGBA for sources with alpha. Without this, \
+                     this is only done if video-caps allows just the format without alpha",
+                    false,
+                    glib::ParamFlags::READWRITE | gst::PARAM_FLAG_MUTABLE_READY,
+                ),
+                glib::ParamSpecBoolean::new(
+                    "freeze-on-loss",
+                    "Freeze On Loss",
+                    "Repeat the last video frame and output silence while no data is received \
+                     from the source, until the timeout or frame-timeout applies",
+                    false,
+                    glib::ParamFlags::READWRITE,
+                ),
+                glib::ParamSpecBoolean::new(
+                    "fill-audio-gaps",
+                    "Fill Audio Gaps",
+                    "Insert silence for missing audio frames to keep audio and video in sync. \
+                     Gaps longer than one second are marked as discontinuity instead",
+                    false,
+                    glib::ParamFlags::READWRITE,
+                ),
+                glib::ParamSpecUInt::new(
+                    "capture-retries",
+                    "Capture Retries",
+                    "Number of consecutive receive errors to retry before failing",
+                    0,
+                    u32::MAX,
+                    5,
+                    glib::ParamFlags::READWRITE,
+                ),
+                glib::ParamSpecUInt::new(
+                    "connect-retries",
+                    "Connect Retries",
+                    "Number of times to retry connecting if the source can't be found or sends \
+                     nothing within connect-timeout before failing",
+                    0,
+                    u32::MAX,
+                    0,
+                    glib::ParamFlags::READWRITE,
+                ),
+                glib::ParamSpecUInt::new(
+                    "connect-retry-interval",
+                    "Connect Retry Interval",
+                    "Delay in ms before the first connection retry, doubled for every further \
+                     retry up to 64 times this value",
+                    0,
+                    u32::MAX,
+                    1000,
+                    glib::ParamFlags::READWRITE,
+                ),
+                glib::ParamSpecBoolean::new(
+                    "reconnect-prefer-url",
+                    "Reconnect Prefer URL",
+                    "When connecting again after frames were received from a source, first try \
+                     its last URL/address for up to connect-timeout and only then look the source \
+                     up by its NDI name",
+                    false,
+                    glib::ParamFlags::READWRITE,
+                ),
+                glib::ParamSpecBoolean::new(
+                    "connected",
+                    "Connected",
+                    "If a frame was received from the source since starting",
+                    false,
+                    glib::ParamFlags::READABLE,
+                ),
+                glib::ParamSpecBoolean::new(
+                    "has-audio",
+                    "Has Audio",
+                    "If audio was received from the source",
+                    false,
+                    glib::ParamFlags::READABLE,
+                ),
+                glib::ParamSpecBoolean::new(
+                    "has-video",
+                    "Has Video",
+                    "If video was received from the source",
+                    false,
+                    glib::ParamFlags::READABLE,
+                ),
+                glib::ParamSpecBoolean::new(
+                    "ptz-supported",
+                    "PTZ Supported",
+                    "If the source can be controlled via PTZ",
+                    false,
+                    glib::ParamFlags::READABLE,
+                ),
+                glib::ParamSpecString::new(
+                    "web-control-url",
+                    "Web Control URL",
+                    "URL of the web interface for configuring the source, if any",
+                    None,
+                    glib::ParamFlags::READABLE,
+                ),
+                glib::ParamSpecBoxed::new(
+                    "source-info",
+                    "Source Info",
+                    "Product name, model and other information about the connected source",
+                    gst::Structure::static_type(),
+                    glib::ParamFlags::READABLE,
+                ),
+                glib::ParamSpecBoxed::new(
+                    "queue-depth",
+                    "Queue Depth",
+                    "Number of audio, video and metadata frames currently queued in the receiver",
+                    gst::Structure::static_type(),
+                    glib::ParamFlags::READABLE,
+                ),
+                glib::ParamSpecDouble::new(
+                    "clock-drift",
+                    "Clock Drift",
+                    "Estimated drift of the source clock against the pipeline clock in ppm, \
+                     positive if the source clock runs slower. Only available with the \
+                     receive-time timestamp modes, otherwise 0",
+                    f64::MIN,
+                    f64::MAX,
+                    0.0,
+                    glib::ParamFlags::READABLE,
+                ),
+                glib::ParamSpecString::new(
+                    "ndi-lib-version",
+                    "NDI Library Version",
+                    "Version of the NDI runtime library in use",
+                    None,
+                    glib::ParamFlags::READABLE,
+                ),
+                #[cfg(feature = "reference-timestamps")]
+                glib::ParamSpecBoolean::new(
+                    "reference-timestamps",
+                    "Reference Timestamps",
+                    "Attach the NDI timecode and timestamp as reference timestamp metas",
+                    true,
+                    glib::ParamFlags::READWRITE,
+                ),
+                glib::ParamSpecBoolean::new(
+                    "timing-meta",
+                    "Timing Meta",
+                    "Attach the raw NDI timecode and timestamp in 100ns units as NdiTimingMeta",
+                    false,
+                    glib::ParamFlags::READWRITE | gst::PARAM_FLAG_MUTABLE_READY,
+                ),
+                #[cfg(feature = "advanced-sdk")]
+                glib::ParamSpecBoolean::new(
+                    "aac-adts",
+                    "AAC ADTS",
+                    "Output compressed AAC audio with ADTS headers instead of raw AAC",
+                    false,
+                    glib::ParamFlags::READWRITE,
+                ),
+                glib::ParamSpecBoolean::new(
+                    "strict-metadata",
+                    "Strict Metadata",
+                    "Fail on received metadata that is not valid UTF-8 instead of replacing \
+                     the invalid parts",
+                    false,
+                    glib::ParamFlags::READWRITE | gst::PARAM_FLAG_MUTABLE_READY,
+                ),
             ]
         });
 
         PROPERTIES.as_ref()
     }
 
+    fn signals() -> &'static [glib::subclass::Signal] {
+        static SIGNALS: Lazy<Vec<glib::subclass::Signal>> = Lazy::new(|| {
+            #[allow(unused_mut)]
+            let mut signals = vec![
+                glib::subclass::Signal::builder(
+                    "wait-for-connection",
+                    &[u32::static_type().into()],
+                    bool::static_type().into(),
+                )
+                .action()
+                .class_handler(|_, args| {
+                    let element = args[0].get::<super::NdiSrc>().expect("signal arg");
+                    let timeout = args[1].get::<u32>().expect("signal arg");
+                    let imp = NdiSrc::from_instance(&element);
+
+                    Some(imp.wait_for_connection(&element, timeout).to_value())
+                })
+                .build(),
+                glib::subclass::Signal::builder(
+                    "scan-sources",
+                    &[u32::static_type().into()],
+                    Vec::<String>::static_type().into(),
+                )
+                .action()
+                .class_handler(|_, args| {
+                    let element = args[0].get::<super::NdiSrc>().expect("signal arg");
+                    let timeout = args[1].get::<u32>().expect("signal arg");
+                    let imp = NdiSrc::from_instance(&element);
+
+                    Some(imp.scan_sources(&element, timeout).to_value())
+                })
+                .build(),
+            ];
+
+            // Emitted from the receive thread with the timecode in ns and the raw XML of every
+            // <ndi_kvm> metadata message the source sends over the metadata channel
+            #[cfg(feature = "kvm")]
+            signals.push(
+                glib::subclass::Signal::builder(
+                    "kvm-data",
+                    &[u64::static_type().into(), glib::Bytes::static_type().into()],
+                    glib::Type::UNIT.into(),
+                )
+                .build(),
+            );
+
+            signals
+        });
+
+        SIGNALS.as_ref()
+    }
+
     fn constructed(&self, obj: &Self::Type) {
         self.parent_constructed(obj);
 
@@ -213,6 +645,90 @@ impl ObjectImpl for NdiSrc {
                 );
                 settings.ndi_name = ndi_name;
             }
+            "ndi-name-match" => {
+                let mut settings = self.settings.lock().unwrap();
+                let ndi_name_match = value.get().unwrap();
+                gst_debug!(
+                    CAT,
+                    obj: obj,
+                    "Changing ndi-name-match from {:?} to {:?}",
+                    settings.ndi_name_match,
+                    ndi_name_match,
+                );
+                settings.ndi_name_match = ndi_name_match;
+            }
+            "source-index" => {
+                let mut settings = self.settings.lock().unwrap();
+                let source_index = value.get().unwrap();
+                gst_debug!(
+                    CAT,
+                    obj: obj,
+                    "Changing source-index from {} to {}",
+                    settings.source_index,
+                    source_index,
+                );
+                settings.source_index = source_index;
+            }
+            "colorimetry" => {
+                let mut settings = self.settings.lock().unwrap();
+                let colorimetry = value.get().unwrap();
+                gst_debug!(
+                    CAT,
+                    obj: obj,
+                    "Changing colorimetry from {:?} to {:?}",
+                    settings.colorimetry,
+                    colorimetry,
+                );
+                settings.colorimetry = colorimetry;
+            }
+            "video-caps" => {
+                let mut settings = self.settings.lock().unwrap();
+                let video_caps = value.get().unwrap();
+                gst_debug!(
+                    CAT,
+                    obj: obj,
+                    "Changing video-caps from {:?} to {:?}",
+                    settings.video_caps,
+                    video_caps,
+                );
+                settings.video_caps = video_caps;
+            }
+            "audio-caps" => {
+                let mut settings = self.settings.lock().unwrap();
+                let audio_caps = value.get().unwrap();
+                gst_debug!(
+                    CAT,
+                    obj: obj,
+                    "Changing audio-caps from {:?} to {:?}",
+                    settings.audio_caps,
+                    audio_caps,
+                );
+                settings.audio_caps = audio_caps;
+            }
+            "reference-level" => {
+                let mut settings = self.settings.lock().unwrap();
+                let reference_level = value.get().unwrap();
+                gst_debug!(
+                    CAT,
+                    obj: obj,
+                    "Changing reference-level from {} to {}",
+                    settings.reference_level,
+                    reference_level,
+                );
+                settings.reference_level = reference_level;
+            }
+            "preferred-formats" => {
+                let mut settings = self.settings.lock().unwrap();
+                let preferred_formats = value.get().unwrap();
+                gst_debug!(
+                    CAT,
+                    obj: obj,
+                    "Changing preferred-formats from {:?} to {:?}",
+                    settings.preferred_formats,
+                    preferred_formats,
+                );
+                settings.preferred_formats = preferred_formats;
+            }
             "url-address" => {
                 let mut settings = self.settings.lock().unwrap();
                 let url_address = value.get().unwrap();
@@ -225,6 +741,18 @@ impl ObjectImpl for NdiSrc {
                 );
                 settings.url_address = url_address;
             }
+            "extra-ips" => {
+                let mut settings = self.settings.lock().unwrap();
+                let extra_ips = value.get().unwrap();
+                gst_debug!(
+                    CAT,
+                    obj: obj,
+                    "Changing extra-ips from {:?} to {:?}",
+                    settings.extra_ips,
+                    extra_ips,
+                );
+                settings.extra_ips = extra_ips;
+            }
             "receiver-ndi-name" => {
                 let mut settings = self.settings.lock().unwrap();
                 let receiver_ndi_name = value.get::<Option<String>>().unwrap();
@@ -250,18 +778,30 @@ impl ObjectImpl for NdiSrc {
                 );
                 settings.connect_timeout = connect_timeout;
             }
-            "timeout" => {
+            "timeout" | "source-timeout" => {
                 let mut settings = self.settings.lock().unwrap();
                 let timeout = value.get().unwrap();
                 gst_debug!(
                     CAT,
                     obj: obj,
-                    "Changing timeout from {} to {}",
+                    "Changing source-timeout from {} to {}",
                     settings.timeout,
                     timeout,
                 );
                 settings.timeout = timeout;
             }
+            "frame-timeout" => {
+                let mut settings = self.settings.lock().unwrap();
+                let frame_timeout = value.get().unwrap();
+                gst_debug!(
+                    CAT,
+                    obj: obj,
+                    "Changing frame-timeout from {} to {}",
+                    settings.frame_timeout,
+                    frame_timeout,
+                );
+                settings.frame_timeout = frame_timeout;
+            }
             "max-queue-length" => {
                 let mut settings = self.settings.lock().unwrap();
                 let max_queue_length = value.get().unwrap();
@@ -280,7 +820,7 @@ impl ObjectImpl for NdiSrc {
                 gst_debug!(
                     CAT,
                     obj: obj,
-                    "Changing bandwidth from {} to {}",
+                    "Changing bandwidth from {:?} to {:?}",
                     settings.bandwidth,
                     bandwidth,
                 );
@@ -313,6 +853,188 @@ impl ObjectImpl for NdiSrc {
                 }
                 settings.timestamp_mode = timestamp_mode;
             }
+            "allow-video-fields" => {
+                let mut settings = self.settings.lock().unwrap();
+                let allow_video_fields = value.get().unwrap();
+                gst_debug!(
+                    CAT,
+                    obj: obj,
+                    "Changing allow-video-fields from {} to {}",
+                    settings.allow_video_fields,
+                    allow_video_fields,
+                );
+                settings.allow_video_fields = allow_video_fields;
+            }
+            "force-progressive" => {
+                let mut settings = self.settings.lock().unwrap();
+                let force_progressive = value.get().unwrap();
+                gst_debug!(
+                    CAT,
+                    obj: obj,
+                    "Changing force-progressive from {} to {}",
+                    settings.force_progressive,
+                    force_progressive,
+                );
+                settings.force_progressive = force_progressive;
+            }
+            "hwaccel" => {
+                let mut settings = self.settings.lock().unwrap();
+                let hwaccel = value.get().unwrap();
+                gst_debug!(
+                    CAT,
+                    obj: obj,
+                    "Changing hwaccel from {} to {}",
+                    settings.hwaccel,
+                    hwaccel,
+                );
+                settings.hwaccel = hwaccel;
+            }
+            "freeze-on-loss" => {
+                let mut settings = self.settings.lock().unwrap();
+                let freeze_on_loss = value.get().unwrap();
+                gst_debug!(
+                    CAT,
+                    obj: obj,
+                    "Changing freeze-on-loss from {} to {}",
+                    settings.freeze_on_loss,
+                    freeze_on_loss,
+                );
+                settings.freeze_on_loss = freeze_on_loss;
+            }
+            "preserve-stride" => {
+                let mut settings = self.settings.lock().unwrap();
+                let preserve_stride = value.get().unwrap();
+                gst_debug!(
+                    CAT,
+                    obj: obj,
+                    "Changing preserve-stride from {} to {}",
+                    settings.preserve_stride,
+                    preserve_stride,
+                );
+                settings.preserve_stride = preserve_stride;
+            }
+            "ignore-alpha" => {
+                let mut settings = self.settings.lock().unwrap();
+                let ignore_alpha = value.get().unwrap();
+                gst_debug!(
+                    CAT,
+                    obj: obj,
+                    "Changing ignore-alpha from {} to {}",
+                    settings.ignore_alpha,
+                    ignore_alpha,
+                );
+                settings.ignore_alpha = ignore_alpha;
+            }
+            "fill-audio-gaps" => {
+                let mut settings = self.settings.lock().unwrap();
+                let fill_audio_gaps = value.get().unwrap();
+                gst_debug!(
+                    CAT,
+                    obj: obj,
+                    "Changing fill-audio-gaps from {} to {}",
+                    settings.fill_audio_gaps,
+                    fill_audio_gaps,
+                );
+                settings.fill_audio_gaps = fill_audio_gaps;
+            }
+            "capture-retries" => {
+                let mut settings = self.settings.lock().unwrap();
+                let capture_retries = value.get().unwrap();
+                gst_debug!(
+                    CAT,
+                    obj: obj,
+                    "Changing capture-retries from {} to {}",
+                    settings.capture_retries,
+                    capture_retries,
+                );
+                settings.capture_retries = capture_retries;
+            }
+            "connect-retries" => {
+                let mut settings = self.settings.lock().unwrap();
+                let connect_retries = value.get().unwrap();
+                gst_debug!(
+                    CAT,
+                    obj: obj,
+                    "Changing connect-retries from {} to {}",
+                    settings.connect_retries,
+                    connect_retries,
+                );
+                settings.connect_retries = connect_retries;
+            }
+            "connect-retry-interval" => {
+                let mut settings = self.settings.lock().unwrap();
+                let connect_retry_interval = value.get().unwrap();
+                gst_debug!(
+                    CAT,
+                    obj: obj,
+                    "Changing connect-retry-interval from {} to {}",
+                    settings.connect_retry_interval,
+                    connect_retry_interval,
+                );
+                settings.connect_retry_interval = connect_retry_interval;
+            }
+            "reconnect-prefer-url" => {
+                let mut settings = self.settings.lock().unwrap();
+                let reconnect_prefer_url = value.get().unwrap();
+                gst_debug!(
+                    CAT,
+                    obj: obj,
+                    "Changing reconnect-prefer-url from {} to {}",
+                    settings.reconnect_prefer_url,
+                    reconnect_prefer_url,
+                );
+                settings.reconnect_prefer_url = reconnect_prefer_url;
+            }
+            #[cfg(feature = "reference-timestamps")]
+            "reference-timestamps" => {
+                let mut settings = self.settings.lock().unwrap();
+                let reference_timestamps = value.get().unwrap();
+                gst_debug!(
+                    CAT,
+                    obj: obj,
+                    "Changing reference-timestamps from {} to {}",
+                    settings.reference_timestamps,
+                    reference_timestamps,
+                );
+                settings.reference_timestamps = reference_timestamps;
+            }
+            "timing-meta" => {
+                let mut settings = self.settings.lock().unwrap();
+                let timing_meta = value.get().unwrap();
+                gst_debug!(
+                    CAT,
+                    obj: obj,
+                    "Changing timing-meta from {} to {}",
+                    settings.timing_meta,
+                    timing_meta,
+                );
+                settings.timing_meta = timing_meta;
+            }
+            #[cfg(feature = "advanced-sdk")]
+            "aac-adts" => {
+                let mut settings = self.settings.lock().unwrap();
+                let aac_adts = value.get().unwrap();
+                gst_debug!(
+                    CAT,
+                    obj: obj,
+                    "Changing aac-adts from {} to {}",
+                    settings.aac_adts,
+                    aac_adts,
+                );
+                settings.aac_adts = aac_adts;
+            }
+            "strict-metadata" => {
+                let mut settings = self.settings.lock().unwrap();
+                let strict_metadata = value.get().unwrap();
+                gst_debug!(
+                    CAT,
+                    obj: obj,
+                    "Changing strict-metadata from {} to {}",
+                    settings.strict_metadata,
+                    strict_metadata,
+                );
+                settings.strict_metadata = strict_metadata;
+            }
             _ => unimplemented!(),
         }
     }
@@ -323,10 +1045,22 @@ impl ObjectImpl for NdiSrc {
                 let settings = self.settings.lock().unwrap();
                 settings.ndi_name.to_value()
             }
-            "url-address" => {
+            "ndi-name-match" => {
+                let settings = self.settings.lock().unwrap();
+                settings.ndi_name_match.to_value()
+            }
+            "source-index" => {
+                let settings = self.settings.lock().unwrap();
+                settings.source_index.to_value()
+            }
+            "url-address" => {
                 let settings = self.settings.lock().unwrap();
                 settings.url_address.to_value()
             }
+            "extra-ips" => {
+                let settings = self.settings.lock().unwrap();
+                settings.extra_ips.to_value()
+            }
             "receiver-ndi-name" => {
                 let settings = self.settings.lock().unwrap();
                 settings.receiver_ndi_name.to_value()
@@ -335,10 +1069,14 @@ impl ObjectImpl for NdiSrc {
                 let settings = self.settings.lock().unwrap();
                 settings.connect_timeout.to_value()
             }
-            "timeout" => {
+            "timeout" | "source-timeout" => {
                 let settings = self.settings.lock().unwrap();
                 settings.timeout.to_value()
             }
+            "frame-timeout" => {
+                let settings = self.settings.lock().unwrap();
+                settings.frame_timeout.to_value()
+            }
             "max-queue-length" => {
                 let settings = self.settings.lock().unwrap();
                 settings.max_queue_length.to_value()
@@ -355,6 +1093,150 @@ impl ObjectImpl for NdiSrc {
                 let settings = self.settings.lock().unwrap();
                 settings.timestamp_mode.to_value()
             }
+            "allow-video-fields" => {
+                let settings = self.settings.lock().unwrap();
+                settings.allow_video_fields.to_value()
+            }
+            "force-progressive" => {
+                let settings = self.settings.lock().unwrap();
+                settings.force_progressive.to_value()
+            }
+            "hwaccel" => {
+                let settings = self.settings.lock().unwrap();
+                settings.hwaccel.to_value()
+            }
+            "freeze-on-loss" => {
+                let settings = self.settings.lock().unwrap();
+                settings.freeze_on_loss.to_value()
+            }
+            "preserve-stride" => {
+                let settings = self.settings.lock().unwrap();
+                settings.preserve_stride.to_value()
+            }
+            "ignore-alpha" => {
+                let settings = self.settings.lock().unwrap();
+                settings.ignore_alpha.to_value()
+            }
+            "fill-audio-gaps" => {
+                let settings = self.settings.lock().unwrap();
+                settings.fill_audio_gaps.to_value()
+            }
+            "capture-retries" => {
+                let settings = self.settings.lock().unwrap();
+                settings.capture_retries.to_value()
+            }
+            "connect-retries" => {
+                let settings = self.settings.lock().unwrap();
+                settings.connect_retries.to_value()
+            }
+            "connect-retry-interval" => {
+                let settings = self.settings.lock().unwrap();
+                settings.connect_retry_interval.to_value()
+            }
+            "reconnect-prefer-url" => {
+                let settings = self.settings.lock().unwrap();
+                settings.reconnect_prefer_url.to_value()
+            }
+            "connected" => {
+                let controller = self.receiver_controller.lock().unwrap();
+                controller
+                    .as_ref()
+                    .map(|controller| controller.is_connected())
+                    .unwrap_or(false)
+                    .to_value()
+            }
+            "has-audio" => {
+                let controller = self.receiver_controller.lock().unwrap();
+                controller
+                    .as_ref()
+                    .map(|controller| controller.has_audio())
+                    .unwrap_or(false)
+                    .to_value()
+            }
+            "has-video" => {
+                let controller = self.receiver_controller.lock().unwrap();
+                controller
+                    .as_ref()
+                    .map(|controller| controller.has_video())
+                    .unwrap_or(false)
+                    .to_value()
+            }
+            "ptz-supported" => {
+                let controller = self.receiver_controller.lock().unwrap();
+                controller
+                    .as_ref()
+                    .map(|controller| controller.ptz_supported())
+                    .unwrap_or(false)
+                    .to_value()
+            }
+            "web-control-url" => {
+                let controller = self.receiver_controller.lock().unwrap();
+                controller
+                    .as_ref()
+                    .and_then(|controller| controller.web_control_url())
+                    .to_value()
+            }
+            "source-info" => {
+                let controller = self.receiver_controller.lock().unwrap();
+                controller
+                    .as_ref()
+                    .and_then(|controller| controller.source_info())
+                    .to_value()
+            }
+            "queue-depth" => {
+                let controller = self.receiver_controller.lock().unwrap();
+                controller
+                    .as_ref()
+                    .and_then(|controller| controller.queue_depth())
+                    .to_value()
+            }
+            "clock-drift" => {
+                let controller = self.receiver_controller.lock().unwrap();
+                controller
+                    .as_ref()
+                    .and_then(|controller| controller.clock_drift())
+                    .unwrap_or(0.0)
+                    .to_value()
+            }
+            "ndi-lib-version" => crate::ndi::version().to_value(),
+            #[cfg(feature = "reference-timestamps")]
+            "reference-timestamps" => {
+                let settings = self.settings.lock().unwrap();
+                settings.reference_timestamps.to_value()
+            }
+            "timing-meta" => {
+                let settings = self.settings.lock().unwrap();
+                settings.timing_meta.to_value()
+            }
+            #[cfg(feature = "advanced-sdk")]
+            "aac-adts" => {
+                let settings = self.settings.lock().unwrap();
+                settings.aac_adts.to_value()
+            }
+            "strict-metadata" => {
+                let settings = self.settings.lock().unwrap();
+                settings.strict_metadata.to_value()
+            }
+            "colorimetry" => {
+                let settings = self.settings.lock().unwrap();
+                settings.colorimetry.to_value()
+            }
+            "video-caps" => {
+                let settings = self.settings.lock().unwrap();
+                settings.video_caps.to_value()
+            }
+            "preferred-formats" => {
+                let settings = self.settings.lock().unwrap();
+                settings.preferred_formats.to_value()
+            }
+            "audio-caps" => {
+                let settings = self.settings.lock().unwrap();
+                settings.audio_caps.to_value()
+            }
+            "reference-level" => {
+                let settings = self.settings.lock().unwrap();
+                settings.reference_level.to_value()
+            }
             _ => unimplemented!(),
         }
     }
@@ -362,6 +1244,346 @@ impl ObjectImpl for NdiSrc {
 
 impl GstObjectImpl for NdiSrc {}
 
+impl NdiSrc {
+    // Blocks for at most timeout ms until the first frame was received from the source. Returns
+    // false if the element is not started or failed to connect in the meantime
+    fn wait_for_connection(&self, element: &super::NdiSrc, timeout: u32) -> bool {
+        let timeout = time::Duration::from_millis(timeout as u64);
+        let start = time::Instant::now();
+
+        // The receiver is only created once streaming, so first wait for it. Don't keep the
+        // controller locked while waiting for the connection so that stopping is not blocked.
+        let mut connect_state = self.connect_state.lock().unwrap();
+        let controller = loop {
+            if let Some(ref controller) = *self.receiver_controller.lock().unwrap() {
+                break controller.clone();
+            }
+            if self.state.lock().unwrap().connect_settings.is_none() {
+                gst_debug!(CAT, obj: element, "Not started, can't wait for connection");
+                return false;
+            }
+            let elapsed = start.elapsed();
+            if elapsed >= timeout {
+                gst_debug!(CAT, obj: element, "No receiver created before the timeout");
+                return false;
+            }
+            connect_state = self
+                .connect_cond
+                .wait_timeout(connect_state, timeout - elapsed)
+                .unwrap()
+                .0;
+        };
+        drop(connect_state);
+
+        let connected = controller.wait_for_connection(timeout.saturating_sub(start.elapsed()));
+        gst_debug!(CAT, obj: element, "Waited for connection: {}", connected);
+
+        connected
+    }
+
+    // Returns the sources currently discovered by the shared find instance as "name (url)". If
+    // none were discovered yet, this blocks for up to the timeout in milliseconds until the first
+    // ones show up.
+    fn scan_sources(&self, element: &super::NdiSrc, timeout: u32) -> Vec<String> {
+        let find = match self.shared_find(element) {
+            Some(find) => find,
+            None => {
+                gst_error!(CAT, obj: element, "Failed to create NDI find instance");
+                return Vec::new();
+            }
+        };
+
+        let mut sources = find.current_sources();
+        if sources.is_empty() && find.wait_for_sources(timeout) {
+            sources = find.current_sources();
+        }
+        gst_debug!(CAT, obj: element, "Found {} sources", sources.len());
+
+        sources.iter().map(|source| source.to_string()).collect()
+    }
+
+    // Returns the find instance shared in the pipeline via a gst.ndi.Context. If there is none
+    // yet, downstream and then the application are asked for it, and if nobody has one a new one
+    // is created and announced to the other elements.
+    fn shared_find(&self, element: &super::NdiSrc) -> Option<SharedFind> {
+        if let Some(ref find) = *self.find.lock().unwrap() {
+            return Some(find.clone());
+        }
+
+        let mut query = gst::query::Context::new(CONTEXT_TYPE);
+        if element.static_pad("src").unwrap().peer_query(&mut query) {
+            if let Some(context) = query.context_owned() {
+                element.set_context(&context);
+            }
+        }
+
+        if self.find.lock().unwrap().is_none() {
+            let _ = element.post_message(
+                gst::message::NeedContext::builder(CONTEXT_TYPE)
+                    .src(element)
+                    .build(),
+            );
+        }
+
+        let mut find_guard = self.find.lock().unwrap();
+        if let Some(ref find) = *find_guard {
+            return Some(find.clone());
+        }
+
+        let extra_ips = self.settings.lock().unwrap().extra_ips.clone();
+        gst_debug!(
+            CAT,
+            obj: element,
+            "Creating new shared NDI find instance with extra IPs {:?}",
+            extra_ips
+        );
+        let find = SharedFind::new(extra_ips.as_deref())?;
+        *find_guard = Some(find.clone());
+        drop(find_guard);
+
+        let _ = element.post_message(
+            gst::message::HaveContext::builder(find.to_context())
+                .src(element)
+                .build(),
+        );
+
+        Some(find)
+    }
+
+    // Looks for a discovered source whose name matches the configured NDI name according to
+    // the match policy, waiting at most connect-timeout for it to appear. With a source index the
+    // matching sources are ordered by URL/address and the URL/address of the selected one is
+    // returned too, as connecting by name alone would pick any of the sources with that name.
+    fn resolve_source(
+        &self,
+        element: &super::NdiSrc,
+        settings: &Settings,
+        find: Option<&SharedFind>,
+    ) -> Result<(String, Option<String>), ConnectError> {
+        let name = settings.ndi_name.as_deref().unwrap();
+
+        let find = find.ok_or_else(|| {
+            gst::error_msg!(
+                gst::ResourceError::NotFound,
+                ["Failed to create NDI find instance"]
+            )
+        })?;
+
+        let start = std::time::Instant::now();
+        loop {
+            let sources = find.current_sources();
+            let mut sources = sources
+                .iter()
+                .filter(|source| settings.ndi_name_match.matches(name, source.ndi_name()))
+                .collect::<Vec<_>>();
+
+            if settings.source_index < 0 {
+                if let Some(source) = sources.first() {
+                    gst_debug!(CAT, obj: element, "Resolved NDI name '{}' to {}", name, source);
+                    return Ok((source.ndi_name().to_owned(), None));
+                }
+            } else {
+                sources.sort_by(|a, b| a.url_address().cmp(b.url_address()));
+                if let Some(source) = sources.get(settings.source_index as usize) {
+                    gst_debug!(
+                        CAT,
+                        obj: element,
+                        "Resolved NDI name '{}' with index {} to {}",
+                        name,
+                        settings.source_index,
+                        source
+                    );
+                    return Ok((
+                        source.ndi_name().to_owned(),
+                        Some(source.url_address().to_owned()),
+                    ));
+                }
+            }
+
+            // A connect-timeout of 0 means waiting forever
+            let elapsed = start.elapsed().as_millis() as u32;
+            if settings.connect_timeout > 0 && elapsed >= settings.connect_timeout {
+                if settings.source_index < 0 {
+                    return Err(gst::error_msg!(
+                        gst::ResourceError::NotFound,
+                        ["No NDI source matching '{}' found", name]
+                    )
+                    .into());
+                } else {
+                    return Err(gst::error_msg!(
+                        gst::ResourceError::NotFound,
+                        [
+                            "Found {} NDI sources matching '{}', wanted index {}",
+                            sources.len(),
+                            name,
+                            settings.source_index
+                        ]
+                    )
+                    .into());
+                }
+            }
+
+            // Wait in small steps to notice flushing in time
+            let timeout = if settings.connect_timeout > 0 {
+                std::cmp::min(settings.connect_timeout - elapsed, 100)
+            } else {
+                100
+            };
+            find.wait_for_sources(timeout);
+            if self.connect_state.lock().unwrap().flushing {
+                gst_debug!(CAT, obj: element, "Flushing while looking for the source");
+                return Err(ConnectError::Flushing);
+            }
+        }
+    }
+
+    // Waits for the given time unless flushing, returns false if flushing
+    fn wait_unless_flushing(&self, timeout: time::Duration) -> bool {
+        let connect_state = self.connect_state.lock().unwrap();
+        let (connect_state, _) = self
+            .connect_cond
+            .wait_timeout_while(connect_state, timeout, |connect_state| {
+                !connect_state.flushing
+            })
+            .unwrap();
+        !connect_state.flushing
+    }
+
+    // Makes the receiver reachable from unlock() and the state changes
+    fn set_receiver_controller(&self, receiver: &Receiver) {
+        // unlock() might have been called before the controller was set
+        let connect_state = self.connect_state.lock().unwrap();
+        *self.receiver_controller.lock().unwrap() = Some(receiver.receiver_control_handle());
+        receiver.set_flushing(connect_state.flushing);
+        receiver.set_playing(connect_state.playing);
+        self.connect_cond.notify_all();
+    }
+
+    fn connect(
+        &self,
+        element: &super::NdiSrc,
+        settings: &Settings,
+        receiver_settings: &ReceiverSettings,
+    ) -> Result<(Receiver, Option<String>), ConnectError> {
+        // Sources given by URL/address are connected to directly and don't need discovery
+        let find = if settings.url_address.is_none() {
+            self.shared_find(element)
+        } else {
+            None
+        };
+
+        let connect = |last_url: Option<&str>| -> Result<_, ConnectError> {
+            // If an URL/address is given we connect directly to it, otherwise the source is looked
+            // up by its NDI name
+            let resolved_source;
+            let (ndi_name, url_address) = match (settings.url_address.as_deref(), last_url) {
+                (Some(url_address), _) | (None, Some(url_address)) => (None, Some(url_address)),
+                (None, None)
+                    if settings.ndi_name_match != NdiNameMatch::Exact
+                        || settings.source_index >= 0 =>
+                {
+                    resolved_source = self.resolve_source(element, settings, find.as_ref())?;
+                    (
+                        Some(resolved_source.0.as_str()),
+                        resolved_source.1.as_deref(),
+                    )
+                }
+                (None, None) => (settings.ndi_name.as_deref(), None),
+            };
+
+            // Remember the URL/address to be able to prefer it when connecting again
+            let source_url = match (url_address, ndi_name, find.as_ref()) {
+                (Some(url_address), _, _) => Some(url_address.to_owned()),
+                (None, Some(ndi_name), Some(find)) => find
+                    .current_sources()
+                    .iter()
+                    .find(|source| source.ndi_name() == ndi_name)
+                    .map(|source| source.url_address().to_owned()),
+                _ => None,
+            };
+
+            let receiver = Receiver::connect(
+                element.upcast_ref(),
+                ndi_name,
+                url_address,
+                receiver_settings,
+                find.clone(),
+            )
+            .ok_or_else(|| {
+                gst::error_msg!(
+                    gst::ResourceError::NotFound,
+                    ["Could not connect to this source"]
+                )
+            })?;
+            self.set_receiver_controller(&receiver);
+
+            Ok((receiver, source_url))
+        };
+
+        // The source might have been renamed since, e.g. by devices that append their state to
+        // the name, so first try the URL/address frames were last received from and only look up
+        // the source by name if nothing is received from there
+        let last_url = if settings.reconnect_prefer_url && settings.url_address.is_none() {
+            self.last_url.lock().unwrap().clone()
+        } else {
+            None
+        };
+        if let Some(ref last_url) = last_url {
+            gst_debug!(CAT, obj: element, "Trying last URL/address {} first", last_url);
+            match connect(Some(last_url)) {
+                // Without a connect-timeout this would wait forever, so stay with the URL
+                Ok(connection) if settings.connect_timeout == 0 => return Ok(connection),
+                Ok((receiver, source_url))
+                    if receiver.wait_for_connection(time::Duration::from_millis(
+                        settings.connect_timeout as u64,
+                    )) =>
+                {
+                    return Ok((receiver, source_url));
+                }
+                Err(ConnectError::Flushing) => return Err(ConnectError::Flushing),
+                _ if self.connect_state.lock().unwrap().flushing => {
+                    return Err(ConnectError::Flushing)
+                }
+                _ => {
+                    gst_info!(
+                        CAT,
+                        obj: element,
+                        "Nothing received from last URL/address {}, looking up source by name",
+                        last_url
+                    );
+                    *self.receiver_controller.lock().unwrap() = None;
+                }
+            }
+        }
+
+        // Retry with exponential backoff, e.g. if the source is started after this element
+        let mut retry = 0;
+        loop {
+            match connect(None) {
+                Ok(connection) => return Ok(connection),
+                Err(ConnectError::Error(err)) if retry < settings.connect_retries => {
+                    let delay = connect_retry_delay(settings.connect_retry_interval, retry);
+                    retry += 1;
+                    gst_warning!(
+                        CAT,
+                        obj: element,
+                        "Failed to connect: {}, retrying in {:?} ({}/{})",
+                        err,
+                        delay,
+                        retry,
+                        settings.connect_retries,
+                    );
+                    if !self.wait_unless_flushing(delay) {
+                        gst_debug!(CAT, obj: element, "Flushing while waiting to retry");
+                        return Err(ConnectError::Flushing);
+                    }
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
 impl ElementImpl for NdiSrc {
     fn metadata() -> Option<&'static gst::subclass::ElementMetadata> {
         static ELEMENT_METADATA: Lazy<gst::subclass::ElementMetadata> = Lazy::new(|| {
@@ -392,6 +1614,15 @@ impl ElementImpl for NdiSrc {
         PAD_TEMPLATES.as_ref()
     }
 
+    fn set_context(&self, element: &Self::Type, context: &gst::Context) {
+        if let Some(find) = SharedFind::from_context(context) {
+            gst_debug!(CAT, obj: element, "Using shared NDI find instance from context");
+            *self.find.lock().unwrap() = Some(find);
+        }
+
+        self.parent_set_context(element, context);
+    }
+
     fn change_state(
         &self,
         element: &Self::Type,
@@ -399,11 +1630,13 @@ impl ElementImpl for NdiSrc {
     ) -> Result<gst::StateChangeSuccess, gst::StateChangeError> {
         match transition {
             gst::StateChange::PausedToPlaying => {
+                self.connect_state.lock().unwrap().playing = true;
                 if let Some(ref controller) = *self.receiver_controller.lock().unwrap() {
                     controller.set_playing(true);
                 }
             }
             gst::StateChange::PlayingToPaused => {
+                self.connect_state.lock().unwrap().playing = false;
                 if let Some(ref controller) = *self.receiver_controller.lock().unwrap() {
                     controller.set_playing(false);
                 }
@@ -427,8 +1660,11 @@ impl BaseSrcImpl for NdiSrc {
             .map_err(|_| gst::loggable_error!(CAT, "Failed to negotiate caps",))
     }
 
+    // Also called by the base class for flush-start/flush-stop events, e.g. when seeking
     fn unlock(&self, element: &Self::Type) -> Result<(), gst::ErrorMessage> {
         gst_debug!(CAT, obj: element, "Unlocking",);
+        self.connect_state.lock().unwrap().flushing = true;
+        self.connect_cond.notify_all();
         if let Some(ref controller) = *self.receiver_controller.lock().unwrap() {
             controller.set_flushing(true);
         }
@@ -437,6 +1673,7 @@ impl BaseSrcImpl for NdiSrc {
 
     fn unlock_stop(&self, element: &Self::Type) -> Result<(), gst::ErrorMessage> {
         gst_debug!(CAT, obj: element, "Stop unlocking",);
+        self.connect_state.lock().unwrap().flushing = false;
         if let Some(ref controller) = *self.receiver_controller.lock().unwrap() {
             controller.set_flushing(false);
         }
@@ -444,6 +1681,10 @@ impl BaseSrcImpl for NdiSrc {
     }
 
     fn start(&self, element: &Self::Type) -> Result<(), gst::ErrorMessage> {
+        if !crate::ndi::is_initialized() {
+            return Err(crate::ndi::initialization_error());
+        }
+
         *self.state.lock().unwrap() = Default::default();
         let settings = self.settings.lock().unwrap().clone();
 
@@ -454,43 +1695,172 @@ impl BaseSrcImpl for NdiSrc {
             ));
         }
 
-        let receiver = Receiver::connect(
-            element.upcast_ref(),
-            settings.ndi_name.as_deref(),
-            settings.url_address.as_deref(),
-            &settings.receiver_ndi_name,
-            settings.connect_timeout,
-            settings.bandwidth,
-            settings.color_format.into(),
-            settings.timestamp_mode,
-            settings.timeout,
-            settings.max_queue_length as usize,
-        );
+        let colorimetry = match settings.colorimetry {
+            Some(ref colorimetry) => Some(
+                colorimetry
+                    .parse::<gst_video::VideoColorimetry>()
+                    .map_err(|_| {
+                        gst::error_msg!(
+                            gst::LibraryError::Settings,
+                            ["Invalid colorimetry {}", colorimetry]
+                        )
+                    })?,
+            ),
+            None => None,
+        };
 
-        match receiver {
-            None => Err(gst::error_msg!(
-                gst::ResourceError::NotFound,
-                ["Could not connect to this source"]
-            )),
-            Some(receiver) => {
-                *self.receiver_controller.lock().unwrap() =
-                    Some(receiver.receiver_control_handle());
-                let mut state = self.state.lock().unwrap();
-                state.receiver = Some(receiver);
+        let audio_format = match settings.audio_caps {
+            Some(ref audio_caps) => {
+                let audio_format = audio_format_from_caps(audio_caps).ok_or_else(|| {
+                    gst::error_msg!(
+                        gst::LibraryError::Settings,
+                        ["No supported audio format in audio-caps {:?}", audio_caps]
+                    )
+                })?;
+                gst_debug!(CAT, obj: element, "Using audio format {}", audio_format);
+                audio_format
+            }
+            None => gst_audio::AUDIO_FORMAT_F32,
+        };
 
-                Ok(())
+        let video_formats = settings
+            .video_caps
+            .as_ref()
+            .and_then(video_formats_from_caps);
+        let color_format = match video_formats {
+            Some(ref video_formats) => {
+                let color_format =
+                    color_format_for_video_formats(video_formats).ok_or_else(|| {
+                        gst::error_msg!(
+                            gst::LibraryError::Settings,
+                            [
+                                "No supported video format in video-caps {:?}",
+                                settings.video_caps
+                            ]
+                        )
+                    })?;
+                gst_debug!(
+                    CAT,
+                    obj: element,
+                    "Using color format {:?} for video formats {:?}",
+                    color_format,
+                    video_formats,
+                );
+                color_format
             }
+            None => match settings.preferred_formats {
+                Some(ref preferred_formats) => {
+                    let preferred_formats = preferred_formats
+                        .split(',')
+                        .map(|format| {
+                            format
+                                .trim()
+                                .parse::<gst_video::VideoFormat>()
+                                .map_err(|_| {
+                                    gst::error_msg!(
+                                        gst::LibraryError::Settings,
+                                        ["Invalid video format {} in preferred-formats", format]
+                                    )
+                                })
+                        })
+                        .collect::<Result<Vec<_>, _>>()?;
+                    let color_format = color_format_for_video_formats(&preferred_formats)
+                        .ok_or_else(|| {
+                            gst::error_msg!(
+                                gst::LibraryError::Settings,
+                                [
+                                    "No supported video format in preferred-formats {:?}",
+                                    preferred_formats
+                                ]
+                            )
+                        })?;
+                    gst_debug!(
+                        CAT,
+                        obj: element,
+                        "Using color format {:?} for preferred formats {:?}",
+                        color_format,
+                        preferred_formats,
+                    );
+                    color_format
+                }
+                None => settings.color_format,
+            },
+        };
+
+        let receiver_settings = ReceiverSettings {
+            receiver_ndi_name: settings.receiver_ndi_name.clone(),
+            bandwidth: settings.bandwidth.into(),
+            color_format: color_format.into(),
+            allow_video_fields: settings.allow_video_fields,
+            force_progressive: settings.force_progressive,
+            hwaccel: settings.hwaccel,
+            timestamp_mode: settings.timestamp_mode,
+            reference_timestamps: settings.reference_timestamps,
+            timing_meta: settings.timing_meta,
+            freeze_on_loss: settings.freeze_on_loss,
+            fill_audio_gaps: settings.fill_audio_gaps,
+            capture_retries: settings.capture_retries,
+            connect_retries: settings.connect_retries,
+            connect_retry_interval: settings.connect_retry_interval,
+            aac_adts: settings.aac_adts,
+            strict_metadata: settings.strict_metadata,
+            colorimetry,
+            video_formats,
+            preserve_stride: settings.preserve_stride,
+            ignore_alpha: settings.ignore_alpha,
+            audio_format,
+            reference_level: settings.reference_level,
+            timeout: settings.timeout,
+            frame_timeout: settings.frame_timeout,
+            connect_timeout: settings.connect_timeout,
+            max_queue_length: settings.max_queue_length as usize,
+        };
+
+        // Start discovering sources right away, the receiver is connected once streaming
+        if settings.url_address.is_none() {
+            let _ = self.shared_find(element);
         }
+
+        self.connect_state.lock().unwrap().flushing = false;
+        self.state.lock().unwrap().connect_settings = Some((settings, receiver_settings));
+
+        Ok(())
     }
 
     fn stop(&self, _element: &Self::Type) -> Result<(), gst::ErrorMessage> {
+        self.connect_state.lock().unwrap().flushing = true;
+
+        let mut state = self.state.lock().unwrap();
         if let Some(ref controller) = self.receiver_controller.lock().unwrap().take() {
+            if controller.is_connected() && state.source_url.is_some() {
+                *self.last_url.lock().unwrap() = state.source_url.take();
+            }
             controller.shutdown();
         }
-        *self.state.lock().unwrap() = State::default();
+        *state = State::default();
+        drop(state);
+
+        // Wakes up anybody waiting for the receiver to be created
+        self.connect_cond.notify_all();
         Ok(())
     }
 
+    fn event(&self, element: &Self::Type, event: &gst::Event) -> bool {
+        use gst::EventView;
+
+        if let EventView::CustomUpstream(ev) = event.view() {
+            if ev.structure().map(|s| s.name()) == Some("ndi-seek-to-live") {
+                gst_debug!(CAT, obj: element, "Seeking to live");
+                if let Some(ref controller) = *self.receiver_controller.lock().unwrap() {
+                    controller.seek_to_live();
+                }
+                return true;
+            }
+        }
+
+        self.parent_event(element, event)
+    }
+
     fn query(&self, element: &Self::Type, query: &mut gst::QueryRef) -> bool {
         use gst::QueryView;
 
@@ -500,6 +1870,15 @@ impl BaseSrcImpl for NdiSrc {
                 q.add_scheduling_modes(&[gst::PadMode::Push]);
                 true
             }
+            QueryView::Context(ref mut q) if q.context_type() == CONTEXT_TYPE => {
+                match *self.find.lock().unwrap() {
+                    Some(ref find) => {
+                        q.set_context(&find.to_context());
+                        true
+                    }
+                    None => false,
+                }
+            }
             QueryView::Latency(ref mut q) => {
                 let state = self.state.lock().unwrap();
                 let settings = self.settings.lock().unwrap();
@@ -507,14 +1886,17 @@ impl BaseSrcImpl for NdiSrc {
                 if let Some(latency) = state.current_latency {
                     let min = if matches!(
                         settings.timestamp_mode,
-                        TimestampMode::ReceiveTimeTimecode | TimestampMode::ReceiveTimeTimestamp
+                        TimestampMode::ReceiveTimeTimecode
+                            | TimestampMode::ReceiveTimeTimestamp
+                            | TimestampMode::Auto
                     ) {
                         latency
                     } else {
                         gst::ClockTime::ZERO
                     };
 
-                    let max = settings.max_queue_length as u64 * latency;
+                    // Up to max-queue-length frames can be queued up in addition
+                    let max = min + settings.max_queue_length as u64 * latency;
 
                     gst_debug!(
                         CAT,
@@ -542,9 +1924,23 @@ impl BaseSrcImpl for NdiSrc {
     ) -> Result<CreateSuccess, gst::FlowError> {
         let recv = {
             let mut state = self.state.lock().unwrap();
-            match state.receiver.take() {
-                Some(recv) => recv,
-                None => {
+            match (state.receiver.take(), state.connect_settings.clone()) {
+                (Some(recv), _) => recv,
+                (None, Some((settings, receiver_settings))) => {
+                    drop(state);
+                    match self.connect(element, &settings, &receiver_settings) {
+                        Ok((recv, source_url)) => {
+                            self.state.lock().unwrap().source_url = source_url;
+                            recv
+                        }
+                        Err(ConnectError::Flushing) => return Err(gst::FlowError::Flushing),
+                        Err(ConnectError::Error(err)) => {
+                            element.post_error_message(err);
+                            return Err(gst::FlowError::Error);
+                        }
+                    }
+                }
+                (None, None) => {
                     gst_error!(CAT, obj: element, "Have no receiver");
                     return Err(gst::FlowError::Error);
                 }
@@ -560,6 +1956,8 @@ impl BaseSrcImpl for NdiSrc {
             ReceiverItem::Buffer(buffer) => {
                 let buffer = match buffer {
                     Buffer::Audio(mut buffer, info) => {
+                        let mut latency_changed = false;
+
                         if state.audio_info.as_ref() != Some(&info) {
                             let caps = info.to_caps().map_err(|_| {
                                 gst::element_error!(
@@ -573,6 +1971,13 @@ impl BaseSrcImpl for NdiSrc {
                             state.audio_caps = Some(caps);
                         }
 
+                        // For audio-only sources the audio frame duration defines the latency,
+                        // otherwise the video frame duration
+                        if state.video_info.is_none() && buffer.duration().is_some() {
+                            latency_changed = state.current_latency != buffer.duration();
+                            state.current_latency = buffer.duration();
+                        }
+
                         {
                             let buffer = buffer.get_mut().unwrap();
                             ndisrcmeta::NdiSrcMeta::add(
@@ -582,6 +1987,13 @@ impl BaseSrcImpl for NdiSrc {
                             );
                         }
 
+                        drop(state);
+                        if latency_changed {
+                            let _ = element.post_message(
+                                gst::message::Latency::builder().src(element).build(),
+                            );
+                        }
+
                         buffer
                     }
                     Buffer::Video(mut buffer, info) => {
@@ -624,9 +2036,284 @@ impl BaseSrcImpl for NdiSrc {
 
                 Ok(CreateSuccess::NewBuffer(buffer))
             }
-            ReceiverItem::Timeout => Err(gst::FlowError::Eos),
-            ReceiverItem::Flushing => Err(gst::FlowError::Flushing),
-            ReceiverItem::Error(err) => Err(err),
+            ReceiverItem::Gap(pts, duration) => {
+                // Empty buffer without NDI source meta, ndisrcdemux converts it to GAP events
+                let mut buffer = gst::Buffer::new();
+                {
+                    let buffer = buffer.get_mut().unwrap();
+                    buffer.set_pts(pts);
+                    buffer.set_duration(duration);
+                    buffer.set_flags(gst::BufferFlags::GAP | gst::BufferFlags::DROPPABLE);
+                }
+
+                Ok(CreateSuccess::NewBuffer(buffer))
+            }
+            ReceiverItem::Timeout => {
+                gst_debug!(CAT, obj: element, "Source timed out, signalling EOS");
+                Err(gst::FlowError::Eos)
+            }
+            ReceiverItem::Flushing => {
+                gst_debug!(CAT, obj: element, "Flushing");
+                Err(gst::FlowError::Flushing)
+            }
+            ReceiverItem::Error(err) => {
+                gst_debug!(CAT, obj: element, "Receiver error {:?}", err);
+                Err(err)
+            }
+        }
+    }
+}
+
+// Raw video formats allowed by the given caps, or None if any format is allowed
+fn video_formats_from_caps(caps: &gst::Caps) -> Option<Vec<gst_video::VideoFormat>> {
+    let mut formats = Vec::new();
+
+    for s in caps.iter().filter(|s| s.name() == "video/x-raw") {
+        if let Ok(format) = s.get::<&str>("format") {
+            formats.push(format.parse::<gst_video::VideoFormat>().ok()?);
+        } else if let Ok(list) = s.get::<gst::List>("format") {
+            for format in list.as_slice() {
+                formats.push(format.get::<&str>().ok()?.parse().ok()?);
+            }
+        } else {
+            return None;
+        }
+    }
+
+    if formats.is_empty() {
+        None
+    } else {
+        Some(formats)
+    }
+}
+
+// Selects the raw audio format to produce for the given caps, preferring the native F32 of the SDK
+// over S16. Caps without format allow any format.
+fn audio_format_from_caps(caps: &gst::Caps) -> Option<gst_audio::AudioFormat> {
+    let mut formats = Vec::new();
+
+    for s in caps.iter().filter(|s| s.name() == "audio/x-raw") {
+        if let Ok(format) = s.get::<&str>("format") {
+            formats.push(String::from(format));
+        } else if let Ok(list) = s.get::<gst::List>("format") {
+            for format in list.as_slice() {
+                formats.push(String::from(format.get::<&str>().ok()?));
+            }
+        } else {
+            return Some(gst_audio::AUDIO_FORMAT_F32);
         }
     }
+
+    [gst_audio::AUDIO_FORMAT_F32, gst_audio::AUDIO_FORMAT_S16]
+        .iter()
+        .copied()
+        .find(|format| formats.iter().any(|f| f == format.to_str()))
+}
+
+// Selects the receive color format that produces most of the given video formats, preferring the
+// one that produces the format listed first if multiple produce the same number. The SDK always
+// produces one format for opaque and one for transparent video, so e.g. for BGRA-only output
+// sources without alpha channel would still be received as BGRX.
+fn color_format_for_video_formats(formats: &[gst_video::VideoFormat]) -> Option<RecvColorFormat> {
+    use gst_video::VideoFormat;
+
+    [
+        (
+            RecvColorFormat::BgrxBgra,
+            [VideoFormat::Bgrx, VideoFormat::Bgra],
+        ),
+        (
+            RecvColorFormat::UyvyBgra,
+            [VideoFormat::Uyvy, VideoFormat::Bgra],
+        ),
+        (
+            RecvColorFormat::RgbxRgba,
+            [VideoFormat::Rgbx, VideoFormat::Rgba],
+        ),
+        (
+            RecvColorFormat::UyvyRgba,
+            [VideoFormat::Uyvy, VideoFormat::Rgba],
+        ),
+    ]
+    .iter()
+    .filter_map(|(color_format, produced)| {
+        let count = produced.iter().filter(|f| formats.contains(f)).count();
+        let first = formats.iter().position(|f| produced.contains(f))?;
+        Some((*color_format, count, first))
+    })
+    .max_by_key(|(_, count, first)| (*count, std::cmp::Reverse(*first)))
+    .map(|(color_format, _, _)| color_format)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{init, FakeFind};
+
+    fn element() -> super::super::NdiSrc {
+        init();
+
+        glib::Object::new::<super::super::NdiSrc>(&[]).unwrap()
+    }
+
+    fn settings(ndi_name: &str, ndi_name_match: NdiNameMatch, source_index: i32) -> Settings {
+        Settings {
+            ndi_name: Some(String::from(ndi_name)),
+            ndi_name_match,
+            source_index,
+            connect_timeout: 200,
+            ..Default::default()
+        }
+    }
+
+    fn resolve(
+        settings: &Settings,
+        find: &FakeFind,
+    ) -> Result<(String, Option<String>), ConnectError> {
+        let element = element();
+        let imp = NdiSrc::from_instance(&element);
+        imp.resolve_source(
+            &element,
+            settings,
+            Some(&SharedFind::from_source(find.clone())),
+        )
+    }
+
+    fn sources() -> FakeFind {
+        FakeFind::new(&[
+            ("STUDIO-B (Camera)", "10.0.0.3:5961"),
+            ("STUDIO-A (Camera)", "10.0.0.2:5961"),
+            ("STUDIO-A (Camera)", "10.0.0.1:5961"),
+            ("STUDIO-A (Slides)", "10.0.0.4:5961"),
+        ])
+    }
+
+    #[test]
+    fn resolve_source_by_name() {
+        let find = sources();
+
+        let resolved = resolve(&settings("Slides", NdiNameMatch::Contains, -1), &find);
+        assert!(matches!(
+            resolved,
+            Ok((ref name, None)) if name == "STUDIO-A (Slides)"
+        ));
+
+        let resolved = resolve(&settings("Camera", NdiNameMatch::SourcePart, -1), &find);
+        assert!(matches!(
+            resolved,
+            Ok((ref name, None)) if name == "STUDIO-B (Camera)"
+        ));
+
+        let resolved = resolve(&settings("Slides", NdiNameMatch::Exact, -1), &find);
+        assert!(matches!(resolved, Err(ConnectError::Error(_))));
+    }
+
+    #[test]
+    fn resolve_source_by_index() {
+        let find = sources();
+
+        // Sources with the same name are ordered by their URL/address
+        let resolved = resolve(
+            &settings("STUDIO-A (Camera)", NdiNameMatch::Exact, 0),
+            &find,
+        );
+        assert!(matches!(
+            resolved,
+            Ok((ref name, Some(ref url))) if name == "STUDIO-A (Camera)" && url == "10.0.0.1:5961"
+        ));
+
+        let resolved = resolve(&settings("Camera", NdiNameMatch::SourcePart, 2), &find);
+        assert!(matches!(
+            resolved,
+            Ok((ref name, Some(ref url))) if name == "STUDIO-B (Camera)" && url == "10.0.0.3:5961"
+        ));
+
+        let resolved = resolve(&settings("Camera", NdiNameMatch::SourcePart, 3), &find);
+        assert!(matches!(resolved, Err(ConnectError::Error(_))));
+    }
+
+    #[test]
+    fn resolve_source_appearing_later() {
+        let find = FakeFind::default();
+
+        let find_clone = find.clone();
+        let announcer = std::thread::spawn(move || {
+            std::thread::sleep(time::Duration::from_millis(50));
+            find_clone.set_sources(&[("STUDIO-A (Camera)", "10.0.0.1:5961")]);
+        });
+
+        let mut settings = settings("Camera", NdiNameMatch::Contains, -1);
+        settings.connect_timeout = 5000;
+        let resolved = resolve(&settings, &find);
+        assert!(matches!(
+            resolved,
+            Ok((ref name, None)) if name == "STUDIO-A (Camera)"
+        ));
+        announcer.join().unwrap();
+    }
+
+    #[test]
+    fn resolve_source_flushing() {
+        let element = element();
+        let imp = NdiSrc::from_instance(&element);
+        imp.connect_state.lock().unwrap().flushing = true;
+
+        // Gives up on flushing instead of waiting for the whole connect-timeout
+        let mut settings = settings("Camera", NdiNameMatch::Contains, -1);
+        settings.connect_timeout = 0;
+        let resolved = imp.resolve_source(
+            &element,
+            &settings,
+            Some(&SharedFind::from_source(FakeFind::default())),
+        );
+        assert!(matches!(resolved, Err(ConnectError::Flushing)));
+    }
+
+    #[test]
+    fn audio_format_from_audio_caps() {
+        init();
+
+        let caps = |caps: &str| caps.parse::<gst::Caps>().unwrap();
+
+        // The native F32 is preferred if allowed
+        assert_eq!(
+            audio_format_from_caps(&caps("audio/x-raw,format={S16LE,F32LE}")),
+            Some(gst_audio::AUDIO_FORMAT_F32)
+        );
+        assert_eq!(
+            audio_format_from_caps(&caps("audio/x-raw,format=S16LE; audio/x-raw,format=F32LE")),
+            Some(gst_audio::AUDIO_FORMAT_F32)
+        );
+        assert_eq!(
+            audio_format_from_caps(&caps("audio/x-raw,format=S16LE")),
+            Some(gst_audio::AUDIO_FORMAT_S16)
+        );
+        // Without a format any format is allowed
+        assert_eq!(
+            audio_format_from_caps(&caps("audio/x-raw,rate=48000")),
+            Some(gst_audio::AUDIO_FORMAT_F32)
+        );
+        assert_eq!(audio_format_from_caps(&caps("audio/x-raw,format=U8")), None);
+        assert_eq!(audio_format_from_caps(&caps("video/x-raw")), None);
+    }
+
+    #[test]
+    fn scan_sources_from_context() {
+        let element = element();
+
+        // The find instance shared via the context is used instead of creating a new one
+        let find = SharedFind::from_source(sources());
+        element.set_context(&find.to_context());
+
+        let scanned = element.emit_by_name::<Vec<String>>("scan-sources", &[&0u32]);
+        assert_eq!(
+            scanned,
+            [
+                "STUDIO-B (Camera) (10.0.0.3:5961)",
+                "STUDIO-A (Camera) (10.0.0.2:5961)",
+                "STUDIO-A (Camera) (10.0.0.1:5961)",
+                "STUDIO-A (Slides) (10.0.0.4:5961)",
+            ]
+        );
+    }
 }
@@ -158,6 +158,29 @@ impl NdiSrcDemux {
     ) -> Result<gst::FlowSuccess, gst::FlowError> {
         gst_log!(CAT, obj: pad, "Handling buffer {:?}", buffer);
 
+        // Gap buffers from metadata-only sources are forwarded as GAP events
+        if buffer.flags().contains(gst::BufferFlags::GAP)
+            && buffer.meta::<ndisrcmeta::NdiSrcMeta>().is_none()
+        {
+            let pads = {
+                let state = self.state.lock().unwrap();
+                state
+                    .audio_pad
+                    .iter()
+                    .chain(state.video_pad.iter())
+                    .cloned()
+                    .collect::<Vec<_>>()
+            };
+
+            if let Some(pts) = buffer.pts() {
+                for srcpad in pads {
+                    srcpad.push_event(gst::event::Gap::new(pts, buffer.duration()));
+                }
+            }
+
+            return Ok(gst::FlowSuccess::Ok);
+        }
+
         let meta = buffer.make_mut().meta_mut::<ndisrcmeta::NdiSrcMeta>().ok_or_else(|| {
             gst_error!(CAT, obj: element, "Buffer without NDI source meta");
             gst::FlowError::Error
@@ -2,6 +2,9 @@ use gst::prelude::*;
 use std::fmt;
 use std::mem;
 
+// Attached by ndisrc to every buffer on its single application/x-ndi source pad, which carries
+// audio and video interleaved in the order they were captured. The stream type tells which stream
+// a buffer belongs to and the caps are the current caps of that stream.
 #[repr(transparent)]
 pub struct NdiSrcMeta(imp::NdiSrcMeta);
 
@@ -1,20 +1,215 @@
 #![allow(non_camel_case_types, non_upper_case_globals, non_snake_case)]
 
-#[cfg_attr(
-    all(target_arch = "x86_64", target_os = "windows"),
-    link(name = "Processing.NDI.Lib.x64")
-)]
-#[cfg_attr(
-    all(target_arch = "x86", target_os = "windows"),
-    link(name = "Processing.NDI.Lib.x86")
-)]
-#[cfg_attr(
-    not(any(target_os = "windows", target_os = "macos")),
-    link(name = "ndi")
-)]
-extern "C" {
+use once_cell::sync::OnceCell;
+use std::ffi::{CStr, OsStr};
+use std::os::raw::c_void;
+use std::path::{Path, PathBuf};
+
+// The NDI runtime is loaded at runtime instead of being linked at build time. This allows the
+// plugin to be loaded even if NDI is not installed, and allows selecting the runtime via the
+// environment variables documented by the NDI SDK.
+#[cfg(all(target_arch = "x86_64", target_os = "windows"))]
+const LIBRARY_NAMES: &[&str] = &["Processing.NDI.Lib.x64.dll"];
+#[cfg(all(target_arch = "x86", target_os = "windows"))]
+const LIBRARY_NAMES: &[&str] = &["Processing.NDI.Lib.x86.dll"];
+#[cfg(target_os = "macos")]
+const LIBRARY_NAMES: &[&str] = &["libndi.dylib"];
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+const LIBRARY_NAMES: &[&str] = &["libndi.so.5", "libndi.so.4", "libndi.so"];
+
+const RUNTIME_DIR_VARIABLES: &[&str] = &["NDI_RUNTIME_DIR_V5", "NDI_RUNTIME_DIR_V4"];
+
+#[cfg(unix)]
+struct Library(*mut c_void);
+
+#[cfg(unix)]
+impl Library {
+    fn open(path: &Path) -> Result<Self, String> {
+        use std::ffi::CString;
+        use std::os::unix::ffi::OsStrExt;
+
+        let path = CString::new(path.as_os_str().as_bytes()).map_err(|err| err.to_string())?;
+        unsafe {
+            let handle = libc::dlopen(path.as_ptr(), libc::RTLD_NOW | libc::RTLD_LOCAL);
+            if handle.is_null() {
+                Err(dl_error())
+            } else {
+                Ok(Library(handle))
+            }
+        }
+    }
+
+    fn symbol(&self, name: &CStr) -> Result<*mut c_void, String> {
+        unsafe {
+            let symbol = libc::dlsym(self.0, name.as_ptr());
+            if symbol.is_null() {
+                Err(dl_error())
+            } else {
+                Ok(symbol)
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+unsafe fn dl_error() -> String {
+    let error = libc::dlerror();
+    if error.is_null() {
+        String::from("unknown error")
+    } else {
+        CStr::from_ptr(error).to_string_lossy().into_owned()
+    }
+}
+
+#[cfg(windows)]
+struct Library(*mut c_void);
+
+#[cfg(windows)]
+#[link(name = "kernel32")]
+extern "system" {
+    fn LoadLibraryExW(lpLibFileName: *const u16, hFile: *mut c_void, dwFlags: u32) -> *mut c_void;
+    fn GetProcAddress(
+        hModule: *mut c_void,
+        lpProcName: *const ::std::os::raw::c_char,
+    ) -> *mut c_void;
+}
+
+#[cfg(windows)]
+impl Library {
+    fn open(path: &Path) -> Result<Self, String> {
+        use std::os::windows::ffi::OsStrExt;
+
+        // Resolve the dependencies of the NDI DLL from its own directory instead of the
+        // current working directory
+        const LOAD_WITH_ALTERED_SEARCH_PATH: u32 = 0x8;
+
+        let flags = if path.is_absolute() {
+            LOAD_WITH_ALTERED_SEARCH_PATH
+        } else {
+            0
+        };
+        let path = path
+            .as_os_str()
+            .encode_wide()
+            .chain(Some(0))
+            .collect::<Vec<u16>>();
+        unsafe {
+            let handle = LoadLibraryExW(path.as_ptr(), std::ptr::null_mut(), flags);
+            if handle.is_null() {
+                Err(std::io::Error::last_os_error().to_string())
+            } else {
+                Ok(Library(handle))
+            }
+        }
+    }
+
+    fn symbol(&self, name: &CStr) -> Result<*mut c_void, String> {
+        unsafe {
+            let symbol = GetProcAddress(self.0, name.as_ptr());
+            if symbol.is_null() {
+                Err(std::io::Error::last_os_error().to_string())
+            } else {
+                Ok(symbol)
+            }
+        }
+    }
+}
+
+// Locations to try loading the NDI runtime from, in order of preference
+fn library_candidates() -> Vec<PathBuf> {
+    let mut candidates = Vec::new();
+
+    for variable in RUNTIME_DIR_VARIABLES {
+        if let Some(dir) = std::env::var_os(variable) {
+            if dir.is_empty() {
+                continue;
+            }
+            for name in LIBRARY_NAMES {
+                candidates.push(Path::new(&dir).join(name));
+            }
+        }
+    }
+
+    // Fall back to the default library search path
+    for name in LIBRARY_NAMES {
+        candidates.push(Path::new(OsStr::new(name)).to_path_buf());
+    }
+
+    candidates
+}
+
+macro_rules! ndi_functions {
+    ($(pub fn $name:ident($($arg:ident: $ty:ty),* $(,)?) $(-> $ret:ty)?;)*) => {
+        struct Functions {
+            $($name: unsafe extern "C" fn($($ty),*) $(-> $ret)?,)*
+        }
+
+        impl Functions {
+            fn resolve(library: &Library) -> Result<Self, String> {
+                unsafe {
+                    Ok(Functions {
+                        $($name: std::mem::transmute::<
+                            *mut c_void,
+                            unsafe extern "C" fn($($ty),*) $(-> $ret)?,
+                        >(library.symbol(
+                            CStr::from_bytes_with_nul(concat!(stringify!($name), "\0").as_bytes())
+                                .unwrap(),
+                        )?),)*
+                    })
+                }
+            }
+        }
+
+        $(
+            #[allow(clippy::missing_safety_doc)]
+            pub unsafe fn $name($($arg: $ty),*) $(-> $ret)? {
+                (functions().$name)($($arg),*)
+            }
+        )*
+    };
+}
+
+static FUNCTIONS: OnceCell<Functions> = OnceCell::new();
+
+fn functions() -> &'static Functions {
+    FUNCTIONS.get().expect("NDI library not loaded")
+}
+
+// Loads the NDI runtime library and resolves all functions from it. The library stays loaded
+// for the lifetime of the process.
+pub fn load() -> Result<(), String> {
+    FUNCTIONS
+        .get_or_try_init(|| {
+            let mut errors = Vec::new();
+
+            for path in library_candidates() {
+                let library = match Library::open(&path) {
+                    Ok(library) => library,
+                    Err(err) => {
+                        errors.push(format!("{}: {}", path.display(), err));
+                        continue;
+                    }
+                };
+
+                match Functions::resolve(&library) {
+                    Ok(functions) => return Ok(functions),
+                    Err(err) => errors.push(format!("{}: {}", path.display(), err)),
+                }
+            }
+
+            Err(errors.join(", "))
+        })
+        .map(|_| ())
+}
+
+pub fn is_loaded() -> bool {
+    FUNCTIONS.get().is_some()
+}
+
+ndi_functions! {
     pub fn NDIlib_initialize() -> bool;
     pub fn NDIlib_destroy();
+    pub fn NDIlib_version() -> *const ::std::os::raw::c_char;
     pub fn NDIlib_find_create_v2(
         p_create_settings: *const NDIlib_find_create_t,
     ) -> NDIlib_find_instance_t;
@@ -62,6 +257,14 @@ extern "C" {
         p_instance: NDIlib_recv_instance_t,
         p_total: *mut NDIlib_recv_queue_t,
     );
+    pub fn NDIlib_recv_ptz_is_supported(p_instance: NDIlib_recv_instance_t) -> bool;
+    pub fn NDIlib_recv_get_web_control(
+        p_instance: NDIlib_recv_instance_t,
+    ) -> *const ::std::os::raw::c_char;
+    pub fn NDIlib_recv_free_string(
+        p_instance: NDIlib_recv_instance_t,
+        p_string: *const ::std::os::raw::c_char,
+    );
     pub fn NDIlib_send_create(
         p_create_settings: *const NDIlib_send_create_t,
     ) -> NDIlib_send_instance_t;
@@ -74,6 +277,24 @@ extern "C" {
         p_instance: NDIlib_send_instance_t,
         p_audio_data: *const NDIlib_audio_frame_v3_t,
     );
+    pub fn NDIlib_send_send_metadata(
+        p_instance: NDIlib_send_instance_t,
+        p_metadata: *const NDIlib_metadata_frame_t,
+    );
+    pub fn NDIlib_send_add_connection_metadata(
+        p_instance: NDIlib_send_instance_t,
+        p_metadata: *const NDIlib_metadata_frame_t,
+    );
+    pub fn NDIlib_send_clear_connection_metadata(p_instance: NDIlib_send_instance_t);
+    pub fn NDIlib_send_capture(
+        p_instance: NDIlib_send_instance_t,
+        p_metadata: *mut NDIlib_metadata_frame_t,
+        timeout_in_ms: u32,
+    ) -> NDIlib_frame_type_e;
+    pub fn NDIlib_send_free_metadata(
+        p_instance: NDIlib_send_instance_t,
+        p_metadata: *const NDIlib_metadata_frame_t,
+    );
 }
 
 pub type NDIlib_find_instance_t = *mut ::std::os::raw::c_void;
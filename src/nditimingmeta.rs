@@ -0,0 +1,160 @@
+use gst::prelude::*;
+use std::fmt;
+
+// Raw NDI timecode and timestamp of the frame a buffer was created from, in 100ns units. This is
+// attached by ndisrc if the timing-meta property is enabled and is kept when buffers are copied.
+#[repr(transparent)]
+pub struct NdiTimingMeta(imp::NdiTimingMeta);
+
+unsafe impl Send for NdiTimingMeta {}
+unsafe impl Sync for NdiTimingMeta {}
+
+impl NdiTimingMeta {
+    pub fn add<'a>(
+        buffer: &'a mut gst::BufferRef,
+        timecode: i64,
+        timestamp: i64,
+    ) -> gst::MetaRefMut<'a, Self, gst::meta::Standalone> {
+        unsafe {
+            let mut params = imp::NdiTimingMetaParams {
+                timecode,
+                timestamp,
+            };
+
+            let meta = gst::ffi::gst_buffer_add_meta(
+                buffer.as_mut_ptr(),
+                imp::ndi_timing_meta_get_info(),
+                &mut params as *mut imp::NdiTimingMetaParams as glib::ffi::gpointer,
+            ) as *mut imp::NdiTimingMeta;
+
+            Self::from_mut_ptr(buffer, meta)
+        }
+    }
+
+    pub fn timecode(&self) -> i64 {
+        self.0.timecode
+    }
+
+    // None if the source doesn't provide timestamps
+    pub fn timestamp(&self) -> Option<i64> {
+        if self.0.timestamp == crate::ndisys::NDIlib_recv_timestamp_undefined {
+            None
+        } else {
+            Some(self.0.timestamp)
+        }
+    }
+}
+
+unsafe impl MetaAPI for NdiTimingMeta {
+    type GstType = imp::NdiTimingMeta;
+
+    fn meta_api() -> glib::Type {
+        imp::ndi_timing_meta_api_get_type()
+    }
+}
+
+impl fmt::Debug for NdiTimingMeta {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("NdiTimingMeta")
+            .field("timecode", &self.timecode())
+            .field("timestamp", &self.timestamp())
+            .finish()
+    }
+}
+
+mod imp {
+    use glib::translate::*;
+    use once_cell::sync::Lazy;
+    use std::mem;
+    use std::ptr;
+
+    pub(super) struct NdiTimingMetaParams {
+        pub timecode: i64,
+        pub timestamp: i64,
+    }
+
+    #[repr(C)]
+    pub struct NdiTimingMeta {
+        parent: gst::ffi::GstMeta,
+        pub(super) timecode: i64,
+        pub(super) timestamp: i64,
+    }
+
+    pub(super) fn ndi_timing_meta_api_get_type() -> glib::Type {
+        static TYPE: Lazy<glib::Type> = Lazy::new(|| unsafe {
+            let t = from_glib(gst::ffi::gst_meta_api_type_register(
+                b"GstNdiTimingMetaAPI\0".as_ptr() as *const _,
+                [ptr::null::<std::os::raw::c_char>()].as_ptr() as *mut *const _,
+            ));
+
+            assert_ne!(t, glib::Type::INVALID);
+
+            t
+        });
+
+        *TYPE
+    }
+
+    unsafe extern "C" fn ndi_timing_meta_init(
+        meta: *mut gst::ffi::GstMeta,
+        params: glib::ffi::gpointer,
+        _buffer: *mut gst::ffi::GstBuffer,
+    ) -> glib::ffi::gboolean {
+        assert!(!params.is_null());
+
+        let meta = &mut *(meta as *mut NdiTimingMeta);
+        let params = &*(params as *const NdiTimingMetaParams);
+
+        meta.timecode = params.timecode;
+        meta.timestamp = params.timestamp;
+
+        true.into_glib()
+    }
+
+    // The timing stays valid for copies of the buffer, e.g. by ndisrcdemux or when making it
+    // writable
+    unsafe extern "C" fn ndi_timing_meta_transform(
+        dest: *mut gst::ffi::GstBuffer,
+        meta: *mut gst::ffi::GstMeta,
+        _buffer: *mut gst::ffi::GstBuffer,
+        type_: glib::ffi::GQuark,
+        _data: glib::ffi::gpointer,
+    ) -> glib::ffi::gboolean {
+        static COPY: Lazy<glib::Quark> = Lazy::new(|| glib::Quark::from_str("gst-copy"));
+
+        if type_ != COPY.into_glib() {
+            return false.into_glib();
+        }
+
+        let meta = &*(meta as *const NdiTimingMeta);
+        super::NdiTimingMeta::add(
+            gst::BufferRef::from_mut_ptr(dest),
+            meta.timecode,
+            meta.timestamp,
+        );
+
+        true.into_glib()
+    }
+
+    pub(super) fn ndi_timing_meta_get_info() -> *const gst::ffi::GstMetaInfo {
+        struct MetaInfo(ptr::NonNull<gst::ffi::GstMetaInfo>);
+        unsafe impl Send for MetaInfo {}
+        unsafe impl Sync for MetaInfo {}
+
+        static META_INFO: Lazy<MetaInfo> = Lazy::new(|| unsafe {
+            MetaInfo(
+                ptr::NonNull::new(gst::ffi::gst_meta_register(
+                    ndi_timing_meta_api_get_type().into_glib(),
+                    b"GstNdiTimingMeta\0".as_ptr() as *const _,
+                    mem::size_of::<NdiTimingMeta>(),
+                    Some(ndi_timing_meta_init),
+                    None,
+                    Some(ndi_timing_meta_transform),
+                ) as *mut gst::ffi::GstMetaInfo)
+                .expect("Failed to register meta API"),
+            )
+        });
+
+        META_INFO.0.as_ptr()
+    }
+}
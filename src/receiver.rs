@@ -1,12 +1,15 @@
 use glib::prelude::*;
 use gst::prelude::*;
-use gst::{gst_debug, gst_error, gst_log, gst_trace, gst_warning};
+use gst::{gst_debug, gst_error, gst_info, gst_log, gst_trace, gst_warning};
 use gst_video::prelude::*;
 
 use byte_slice_cast::*;
 
 use std::cmp;
 use std::collections::VecDeque;
+use std::convert::TryInto;
+use std::mem;
+use std::sync::atomic::{self, AtomicBool};
 use std::sync::{Arc, Condvar, Mutex, Weak};
 use std::thread;
 
@@ -23,7 +26,7 @@ static CAT: Lazy<gst::DebugCategory> = Lazy::new(|| {
 #[derive(Clone)]
 pub struct Receiver(Arc<ReceiverInner>);
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum AudioInfo {
     AudioInfo(gst_audio::AudioInfo),
     #[cfg(feature = "advanced-sdk")]
@@ -36,6 +39,7 @@ pub enum AudioInfo {
         sample_rate: i32,
         no_channels: i32,
         codec_data: [u8; 2],
+        adts: bool,
     },
 }
 
@@ -53,10 +57,23 @@ impl AudioInfo {
                 .field("channel-mapping-family", 0i32)
                 .build()),
             #[cfg(feature = "advanced-sdk")]
+            AudioInfo::AacInfo {
+                sample_rate,
+                no_channels,
+                adts: true,
+                ..
+            } => Ok(gst::Caps::builder("audio/mpeg")
+                .field("channels", *no_channels)
+                .field("rate", *sample_rate)
+                .field("mpegversion", 4i32)
+                .field("stream-format", "adts")
+                .build()),
+            #[cfg(feature = "advanced-sdk")]
             AudioInfo::AacInfo {
                 sample_rate,
                 no_channels,
                 codec_data,
+                adts: false,
             } => Ok(gst::Caps::builder("audio/mpeg")
                 .field("channels", *no_channels)
                 .field("rate", *sample_rate)
@@ -68,7 +85,7 @@ impl AudioInfo {
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum VideoInfo {
     VideoInfo(gst_video::VideoInfo),
     #[cfg(feature = "advanced-sdk")]
@@ -82,6 +99,7 @@ pub enum VideoInfo {
         par_d: i32,
         interlace_mode: gst_video::VideoInterlaceMode,
     },
+    // Profile, tier and level are parsed from the SPS if known
     #[cfg(feature = "advanced-sdk")]
     H264Info {
         xres: i32,
@@ -91,6 +109,8 @@ pub enum VideoInfo {
         par_n: i32,
         par_d: i32,
         interlace_mode: gst_video::VideoInterlaceMode,
+        profile: Option<&'static str>,
+        level: Option<String>,
     },
     #[cfg(feature = "advanced-sdk")]
     H265Info {
@@ -101,6 +121,9 @@ pub enum VideoInfo {
         par_n: i32,
         par_d: i32,
         interlace_mode: gst_video::VideoInterlaceMode,
+        profile: Option<&'static str>,
+        tier: Option<&'static str>,
+        level: Option<String>,
     },
 }
 
@@ -135,16 +158,29 @@ impl VideoInfo {
                 par_n,
                 par_d,
                 interlace_mode,
-                ..
-            } => Ok(gst::Caps::builder("video/x-h264")
-                .field("width", *xres)
-                .field("height", *yres)
-                .field("framerate", gst::Fraction::new(*fps_n, *fps_d))
-                .field("pixel-aspect-ratio", gst::Fraction::new(*par_n, *par_d))
-                .field("interlace-mode", interlace_mode.to_str())
-                .field("stream-format", "byte-stream")
-                .field("alignment", "au")
-                .build()),
+                profile,
+                level,
+            } => {
+                let mut caps = gst::Caps::builder("video/x-h264")
+                    .field("width", *xres)
+                    .field("height", *yres)
+                    .field("framerate", gst::Fraction::new(*fps_n, *fps_d))
+                    .field("pixel-aspect-ratio", gst::Fraction::new(*par_n, *par_d))
+                    .field("interlace-mode", interlace_mode.to_str())
+                    .field("stream-format", "byte-stream")
+                    .field("alignment", "au")
+                    .build();
+                {
+                    let s = caps.get_mut().unwrap().structure_mut(0).unwrap();
+                    if let Some(profile) = profile {
+                        s.set("profile", profile);
+                    }
+                    if let Some(level) = level {
+                        s.set("level", level);
+                    }
+                }
+                Ok(caps)
+            }
             #[cfg(feature = "advanced-sdk")]
             VideoInfo::H265Info {
                 xres,
@@ -154,16 +190,33 @@ impl VideoInfo {
                 par_n,
                 par_d,
                 interlace_mode,
-                ..
-            } => Ok(gst::Caps::builder("video/x-h265")
-                .field("width", *xres)
-                .field("height", *yres)
-                .field("framerate", gst::Fraction::new(*fps_n, *fps_d))
-                .field("pixel-aspect-ratio", gst::Fraction::new(*par_n, *par_d))
-                .field("interlace-mode", interlace_mode.to_str())
-                .field("stream-format", "byte-stream")
-                .field("alignment", "au")
-                .build()),
+                profile,
+                tier,
+                level,
+            } => {
+                let mut caps = gst::Caps::builder("video/x-h265")
+                    .field("width", *xres)
+                    .field("height", *yres)
+                    .field("framerate", gst::Fraction::new(*fps_n, *fps_d))
+                    .field("pixel-aspect-ratio", gst::Fraction::new(*par_n, *par_d))
+                    .field("interlace-mode", interlace_mode.to_str())
+                    .field("stream-format", "byte-stream")
+                    .field("alignment", "au")
+                    .build();
+                {
+                    let s = caps.get_mut().unwrap().structure_mut(0).unwrap();
+                    if let Some(profile) = profile {
+                        s.set("profile", profile);
+                    }
+                    if let Some(tier) = tier {
+                        s.set("tier", tier);
+                    }
+                    if let Some(level) = level {
+                        s.set("level", level);
+                    }
+                }
+                Ok(caps)
+            }
         }
     }
 }
@@ -177,22 +230,92 @@ pub enum Buffer {
 #[derive(Debug)]
 pub enum ReceiverItem {
     Buffer(Buffer),
+    // Nothing received for the given running time, e.g. in metadata-only mode
+    Gap(gst::ClockTime, gst::ClockTime),
     Flushing,
     Timeout,
     Error(gst::FlowError),
 }
 
+// Configuration of a receiver, built once from the ndisrc settings when starting
+#[derive(Debug, Clone)]
+pub struct ReceiverSettings {
+    pub receiver_ndi_name: String,
+    // Requested bandwidth. With metadata-only no audio/video is received, with audio-only no
+    // video
+    pub bandwidth: NDIlib_recv_bandwidth_e,
+    pub color_format: NDIlib_recv_color_format_e,
+    pub allow_video_fields: bool,
+    // Mark interlaced frames as progressive, fields are already woven by the SDK in that case
+    pub force_progressive: bool,
+    pub hwaccel: bool,
+    pub timestamp_mode: TimestampMode,
+    #[cfg_attr(not(feature = "reference-timestamps"), allow(dead_code))]
+    pub reference_timestamps: bool,
+    // Attach the raw NDI timecode and timestamp as NdiTimingMeta
+    pub timing_meta: bool,
+    pub freeze_on_loss: bool,
+    pub fill_audio_gaps: bool,
+    pub capture_retries: u32,
+    // Number of times connect-timeout is waited again for the first frame before giving up, and
+    // the initial delay between these retries in milliseconds
+    pub connect_retries: u32,
+    pub connect_retry_interval: u32,
+    #[cfg_attr(not(feature = "advanced-sdk"), allow(dead_code))]
+    pub aac_adts: bool,
+    // Fail on metadata that is not valid UTF-8 instead of replacing the invalid parts
+    pub strict_metadata: bool,
+    // Overrides the colorimetry of YUV video
+    pub colorimetry: Option<gst_video::VideoColorimetry>,
+    // Raw video formats that are allowed to be produced
+    pub video_formats: Option<Vec<gst_video::VideoFormat>>,
+    // Keep the line stride of the SDK and describe it with a video meta instead of repacking
+    pub preserve_stride: bool,
+    // Output frames with alpha as their variant without alpha
+    pub ignore_alpha: bool,
+    // Raw audio format to produce, F32 or S16
+    pub audio_format: gst_audio::AudioFormat,
+    // dB above +4 dBu that full range S16 corresponds to, like the reference-level of ndisink
+    pub reference_level: i32,
+    pub timeout: u32,
+    pub frame_timeout: u32,
+    pub connect_timeout: u32,
+    pub max_queue_length: usize,
+}
+
 pub struct ReceiverInner {
     queue: ReceiverQueue,
-    max_queue_length: usize,
+    settings: ReceiverSettings,
+
+    // Find instance shared with other elements, used for checking if the source is still there
+    find: Option<SharedFind>,
 
     observations: Observations,
+    timecode_tracker: TimecodeTracker,
+
+    // Product information from the connection metadata of the source
+    source_info: Arc<Mutex<Option<gst::Structure>>>,
+    // Number of audio, video and metadata frames queued up inside the SDK
+    queue_depth: Arc<Mutex<Option<Queue>>>,
+    // If audio/video frames were received from the source so far
+    has_audio: Arc<AtomicBool>,
+    has_video: Arc<AtomicBool>,
+    // Refreshed whenever the source signals a status change
+    capabilities: Arc<Mutex<Capabilities>>,
 
     element: glib::WeakRef<gst_base::BaseSrc>,
-    timestamp_mode: TimestampMode,
-
-    timeout: u32,
-    connect_timeout: u32,
+    // Source that was connected to, for the connected message
+    ndi_name: Option<String>,
+    url_address: Option<String>,
+    // End of the last audio buffer for detecting gaps
+    next_audio_pts: Mutex<Option<gst::ClockTime>>,
+    // Timecode of the last video frame for variable frame rate sources
+    last_video_timecode: Mutex<Option<i64>>,
+    // Timestamp mode selected for the source with TimestampMode::Auto
+    auto_timestamp_mode: Mutex<Option<TimestampMode>>,
+    // Last SPS/PPS/VPS of compressed video
+    #[cfg(feature = "advanced-sdk")]
+    last_extra_data: Mutex<Option<Vec<u8>>>,
 
     thread: Mutex<Option<std::thread::JoinHandle<()>>>,
 }
@@ -214,14 +337,220 @@ struct ReceiverQueueInner {
     //
     // On timeout/error will contain a single item and then never be filled again
     buffer_queue: VecDeque<Buffer>,
+    // Pending gap if nothing was captured
+    gap: Option<(gst::ClockTime, gst::ClockTime)>,
 
     error: Option<gst::FlowError>,
     timeout: bool,
+    // Set once the first frame was received from the source
+    connected: bool,
+}
+
+impl ReceiverQueue {
+    fn set_flushing(&self, flushing: bool) {
+        let mut queue = (self.0).0.lock().unwrap();
+        queue.flushing = flushing;
+        // Drop everything captured before the flush right away instead of waiting for the
+        // capture thread to notice, so that no stale buffers are output after the flush
+        if flushing {
+            queue.buffer_queue.clear();
+            queue.gap = None;
+        }
+        (self.0).1.notify_all();
+    }
+
+    // Blocks until the first frame was received, or returns false if the receiver failed,
+    // timed out, was shut down or is flushing before, or the timeout passed
+    fn wait_for_connection(&self, timeout: time::Duration) -> bool {
+        let queue = (self.0).0.lock().unwrap();
+        let (queue, _) = (self.0)
+            .1
+            .wait_timeout_while(queue, timeout, |queue| {
+                !queue.connected
+                    && queue.error.is_none()
+                    && !queue.timeout
+                    && !queue.shutdown
+                    && !queue.flushing
+            })
+            .unwrap();
+        queue.connected
+    }
+
+    // Blocks for the connect retry delay unless shut down or flushing in the meantime, so that
+    // neither stopping nor seeking has to wait for the retry
+    fn wait_for_retry(&self, delay: time::Duration) {
+        let queue = (self.0).0.lock().unwrap();
+        let _ = (self.0)
+            .1
+            .wait_timeout_while(queue, delay, |queue| !queue.shutdown && !queue.flushing)
+            .unwrap();
+    }
 }
 
 const WINDOW_LENGTH: u64 = 512;
 const WINDOW_DURATION: u64 = 2_000_000_000;
 
+// Longer audio gaps are not filled with silence but only marked as discontinuity
+const MAX_AUDIO_GAP: u64 = 1_000_000_000;
+
+// Last received video frame and audio configuration for repeating them while the source is lost
+#[derive(Default)]
+struct FreezeState {
+    last_video: Option<(gst::Buffer, VideoInfo)>,
+    last_audio: Option<(gst_audio::AudioInfo, gst::ClockTime)>,
+    next_audio_pts: Option<gst::ClockTime>,
+    frozen: bool,
+}
+
+impl FreezeState {
+    fn update(&mut self, element: &gst_base::BaseSrc, item: &mut Buffer) {
+        let buffer = match item {
+            Buffer::Video(ref mut buffer, ref info) => {
+                self.last_video = Some((buffer.clone(), info.clone()));
+                buffer
+            }
+            Buffer::Audio(ref mut buffer, AudioInfo::AudioInfo(ref info)) => {
+                self.last_audio = buffer.duration().map(|duration| (info.clone(), duration));
+                self.next_audio_pts = buffer
+                    .pts()
+                    .zip(buffer.duration())
+                    .map(|(pts, duration)| pts + duration);
+                buffer
+            }
+            #[allow(unreachable_patterns)]
+            Buffer::Audio(ref mut buffer, _) => buffer,
+        };
+
+        if self.frozen {
+            gst_debug!(CAT, obj: element, "Source is back");
+            self.frozen = false;
+            buffer.make_mut().set_flags(gst::BufferFlags::DISCONT);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SourceState {
+    Alive,
+    Paused,
+    Gone,
+}
+
+// Distinguishes a paused source from one that is gone while no frames are received. A source
+// that is idle for LIVENESS_IDLE_TIME but still announced on the network is considered paused,
+// one that is not announced anymore after LIVENESS_DISCOVERY_TIME of discovery is considered
+// gone. Changes are posted as ndi-source-paused, ndi-source-gone and ndi-source-resumed element
+// messages.
+struct Liveness {
+    state: SourceState,
+    find: Option<(SharedFind, time::Instant)>,
+    last_check: Option<time::Instant>,
+}
+
+impl Default for Liveness {
+    fn default() -> Self {
+        Liveness {
+            state: SourceState::Alive,
+            find: None,
+            last_check: None,
+        }
+    }
+}
+
+impl Liveness {
+    fn idle(&mut self, element: &gst_base::BaseSrc, receiver: &Receiver, idle: time::Duration) {
+        if idle < LIVENESS_IDLE_TIME {
+            return;
+        }
+        if let Some(last_check) = self.last_check {
+            if last_check.elapsed() < LIVENESS_CHECK_INTERVAL {
+                return;
+            }
+        }
+        self.last_check = Some(time::Instant::now());
+
+        if self.find.is_none() {
+            gst_debug!(
+                CAT,
+                obj: element,
+                "No frames received, checking if source is still announced"
+            );
+            self.find = receiver
+                .0
+                .find
+                .clone()
+                .or_else(|| SharedFind::new(None))
+                .map(|find| (find, time::Instant::now()));
+        }
+        let (find, created) = match self.find {
+            Some((ref find, created)) => (find, created),
+            None => return,
+        };
+
+        let announced = find.current_sources().iter().any(|source| {
+            receiver.0.ndi_name.as_deref() == Some(source.ndi_name())
+                || receiver.0.url_address.as_deref() == Some(source.url_address())
+        });
+
+        let state = if announced {
+            SourceState::Paused
+        } else if created.elapsed() >= LIVENESS_DISCOVERY_TIME {
+            SourceState::Gone
+        } else {
+            return;
+        };
+
+        if state != self.state {
+            gst_debug!(
+                CAT,
+                obj: element,
+                "Source {} state changed to {:?}",
+                receiver.source_name(),
+                state
+            );
+            self.state = state;
+            self.post_message(
+                element,
+                receiver,
+                if state == SourceState::Paused {
+                    "ndi-source-paused"
+                } else {
+                    "ndi-source-gone"
+                },
+            );
+        }
+    }
+
+    fn frame_received(&mut self, element: &gst_base::BaseSrc, receiver: &Receiver) {
+        self.find = None;
+        self.last_check = None;
+
+        if self.state != SourceState::Alive {
+            gst_debug!(
+                CAT,
+                obj: element,
+                "Source {} alive again",
+                receiver.source_name()
+            );
+            self.state = SourceState::Alive;
+            self.post_message(element, receiver, "ndi-source-resumed");
+        }
+    }
+
+    fn post_message(&self, element: &gst_base::BaseSrc, receiver: &Receiver, name: &str) {
+        let _ = element.post_message(
+            gst::message::Element::builder(
+                gst::Structure::builder(name)
+                    .field("ndi-name", &receiver.0.ndi_name)
+                    .field("url-address", &receiver.0.url_address)
+                    .build(),
+            )
+            .src(element)
+            .build(),
+        );
+    }
+}
+
 #[derive(Clone)]
 struct Observations(Arc<Mutex<ObservationsInner>>);
 
@@ -233,6 +562,8 @@ struct ObservationsInner {
     skew: i64,
     filling: bool,
     window_size: usize,
+    // Remote time since the base time of the last observation
+    remote_diff: u64,
 }
 
 impl Default for ObservationsInner {
@@ -245,6 +576,7 @@ impl Default for ObservationsInner {
             skew: 0,
             filling: true,
             window_size: 0,
+            remote_diff: 0,
         }
     }
 }
@@ -254,9 +586,24 @@ impl Observations {
         Self(Arc::new(Mutex::new(ObservationsInner::default())))
     }
 
-    // Based on the algorithm used in GStreamer's rtpjitterbuffer, which comes from
-    // Fober, Orlarey and Letz, 2005, "Real Time Clock Skew Estimation over Network Delays":
-    // http://citeseerx.ist.psu.edu/viewdoc/summary?doi=10.1.1.102.1546
+    // Forget everything observed so far and start estimating latency and skew from scratch
+    fn reset(&self) {
+        *self.0.lock().unwrap() = ObservationsInner::default();
+    }
+
+    // Estimated drift of the remote clock against the local clock in parts per million, positive
+    // if the remote clock runs slower. This is the skew accumulated since the base time relative
+    // to the remote time passed since then, so it becomes more accurate over time.
+    fn drift(&self) -> Option<f64> {
+        let inner = self.0.lock().unwrap();
+
+        if inner.deltas.is_empty() || inner.remote_diff == 0 {
+            return None;
+        }
+
+        Some(inner.skew as f64 * 1_000_000.0 / inner.remote_diff as f64)
+    }
+
     fn process(
         &self,
         element: &gst_base::BaseSrc,
@@ -279,80 +626,50 @@ impl Observations {
             gst::ClockTime::from_nseconds(remote_time),
         );
 
-        let mut inner = self.0.lock().unwrap();
-
-        let (base_remote_time, base_local_time) =
-            match (inner.base_remote_time, inner.base_local_time) {
-                (Some(remote), Some(local)) => (remote, local),
-                _ => {
-                    gst_debug!(
-                        CAT,
-                        obj: element,
-                        "Initializing base time: local {}, remote {}",
-                        gst::ClockTime::from_nseconds(local_time),
-                        gst::ClockTime::from_nseconds(remote_time),
-                    );
-                    inner.base_remote_time = Some(remote_time);
-                    inner.base_local_time = Some(local_time);
-
-                    return (gst::ClockTime::from_nseconds(local_time), duration, true);
-                }
-            };
-
-        let remote_diff = remote_time.saturating_sub(base_remote_time);
-        let local_diff = local_time.saturating_sub(base_local_time);
-        let delta = (local_diff as i64) - (remote_diff as i64);
-
-        gst_trace!(
-            CAT,
-            obj: element,
-            "Local diff {}, remote diff {}, delta {}",
-            gst::ClockTime::from_nseconds(local_diff),
-            gst::ClockTime::from_nseconds(remote_diff),
-            delta,
-        );
+        let estimation = self.0.lock().unwrap().process(remote_time, local_time);
 
-        if remote_diff > 0 && local_diff > 0 {
-            let slope = (local_diff as f64) / (remote_diff as f64);
-            if !(0.8..1.2).contains(&slope) {
+        let (out_time, discont) = match estimation {
+            Estimation::Initialized => (local_time, true),
+            Estimation::ResetSlope { slope, discont } => {
                 gst_warning!(
                     CAT,
                     obj: element,
                     "Too small/big slope {}, resetting",
                     slope
                 );
-
-                let discont = !inner.deltas.is_empty();
-                *inner = ObservationsInner::default();
-
-                gst_debug!(
+                (local_time, discont)
+            }
+            Estimation::ResetDelta {
+                delta,
+                skew,
+                discont,
+            } => {
+                gst_warning!(
                     CAT,
                     obj: element,
-                    "Initializing base time: local {}, remote {}",
-                    gst::ClockTime::from_nseconds(local_time),
-                    gst::ClockTime::from_nseconds(remote_time),
+                    "Delta {} too far from skew {}, resetting",
+                    delta,
+                    skew
                 );
-                inner.base_remote_time = Some(remote_time);
-                inner.base_local_time = Some(local_time);
-
-                return (gst::ClockTime::from_nseconds(local_time), duration, discont);
+                (local_time, discont)
             }
-        }
-
-        if (delta > inner.skew && delta - inner.skew > 1_000_000_000)
-            || (delta < inner.skew && inner.skew - delta > 1_000_000_000)
-        {
-            gst_warning!(
-                CAT,
-                obj: element,
-                "Delta {} too far from skew {}, resetting",
-                delta,
-                inner.skew
-            );
-
-            let discont = !inner.deltas.is_empty();
-            *inner = ObservationsInner::default();
+            Estimation::Estimated {
+                time,
+                skew,
+                min_delta,
+            } => {
+                gst_trace!(CAT, obj: element, "Skew {}, min delta {}", skew, min_delta);
+                gst_trace!(
+                    CAT,
+                    obj: element,
+                    "Outputting {}",
+                    gst::ClockTime::from_nseconds(time)
+                );
+                (time, false)
+            }
+        };
 
+        if !matches!(estimation, Estimation::Estimated { .. }) {
             gst_debug!(
                 CAT,
                 obj: element,
@@ -360,79 +677,264 @@ impl Observations {
                 gst::ClockTime::from_nseconds(local_time),
                 gst::ClockTime::from_nseconds(remote_time),
             );
-            inner.base_remote_time = Some(remote_time);
-            inner.base_local_time = Some(local_time);
+        }
+
+        (gst::ClockTime::from_nseconds(out_time), duration, discont)
+    }
+}
+
+// Outcome of a single observation of the skew estimator
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Estimation {
+    // First observation, which is taken as base time
+    Initialized,
+    // The estimation was restarted from this observation because the clocks don't advance at a
+    // similar rate or the delta is too far from the skew. Discontinuous if anything was
+    // estimated before.
+    ResetSlope {
+        slope: f64,
+        discont: bool,
+    },
+    ResetDelta {
+        delta: i64,
+        skew: i64,
+        discont: bool,
+    },
+    // Local time corresponding to the remote time, corrected by the estimated skew
+    Estimated {
+        time: u64,
+        skew: i64,
+        min_delta: i64,
+    },
+}
+
+impl ObservationsInner {
+    fn reset_base(&mut self, remote_time: u64, local_time: u64) {
+        *self = ObservationsInner::default();
+        self.base_remote_time = Some(remote_time);
+        self.base_local_time = Some(local_time);
+    }
+
+    // Based on the algorithm used in GStreamer's rtpjitterbuffer, which comes from
+    // Fober, Orlarey and Letz, 2005, "Real Time Clock Skew Estimation over Network Delays":
+    // http://citeseerx.ist.psu.edu/viewdoc/summary?doi=10.1.1.102.1546
+    fn process(&mut self, remote_time: u64, local_time: u64) -> Estimation {
+        let (base_remote_time, base_local_time) =
+            match (self.base_remote_time, self.base_local_time) {
+                (Some(remote), Some(local)) => (remote, local),
+                _ => {
+                    self.reset_base(remote_time, local_time);
+                    return Estimation::Initialized;
+                }
+            };
+
+        let remote_diff = remote_time.saturating_sub(base_remote_time);
+        let local_diff = local_time.saturating_sub(base_local_time);
+        let delta = (local_diff as i64) - (remote_diff as i64);
 
-            return (gst::ClockTime::from_nseconds(local_time), duration, discont);
+        if remote_diff > 0 && local_diff > 0 {
+            let slope = (local_diff as f64) / (remote_diff as f64);
+            if !(0.8..1.2).contains(&slope) {
+                let discont = !self.deltas.is_empty();
+                self.reset_base(remote_time, local_time);
+                return Estimation::ResetSlope { slope, discont };
+            }
+        }
+
+        if (delta > self.skew && delta - self.skew > 1_000_000_000)
+            || (delta < self.skew && self.skew - delta > 1_000_000_000)
+        {
+            let skew = self.skew;
+            let discont = !self.deltas.is_empty();
+            self.reset_base(remote_time, local_time);
+            return Estimation::ResetDelta {
+                delta,
+                skew,
+                discont,
+            };
         }
 
-        if inner.filling {
-            if inner.deltas.is_empty() || delta < inner.min_delta {
-                inner.min_delta = delta;
+        if self.filling {
+            if self.deltas.is_empty() || delta < self.min_delta {
+                self.min_delta = delta;
             }
-            inner.deltas.push_back(delta);
+            self.deltas.push_back(delta);
 
-            if remote_diff > WINDOW_DURATION || inner.deltas.len() as u64 == WINDOW_LENGTH {
-                inner.window_size = inner.deltas.len();
-                inner.skew = inner.min_delta;
-                inner.filling = false;
+            if remote_diff > WINDOW_DURATION || self.deltas.len() as u64 == WINDOW_LENGTH {
+                self.window_size = self.deltas.len();
+                self.skew = self.min_delta;
+                self.filling = false;
             } else {
                 let perc_time = remote_diff.mul_div_floor(100, WINDOW_DURATION).unwrap() as i64;
-                let perc_window = (inner.deltas.len() as u64)
+                let perc_window = (self.deltas.len() as u64)
                     .mul_div_floor(100, WINDOW_LENGTH)
                     .unwrap() as i64;
                 let perc = cmp::max(perc_time, perc_window);
 
-                inner.skew = (perc * inner.min_delta + ((10_000 - perc) * inner.skew)) / 10_000;
+                self.skew = (perc * self.min_delta + ((10_000 - perc) * self.skew)) / 10_000;
             }
         } else {
-            let old = inner.deltas.pop_front().unwrap();
-            inner.deltas.push_back(delta);
+            let old = self.deltas.pop_front().unwrap();
+            self.deltas.push_back(delta);
 
-            if delta <= inner.min_delta {
-                inner.min_delta = delta;
-            } else if old == inner.min_delta {
-                inner.min_delta = inner.deltas.iter().copied().min().unwrap();
+            if delta <= self.min_delta {
+                self.min_delta = delta;
+            } else if old == self.min_delta {
+                self.min_delta = self.deltas.iter().copied().min().unwrap();
             }
 
-            inner.skew = (inner.min_delta + (124 * inner.skew)) / 125;
+            self.skew = (self.min_delta + (124 * self.skew)) / 125;
         }
 
+        self.remote_diff = remote_diff;
+
         let out_time = base_local_time + remote_diff;
-        let out_time = if inner.skew < 0 {
-            out_time.saturating_sub((-inner.skew) as u64)
+        let out_time = if self.skew < 0 {
+            out_time.saturating_sub((-self.skew) as u64)
         } else {
-            out_time + (inner.skew as u64)
+            out_time + (self.skew as u64)
         };
 
-        gst_trace!(
-            CAT,
-            obj: element,
-            "Skew {}, min delta {}",
-            inner.skew,
-            inner.min_delta
-        );
-        gst_trace!(
-            CAT,
-            obj: element,
-            "Outputting {}",
-            gst::ClockTime::from_nseconds(out_time)
-        );
+        Estimation::Estimated {
+            time: out_time,
+            skew: self.skew,
+            min_delta: self.min_delta,
+        }
+    }
+}
+
+// Time without frames after which it is checked if the source is still announced, and
+// interval of these checks
+const LIVENESS_IDLE_TIME: time::Duration = time::Duration::from_secs(1);
+const LIVENESS_CHECK_INTERVAL: time::Duration = time::Duration::from_millis(500);
+// Time the source is looked for before it is considered gone
+const LIVENESS_DISCOVERY_TIME: time::Duration = time::Duration::from_secs(3);
+
+// Interval in which gaps are output if no audio/video is received in metadata-only mode
+const METADATA_ONLY_GAP_INTERVAL: time::Duration = time::Duration::from_millis(100);
+
+// Backwards jumps of the timecode by more than this are considered a wraparound
+// instead of reordering between the audio and video stream
+const TIMECODE_WRAP_THRESHOLD: gst::ClockTime = gst::ClockTime::SECOND;
+
+// Delay before the given connection retry, doubling with every retry up to 64 times the interval
+pub fn connect_retry_delay(interval: u32, retry: u32) -> time::Duration {
+    time::Duration::from_millis((interval as u64) << cmp::min(retry, 6))
+}
+
+// First NDI timestamp received by any source in shared-timestamp mode and the clock time it
+// was received at. All sources map their timestamps relative to this until the last receiver in
+// shared-timestamp mode is gone, so that the next pipeline starts with a new base.
+#[derive(Default)]
+struct SharedTimestampBase {
+    receivers: usize,
+    base: Option<(gst::ClockTime, gst::ClockTime)>,
+}
+
+static SHARED_TIMESTAMP_BASE: Lazy<Mutex<SharedTimestampBase>> =
+    Lazy::new(|| Mutex::new(SharedTimestampBase::default()));
+
+#[derive(Clone)]
+struct TimecodeTracker(Arc<Mutex<TimecodeTrackerInner>>);
+
+#[derive(Default)]
+struct TimecodeTrackerInner {
+    // First timecode after the last wraparound and the PTS it corresponds to
+    base_timecode: Option<gst::ClockTime>,
+    base_pts: gst::ClockTime,
+    // Highest timecode since the last wraparound and the end of the corresponding buffer
+    last_timecode: Option<gst::ClockTime>,
+    last_end: gst::ClockTime,
+}
+
+impl TimecodeTracker {
+    fn new() -> Self {
+        Self(Arc::new(Mutex::new(TimecodeTrackerInner::default())))
+    }
+
+    // Converts the timecode into a running time by taking the first timecode as base. If the
+    // timecode jumps backwards this is handled as a wraparound and the PTS continues from
+    // the end of the previous buffer instead of going backwards.
+    fn process(
+        &self,
+        element: &gst_base::BaseSrc,
+        timecode: gst::ClockTime,
+        receive_time: gst::ClockTime,
+        duration: Option<gst::ClockTime>,
+    ) -> (gst::ClockTime, Option<gst::ClockTime>, bool) {
+        let mut inner = self.0.lock().unwrap();
+
+        let mut discont = false;
+        let base_timecode = match (inner.base_timecode, inner.last_timecode) {
+            (None, _) | (_, None) => {
+                gst_debug!(
+                    CAT,
+                    obj: element,
+                    "Initializing timecode base {} at running time {}",
+                    timecode,
+                    receive_time,
+                );
+                inner.base_timecode = Some(timecode);
+                inner.base_pts = receive_time;
+                timecode
+            }
+            (Some(_), Some(last_timecode))
+                if timecode + TIMECODE_WRAP_THRESHOLD < last_timecode =>
+            {
+                gst_debug!(
+                    CAT,
+                    obj: element,
+                    "Timecode wrapped around from {} to {}, continuing at {}",
+                    last_timecode,
+                    timecode,
+                    inner.last_end,
+                );
+                inner.base_timecode = Some(timecode);
+                inner.base_pts = inner.last_end;
+                inner.last_timecode = None;
+                discont = true;
+                timecode
+            }
+            (Some(base_timecode), Some(_)) => base_timecode,
+        };
+
+        // Timecodes slightly before the base can happen when audio and video are slightly
+        // reordered against each other.
+        let pts = if timecode >= base_timecode {
+            inner.base_pts + (timecode - base_timecode)
+        } else {
+            inner.base_pts.saturating_sub(base_timecode - timecode)
+        };
+
+        if Some(timecode) > inner.last_timecode {
+            inner.last_timecode = Some(timecode);
+            inner.last_end = pts + duration.unwrap_or(gst::ClockTime::ZERO);
+        }
 
-        (gst::ClockTime::from_nseconds(out_time), duration, false)
+        (pts, duration, discont)
     }
 }
 
+#[derive(Debug, Default, Clone, PartialEq)]
+struct Capabilities {
+    ptz_supported: bool,
+    web_control_url: Option<String>,
+}
+
 #[derive(Clone)]
 pub struct ReceiverControlHandle {
     queue: ReceiverQueue,
+    source_info: Arc<Mutex<Option<gst::Structure>>>,
+    queue_depth: Arc<Mutex<Option<Queue>>>,
+    has_audio: Arc<AtomicBool>,
+    has_video: Arc<AtomicBool>,
+    capabilities: Arc<Mutex<Capabilities>>,
+    observations: Observations,
 }
 
 impl ReceiverControlHandle {
     pub fn set_flushing(&self, flushing: bool) {
-        let mut queue = (self.queue.0).0.lock().unwrap();
-        queue.flushing = flushing;
-        (self.queue.0).1.notify_all();
+        self.queue.set_flushing(flushing);
     }
 
     pub fn set_playing(&self, playing: bool) {
@@ -445,17 +947,107 @@ impl ReceiverControlHandle {
         queue.shutdown = true;
         (self.queue.0).1.notify_all();
     }
-}
 
-impl Drop for ReceiverInner {
-    fn drop(&mut self) {
-        // Will shut down the receiver thread on the next iteration
+    // Drop everything that is currently queued and restart the latency and skew estimation so
+    // that the next buffer is the most recently captured frame
+    pub fn seek_to_live(&self) {
         let mut queue = (self.queue.0).0.lock().unwrap();
-        queue.shutdown = true;
-        drop(queue);
+        queue.buffer_queue.clear();
+        queue.gap = None;
+        self.observations.reset();
+        (self.queue.0).1.notify_all();
+    }
+
+    pub fn source_info(&self) -> Option<gst::Structure> {
+        self.source_info.lock().unwrap().clone()
+    }
+
+    pub fn queue_depth(&self) -> Option<gst::Structure> {
+        self.queue_depth.lock().unwrap().as_ref().map(|queue| {
+            gst::Structure::builder("ndi-queue-depth")
+                .field("audio-frames", queue.audio_frames())
+                .field("video-frames", queue.video_frames())
+                .field("metadata-frames", queue.metadata_frames())
+                .build()
+        })
+    }
+
+    pub fn clock_drift(&self) -> Option<f64> {
+        self.observations.drift()
+    }
+
+    pub fn is_connected(&self) -> bool {
+        (self.queue.0).0.lock().unwrap().connected
+    }
+
+    pub fn wait_for_connection(&self, timeout: time::Duration) -> bool {
+        self.queue.wait_for_connection(timeout)
+    }
+
+    pub fn has_audio(&self) -> bool {
+        self.has_audio.load(atomic::Ordering::SeqCst)
+    }
+
+    pub fn has_video(&self) -> bool {
+        self.has_video.load(atomic::Ordering::SeqCst)
+    }
+
+    pub fn ptz_supported(&self) -> bool {
+        self.capabilities.lock().unwrap().ptz_supported
+    }
+
+    pub fn web_control_url(&self) -> Option<String> {
+        self.capabilities.lock().unwrap().web_control_url.clone()
+    }
+}
+
+// Everything the receive thread needs from the NDI receiver. Besides RecvInstance this is
+// implemented by a fake in the tests that replays canned frames.
+trait FrameSource: Send + 'static {
+    fn capture(&self, timeout_in_ms: u32) -> Result<Option<Frame<'_>>, ()>;
+    fn get_queue(&self) -> Queue;
+    fn ptz_is_supported(&self) -> bool;
+    fn web_control(&self) -> Option<String>;
+}
+
+impl FrameSource for RecvInstance {
+    fn capture(&self, timeout_in_ms: u32) -> Result<Option<Frame<'_>>, ()> {
+        RecvInstance::capture(self, timeout_in_ms)
+    }
+
+    fn get_queue(&self) -> Queue {
+        RecvInstance::get_queue(self)
+    }
+
+    fn ptz_is_supported(&self) -> bool {
+        RecvInstance::ptz_is_supported(self)
+    }
+
+    fn web_control(&self) -> Option<String> {
+        RecvInstance::web_control(self)
+    }
+}
+
+impl Drop for ReceiverInner {
+    fn drop(&mut self) {
+        // Will shut down the receiver thread on the next iteration
+        let mut queue = (self.queue.0).0.lock().unwrap();
+        queue.shutdown = true;
+        drop(queue);
 
         let element = self.element.upgrade();
 
+        if self.settings.timestamp_mode == TimestampMode::SharedTimestamp {
+            let mut shared_timestamp_base = SHARED_TIMESTAMP_BASE.lock().unwrap();
+            shared_timestamp_base.receivers -= 1;
+            if shared_timestamp_base.receivers == 0 {
+                if let Some(ref element) = element {
+                    gst_debug!(CAT, obj: element, "Resetting shared timestamp base");
+                }
+                shared_timestamp_base.base = None;
+            }
+        }
+
         if let Some(ref element) = element {
             gst_debug!(CAT, obj: element, "Closed NDI connection");
         }
@@ -463,37 +1055,56 @@ impl Drop for ReceiverInner {
 }
 
 impl Receiver {
-    fn new(
-        recv: RecvInstance,
-        timestamp_mode: TimestampMode,
-        timeout: u32,
-        connect_timeout: u32,
-        max_queue_length: usize,
+    fn new<R: FrameSource>(
+        recv: R,
+        ndi_name: Option<&str>,
+        url_address: Option<&str>,
+        settings: &ReceiverSettings,
+        find: Option<SharedFind>,
         element: &gst_base::BaseSrc,
     ) -> Self {
+        if settings.timestamp_mode == TimestampMode::SharedTimestamp {
+            SHARED_TIMESTAMP_BASE.lock().unwrap().receivers += 1;
+        }
+
         let receiver = Receiver(Arc::new(ReceiverInner {
             queue: ReceiverQueue(Arc::new((
                 Mutex::new(ReceiverQueueInner {
                     shutdown: false,
                     playing: false,
                     flushing: false,
-                    buffer_queue: VecDeque::with_capacity(max_queue_length),
+                    buffer_queue: VecDeque::with_capacity(settings.max_queue_length),
+                    gap: None,
                     error: None,
                     timeout: false,
+                    connected: false,
                 }),
                 Condvar::new(),
             ))),
-            max_queue_length,
+            settings: settings.clone(),
+            find,
             observations: Observations::new(),
+            timecode_tracker: TimecodeTracker::new(),
+            source_info: Arc::new(Mutex::new(None)),
+            queue_depth: Arc::new(Mutex::new(None)),
+            has_audio: Arc::new(AtomicBool::new(false)),
+            has_video: Arc::new(AtomicBool::new(false)),
+            capabilities: Arc::new(Mutex::new(Capabilities::default())),
             element: element.downgrade(),
-            timestamp_mode,
-            timeout,
-            connect_timeout,
+            ndi_name: ndi_name.map(String::from),
+            url_address: url_address.map(String::from),
+            next_audio_pts: Mutex::new(None),
+            last_video_timecode: Mutex::new(None),
+            auto_timestamp_mode: Mutex::new(None),
+            #[cfg(feature = "advanced-sdk")]
+            last_extra_data: Mutex::new(None),
             thread: Mutex::new(None),
         }));
 
         let weak = Arc::downgrade(&receiver.0);
-        let thread = thread::spawn(move || {
+        // Name the thread after the element to make it identifiable in debuggers and top
+        let thread_name = format!("ndi-recv-{}", element.name());
+        let thread = thread::Builder::new().name(thread_name).spawn(move || {
             use std::panic;
 
             let weak_clone = weak.clone();
@@ -507,33 +1118,82 @@ impl Receiver {
                             gst::element_error!(
                                 element,
                                 gst::LibraryError::Failed,
-                                ["Panic while connecting to NDI source"]
+                                [
+                                    "Panic while connecting to NDI source {}",
+                                    receiver.source_name()
+                                ]
                             );
                         }
 
                         let mut queue = (receiver.0.queue.0).0.lock().unwrap();
                         queue.error = Some(gst::FlowError::Error);
-                        (receiver.0.queue.0).1.notify_one();
+                        (receiver.0.queue.0).1.notify_all();
                     }
                 }
             }
         });
 
-        *receiver.0.thread.lock().unwrap() = Some(thread);
+        *receiver.0.thread.lock().unwrap() = Some(thread.expect("Failed to spawn receive thread"));
 
         receiver
     }
 
+    // Queries the PTZ support and web control URL of the source and notifies the corresponding
+    // properties if they changed
+    fn update_capabilities<R: FrameSource>(&self, element: &gst_base::BaseSrc, recv: &R) {
+        let capabilities = Capabilities {
+            ptz_supported: recv.ptz_is_supported(),
+            web_control_url: recv.web_control(),
+        };
+
+        let mut stored_capabilities = self.0.capabilities.lock().unwrap();
+        if *stored_capabilities == capabilities {
+            return;
+        }
+
+        gst_debug!(
+            CAT,
+            obj: element,
+            "Source capabilities changed to {:?}",
+            capabilities
+        );
+        let ptz_changed = stored_capabilities.ptz_supported != capabilities.ptz_supported;
+        let web_control_changed =
+            stored_capabilities.web_control_url != capabilities.web_control_url;
+        *stored_capabilities = capabilities;
+        drop(stored_capabilities);
+
+        if ptz_changed {
+            element.notify("ptz-supported");
+        }
+        if web_control_changed {
+            element.notify("web-control-url");
+        }
+    }
+
+    // NDI name of the source, or its URL/address if connecting directly
+    fn source_name(&self) -> &str {
+        self.0
+            .ndi_name
+            .as_deref()
+            .or(self.0.url_address.as_deref())
+            .unwrap_or_default()
+    }
+
     pub fn receiver_control_handle(&self) -> ReceiverControlHandle {
         ReceiverControlHandle {
             queue: self.0.queue.clone(),
+            source_info: self.0.source_info.clone(),
+            queue_depth: self.0.queue_depth.clone(),
+            has_audio: self.0.has_audio.clone(),
+            has_video: self.0.has_video.clone(),
+            capabilities: self.0.capabilities.clone(),
+            observations: self.0.observations.clone(),
         }
     }
 
     pub fn set_flushing(&self, flushing: bool) {
-        let mut queue = (self.0.queue.0).0.lock().unwrap();
-        queue.flushing = flushing;
-        (self.0.queue.0).1.notify_all();
+        self.0.queue.set_flushing(flushing);
     }
 
     pub fn set_playing(&self, playing: bool) {
@@ -547,17 +1207,32 @@ impl Receiver {
         (self.0.queue.0).1.notify_all();
     }
 
+    pub fn is_connected(&self) -> bool {
+        (self.0.queue.0).0.lock().unwrap().connected
+    }
+
+    // Blocks until the first frame was received from the source. Returns false if the receiver
+    // failed, timed out or was shut down before, or if nothing was received within the timeout
+    pub fn wait_for_connection(&self, timeout: time::Duration) -> bool {
+        self.0.queue.wait_for_connection(timeout)
+    }
+
     pub fn capture(&self) -> ReceiverItem {
         let mut queue = (self.0.queue.0).0.lock().unwrap();
         loop {
-            if let Some(err) = queue.error {
+            // Stopping or flushing always takes precedence so that a user-initiated state change
+            // never results in an error or EOS, while a source timeout only results in EOS once
+            // everything captured before it was output
+            if queue.flushing || queue.shutdown {
+                return ReceiverItem::Flushing;
+            } else if let Some(err) = queue.error {
                 return ReceiverItem::Error(err);
             } else if queue.buffer_queue.is_empty() && queue.timeout {
                 return ReceiverItem::Timeout;
-            } else if queue.flushing || queue.shutdown {
-                return ReceiverItem::Flushing;
             } else if let Some(buffer) = queue.buffer_queue.pop_front() {
                 return ReceiverItem::Buffer(buffer);
+            } else if let Some((pts, duration)) = queue.gap.take() {
+                return ReceiverItem::Gap(pts, duration);
             }
 
             queue = (self.0.queue.0).1.wait(queue).unwrap();
@@ -568,39 +1243,51 @@ impl Receiver {
         element: &gst_base::BaseSrc,
         ndi_name: Option<&str>,
         url_address: Option<&str>,
-        receiver_ndi_name: &str,
-        connect_timeout: u32,
-        bandwidth: NDIlib_recv_bandwidth_e,
-        color_format: NDIlib_recv_color_format_e,
-        timestamp_mode: TimestampMode,
-        timeout: u32,
-        max_queue_length: usize,
+        settings: &ReceiverSettings,
+        find: Option<SharedFind>,
     ) -> Option<Self> {
-        gst_debug!(CAT, obj: element, "Starting NDI connection...");
-
         assert!(ndi_name.is_some() || url_address.is_some());
 
+        let source_name = ndi_name.or(url_address).unwrap();
         gst_debug!(
             CAT,
             obj: element,
-            "Connecting to NDI source with NDI name '{:?}' and URL/Address {:?}",
-            ndi_name,
-            url_address,
+            "Starting NDI connection to {} as '{}'...",
+            source_name,
+            settings.receiver_ndi_name,
         );
 
-        // FIXME: Ideally we would use NDIlib_recv_color_format_fastest here but that seems to be
-        // broken with interlaced content currently
-        let recv = RecvInstance::builder(ndi_name, url_address, receiver_ndi_name)
-            .bandwidth(bandwidth)
-            .color_format(color_format)
-            .allow_video_fields(true)
+        if ndi_name.is_none() {
+            // Without an NDI name the SDK connects directly to the URL/address and doesn't
+            // need to discover the source first, which also works across subnets
+            gst_debug!(
+                CAT,
+                obj: element,
+                "Connecting directly to NDI source with URL/Address {:?}",
+                url_address,
+            );
+        } else {
+            gst_debug!(
+                CAT,
+                obj: element,
+                "Connecting to NDI source with NDI name '{:?}' and URL/Address {:?}",
+                ndi_name,
+                url_address,
+            );
+        }
+
+        let recv = RecvInstance::builder(ndi_name, url_address, &settings.receiver_ndi_name)
+            .bandwidth(settings.bandwidth)
+            .color_format(settings.color_format)
+            .allow_video_fields(settings.allow_video_fields && !settings.force_progressive)
             .build();
         let recv = match recv {
             None => {
                 gst::element_error!(
                     element,
                     gst::CoreError::Negotiation,
-                    ["Failed to connect to source"]
+                    ["Failed to connect to source {}", source_name],
+                    details: ErrorCode::ConnectFailed.details()
                 );
                 return None;
             }
@@ -609,27 +1296,33 @@ impl Receiver {
 
         recv.set_tally(&Tally::default());
 
-        let enable_hw_accel = MetadataFrame::new(0, Some("<ndi_hwaccel enabled=\"true\"/>"));
-        recv.send_metadata(&enable_hw_accel);
+        // Hardware accelerated decoding can be slower than software decoding on some systems,
+        // especially with only a few streams
+        if settings.hwaccel {
+            let enable_hw_accel = MetadataFrame::new(0, Some("<ndi_hwaccel enabled=\"true\"/>"));
+            recv.send_metadata(&enable_hw_accel);
+        }
 
         // This will set info.audio/video accordingly
-        let receiver = Receiver::new(
-            recv,
-            timestamp_mode,
-            timeout,
-            connect_timeout,
-            max_queue_length,
-            element,
-        );
+        let receiver = Receiver::new(recv, ndi_name, url_address, settings, find, element);
 
         Some(receiver)
     }
 
-    fn receive_thread(receiver: &Weak<ReceiverInner>, recv: RecvInstance) {
+    fn receive_thread<R: FrameSource>(receiver: &Weak<ReceiverInner>, recv: R) {
         let mut first_video_frame = true;
         let mut first_audio_frame = true;
         let mut first_frame = true;
+        let mut connected = false;
         let mut timer = time::Instant::now();
+        // Time since the last frame or the last gap output because of the frame timeout
+        let mut frame_timer = time::Instant::now();
+        let mut stalled = false;
+        let mut gap_timer = time::Instant::now();
+        let mut freeze_state = FreezeState::default();
+        let mut liveness = Liveness::default();
+        let mut capture_errors = 0;
+        let mut connect_retries = 0;
 
         // Capture until error or shutdown
         loop {
@@ -660,34 +1353,159 @@ impl Receiver {
             };
 
             let timeout = if first_frame {
-                receiver.0.connect_timeout
+                receiver.0.settings.connect_timeout
             } else {
-                receiver.0.timeout
+                receiver.0.settings.timeout
             };
 
-            let res = match recv.capture(50) {
+            *receiver.0.queue_depth.lock().unwrap() = Some(recv.get_queue());
+
+            // Without audio/video, regularly output gaps so that downstream knows that time
+            // progresses instead of timing out
+            if receiver.0.settings.bandwidth == NDIlib_recv_bandwidth_metadata_only
+                && !flushing
+                && gap_timer.elapsed() >= METADATA_ONLY_GAP_INTERVAL
+            {
+                gap_timer = time::Instant::now();
+                if let Some(now) = element.current_running_time() {
+                    receiver.queue_gap(
+                        now,
+                        gst::ClockTime::from_nseconds(METADATA_ONLY_GAP_INTERVAL.as_nanos() as u64),
+                    );
+                }
+            }
+
+            let frame = recv.capture(50);
+            if frame.is_ok() {
+                capture_errors = 0;
+            }
+
+            if connected && !flushing && matches!(frame, Ok(None)) {
+                liveness.idle(&element, &receiver, timer.elapsed());
+            }
+
+            let res = match frame {
                 _ if flushing => {
                     gst_debug!(CAT, obj: &element, "Flushing");
                     Err(gst::FlowError::Flushing)
                 }
+                Err(_) if capture_errors < receiver.0.settings.capture_retries => {
+                    // Back off exponentially from 50ms up to 800ms between retries
+                    let backoff = time::Duration::from_millis(50 << cmp::min(capture_errors, 4));
+                    capture_errors += 1;
+                    gst_warning!(
+                        CAT,
+                        obj: &element,
+                        "Error receiving frame from {}, retrying in {:?} ({}/{})",
+                        receiver.source_name(),
+                        backoff,
+                        capture_errors,
+                        receiver.0.settings.capture_retries,
+                    );
+                    thread::sleep(backoff);
+                    continue;
+                }
                 Err(_) => {
                     gst::element_error!(
                         element,
                         gst::ResourceError::Read,
-                        ["Error receiving frame"]
+                        ["Error receiving frame from {}", receiver.source_name()],
+                        details: ErrorCode::CaptureError.details()
                     );
                     Err(gst::FlowError::Error)
                 }
-                Ok(None) if timeout > 0 && timer.elapsed().as_millis() >= timeout as u128 => {
-                    gst_debug!(CAT, obj: &element, "Timed out -- assuming EOS",);
+                Ok(None)
+                    if first_frame
+                        && timeout > 0
+                        && connect_retries < receiver.0.settings.connect_retries
+                        && receiver.0.settings.bandwidth != NDIlib_recv_bandwidth_metadata_only
+                        && timer.elapsed().as_millis() >= timeout as u128 =>
+                {
+                    let delay = connect_retry_delay(
+                        receiver.0.settings.connect_retry_interval,
+                        connect_retries,
+                    );
+                    connect_retries += 1;
+                    gst_warning!(
+                        CAT,
+                        obj: &element,
+                        "No frame received from {} yet, retrying in {:?} ({}/{})",
+                        receiver.source_name(),
+                        delay,
+                        connect_retries,
+                        receiver.0.settings.connect_retries,
+                    );
+
+                    receiver.0.queue.wait_for_retry(delay);
+                    timer = time::Instant::now();
+                    continue;
+                }
+                Ok(None)
+                    if timeout > 0
+                        && receiver.0.settings.bandwidth != NDIlib_recv_bandwidth_metadata_only
+                        && timer.elapsed().as_millis() >= timeout as u128 =>
+                {
+                    gst_debug!(
+                        CAT,
+                        obj: &element,
+                        "Timed out waiting for {} -- assuming EOS",
+                        receiver.source_name(),
+                    );
                     Err(gst::FlowError::Eos)
                 }
+                Ok(None)
+                    if !first_frame
+                        && receiver.0.settings.frame_timeout > 0
+                        && receiver.0.settings.bandwidth != NDIlib_recv_bandwidth_metadata_only
+                        && frame_timer.elapsed().as_millis()
+                            >= receiver.0.settings.frame_timeout as u128 =>
+                {
+                    if !stalled {
+                        stalled = true;
+                        gst::element_warning!(
+                            element,
+                            gst::ResourceError::Read,
+                            [
+                                "No frame received from {} for {}ms",
+                                receiver.source_name(),
+                                timer.elapsed().as_millis()
+                            ]
+                        );
+                    }
+
+                    // Let downstream know that time progresses while waiting for frames
+                    if let Some(now) = element.current_running_time() {
+                        receiver.queue_gap(
+                            now,
+                            gst::ClockTime::from_mseconds(receiver.0.settings.frame_timeout as u64),
+                        );
+                    }
+                    frame_timer = time::Instant::now();
+                    continue;
+                }
+                // Only freezes until one of the timeouts above applies
+                Ok(None) if receiver.0.settings.freeze_on_loss && !first_frame => {
+                    for item in receiver.create_freeze_buffers(&element, &mut freeze_state) {
+                        receiver.queue_buffer(&element, item);
+                    }
+                    continue;
+                }
                 Ok(None) => {
                     gst_debug!(CAT, obj: &element, "No frame received yet, retry");
                     continue;
                 }
+                Ok(Some(Frame::Video(_)))
+                    if receiver.0.settings.bandwidth == NDIlib_recv_bandwidth_audio_only =>
+                {
+                    // The sender might still send video until it handled the bandwidth request
+                    gst_trace!(CAT, obj: &element, "Dropping video frame in audio-only mode");
+                    continue;
+                }
                 Ok(Some(Frame::Video(frame))) => {
                     first_frame = false;
+                    if !receiver.0.has_video.swap(true, atomic::Ordering::SeqCst) {
+                        element.notify("has-video");
+                    }
                     let mut buffer = receiver.create_video_buffer_and_info(&element, frame);
                     if first_video_frame {
                         if let Ok(Buffer::Video(ref mut buffer, _)) = buffer {
@@ -702,6 +1520,9 @@ impl Receiver {
                 }
                 Ok(Some(Frame::Audio(frame))) => {
                     first_frame = false;
+                    if !receiver.0.has_audio.swap(true, atomic::Ordering::SeqCst) {
+                        element.notify("has-audio");
+                    }
                     let mut buffer = receiver.create_audio_buffer_and_info(&element, frame);
                     if first_audio_frame {
                         if let Ok(Buffer::Audio(ref mut buffer, _)) = buffer {
@@ -714,42 +1535,151 @@ impl Receiver {
                     }
                     buffer
                 }
+                Ok(Some(Frame::StatusChange)) => {
+                    gst_debug!(CAT, obj: &element, "Source status changed");
+                    receiver.update_capabilities(&element, &recv);
+                    continue;
+                }
                 Ok(Some(Frame::Metadata(frame))) => {
-                    if let Some(metadata) = frame.metadata() {
-                        gst_debug!(
-                            CAT,
-                            obj: &element,
-                            "Received metadata at timecode {}: {}",
-                            gst::ClockTime::from_nseconds(frame.timecode() as u64 * 100),
-                            metadata,
-                        );
-                    }
+                    let metadata = match frame.metadata_bytes().map(std::str::from_utf8) {
+                        Some(Err(err)) if receiver.0.settings.strict_metadata => {
+                            gst::element_error!(
+                                element,
+                                gst::StreamError::Decode,
+                                ["Received metadata that is not valid UTF-8: {}", err]
+                            );
+                            Err(gst::FlowError::Error)
+                        }
+                        Some(Err(err)) => {
+                            gst_warning!(
+                                CAT,
+                                obj: &element,
+                                "Replacing invalid UTF-8 in received metadata: {}",
+                                err
+                            );
+                            Ok(frame.metadata())
+                        }
+                        Some(Ok(metadata)) => Ok(Some(metadata.into())),
+                        None => Ok(None),
+                    };
+
+                    match metadata {
+                        Err(err) => Err(err),
+                        Ok(metadata) => {
+                            if let Some(metadata) = metadata.as_deref() {
+                                gst_debug!(
+                                    CAT,
+                                    obj: &element,
+                                    "Received metadata at timecode {}: {}",
+                                    gst::ClockTime::from_nseconds(frame.timecode() as u64 * 100),
+                                    metadata,
+                                );
+
+                                if let Some(source_info) = parse_source_info(metadata) {
+                                    let mut stored_source_info =
+                                        receiver.0.source_info.lock().unwrap();
+                                    if stored_source_info.is_none() {
+                                        gst_debug!(
+                                            CAT,
+                                            obj: &element,
+                                            "Connected to source {}: {}",
+                                            receiver.source_name(),
+                                            source_info
+                                        );
+                                        *stored_source_info = Some(source_info);
+                                        drop(stored_source_info);
+                                        element.notify("source-info");
+                                    }
+                                }
+
+                                #[cfg(feature = "kvm")]
+                                if is_kvm_metadata(metadata) {
+                                    element.emit_by_name::<()>(
+                                        "kvm-data",
+                                        &[
+                                            &(frame.timecode() as u64 * 100),
+                                            &glib::Bytes::from(metadata.as_bytes()),
+                                        ],
+                                    );
+                                }
+
+                                if receiver.0.settings.bandwidth
+                                    == NDIlib_recv_bandwidth_metadata_only
+                                {
+                                    let _ = element.post_message(
+                                        gst::message::Element::builder(
+                                            gst::Structure::builder("ndi-metadata")
+                                                .field(
+                                                    "timecode",
+                                                    gst::ClockTime::from_nseconds(
+                                                        frame.timecode() as u64 * 100,
+                                                    ),
+                                                )
+                                                .field("metadata", metadata)
+                                                .build(),
+                                        )
+                                        .src(&element)
+                                        .build(),
+                                    );
+                                }
+                            }
 
-                    continue;
+                            continue;
+                        }
+                    }
                 }
             };
 
             match res {
-                Ok(item) => {
-                    let mut queue = (receiver.0.queue.0).0.lock().unwrap();
-                    while queue.buffer_queue.len() > receiver.0.max_queue_length {
-                        gst_warning!(
+                Ok(mut item) => {
+                    if !connected {
+                        connected = true;
+                        gst_debug!(
                             CAT,
                             obj: &element,
-                            "Dropping old buffer -- queue has {} items",
-                            queue.buffer_queue.len()
+                            "Received first frame, connected to {}",
+                            receiver.source_name(),
+                        );
+                        receiver.update_capabilities(&element, &recv);
+                        {
+                            let mut queue = (receiver.0.queue.0).0.lock().unwrap();
+                            queue.connected = true;
+                            (receiver.0.queue.0).1.notify_all();
+                        }
+                        element.notify("connected");
+                        let _ = element.post_message(
+                            gst::message::Element::builder(
+                                gst::Structure::builder("ndi-connected")
+                                    .field("ndi-name", &receiver.0.ndi_name)
+                                    .field("url-address", &receiver.0.url_address)
+                                    .build(),
+                            )
+                            .src(&element)
+                            .build(),
                         );
-                        queue.buffer_queue.pop_front();
                     }
-                    queue.buffer_queue.push_back(item);
-                    (receiver.0.queue.0).1.notify_one();
+                    liveness.frame_received(&element, &receiver);
+                    if receiver.0.settings.freeze_on_loss {
+                        freeze_state.update(&element, &mut item);
+                    }
+                    receiver.queue_buffer(&element, item);
                     timer = time::Instant::now();
+                    frame_timer = time::Instant::now();
+                    if stalled {
+                        stalled = false;
+                        gst_info!(
+                            CAT,
+                            obj: &element,
+                            "Receiving frames from {} again",
+                            receiver.source_name()
+                        );
+                    }
                 }
                 Err(gst::FlowError::Eos) => {
                     gst_debug!(CAT, obj: &element, "Signalling EOS");
                     let mut queue = (receiver.0.queue.0).0.lock().unwrap();
                     queue.timeout = true;
-                    (receiver.0.queue.0).1.notify_one();
+                    (receiver.0.queue.0).1.notify_all();
                     break;
                 }
                 Err(gst::FlowError::Flushing) => {
@@ -758,6 +1688,7 @@ impl Receiver {
                     queue.buffer_queue.clear();
                     (receiver.0.queue.0).1.notify_one();
                     timer = time::Instant::now();
+                    frame_timer = time::Instant::now();
                 }
                 Err(err) => {
                     gst_error!(CAT, obj: &element, "Signalling error");
@@ -765,11 +1696,107 @@ impl Receiver {
                     if queue.error.is_none() {
                         queue.error = Some(err);
                     }
-                    (receiver.0.queue.0).1.notify_one();
+                    (receiver.0.queue.0).1.notify_all();
+                    break;
+                }
+            }
+        }
+    }
+
+    fn queue_buffer(&self, element: &gst_base::BaseSrc, item: Buffer) {
+        let mut queue = (self.0.queue.0).0.lock().unwrap();
+        while queue.buffer_queue.len() > self.0.settings.max_queue_length {
+            gst_warning!(
+                CAT,
+                obj: element,
+                "Dropping old buffer -- queue has {} items",
+                queue.buffer_queue.len()
+            );
+            queue.buffer_queue.pop_front();
+        }
+        queue.buffer_queue.push_back(item);
+        (self.0.queue.0).1.notify_one();
+    }
+
+    fn queue_gap(&self, pts: gst::ClockTime, duration: gst::ClockTime) {
+        let mut queue = (self.0.queue.0).0.lock().unwrap();
+        queue.gap = Some((pts, duration));
+        (self.0.queue.0).1.notify_one();
+    }
+
+    // Repeats the last video frame and outputs silence for every frame duration that passed
+    // since the last frame was expected
+    fn create_freeze_buffers(
+        &self,
+        element: &gst_base::BaseSrc,
+        freeze_state: &mut FreezeState,
+    ) -> Vec<Buffer> {
+        let now = match element.current_running_time() {
+            Some(now) => now,
+            None => return vec![],
+        };
+
+        let mut buffers = vec![];
+
+        while let Some((last_buffer, info)) = freeze_state.last_video.take() {
+            let (next_pts, duration) = match last_buffer
+                .pts()
+                .zip(last_buffer.duration())
+                .map(|(pts, duration)| (pts + duration, duration))
+            {
+                // Only repeat once the next frame is late by a whole frame duration
+                Some((next_pts, duration)) if next_pts + duration <= now => (next_pts, duration),
+                _ => {
+                    freeze_state.last_video = Some((last_buffer, info));
+                    break;
+                }
+            };
+
+            gst_trace!(CAT, obj: element, "Repeating last video frame at {}", next_pts);
+
+            let mut buffer = last_buffer.copy();
+            {
+                let buffer = buffer.get_mut().unwrap();
+                buffer.set_pts(next_pts);
+                buffer.set_duration(duration);
+                buffer.set_flags(gst::BufferFlags::GAP);
+                if !freeze_state.frozen {
+                    buffer.set_flags(gst::BufferFlags::DISCONT);
+                }
+            }
+            freeze_state.frozen = true;
+
+            freeze_state.last_video = Some((buffer.clone(), info.clone()));
+            buffers.push(Buffer::Video(buffer, info));
+        }
+
+        if let Some((ref info, duration)) = freeze_state.last_audio {
+            while let Some(next_pts) = freeze_state.next_audio_pts {
+                if next_pts + duration > now {
                     break;
                 }
+
+                gst_trace!(CAT, obj: element, "Inserting silence at {}", next_pts);
+
+                let mut buffer = match create_silence_buffer(info, next_pts, duration) {
+                    Some(buffer) => buffer,
+                    None => break,
+                };
+                if !freeze_state.frozen {
+                    buffer
+                        .get_mut()
+                        .unwrap()
+                        .set_flags(gst::BufferFlags::DISCONT);
+                }
+                freeze_state.frozen = true;
+
+                freeze_state.next_audio_pts = Some(next_pts + duration);
+                *self.0.next_audio_pts.lock().unwrap() = Some(next_pts + duration);
+                buffers.push(Buffer::Audio(buffer, AudioInfo::AudioInfo(info.clone())));
             }
         }
+
+        buffers
     }
 
     fn calculate_timestamp(
@@ -800,7 +1827,30 @@ impl Receiver {
             real_time_now,
         );
 
-        let (pts, duration, discont) = match self.0.timestamp_mode {
+        let timestamp_mode = match self.0.settings.timestamp_mode {
+            TimestampMode::Auto => *self
+                .0
+                .auto_timestamp_mode
+                .lock()
+                .unwrap()
+                .get_or_insert_with(|| {
+                    let timestamp_mode = if timestamp.is_some() {
+                        TimestampMode::ReceiveTimeTimestamp
+                    } else {
+                        TimestampMode::ReceiveTimeTimecode
+                    };
+                    gst_debug!(
+                        CAT,
+                        obj: element,
+                        "Selected timestamp mode {:?} for source",
+                        timestamp_mode
+                    );
+                    timestamp_mode
+                }),
+            timestamp_mode => timestamp_mode,
+        };
+
+        let (pts, duration, discont) = match timestamp_mode {
             TimestampMode::ReceiveTimeTimecode => {
                 self.0
                     .observations
@@ -812,6 +1862,11 @@ impl Receiver {
                     .process(element, (timestamp, receive_time), duration)
             }
             TimestampMode::Timecode => (timecode, duration, false),
+            TimestampMode::RunningTimecode => {
+                self.0
+                    .timecode_tracker
+                    .process(element, timecode, receive_time, duration)
+            }
             TimestampMode::Timestamp if timestamp.is_none() => (receive_time, duration, false),
             TimestampMode::Timestamp => {
                 // Timestamps are relative to the UNIX epoch
@@ -828,7 +1883,33 @@ impl Receiver {
                     (receive_time + diff, duration, false)
                 }
             }
+            TimestampMode::SharedTimestamp if timestamp.is_none() => {
+                (receive_time, duration, false)
+            }
+            TimestampMode::SharedTimestamp => {
+                let timestamp = timestamp?;
+                let base_time = element.base_time()?;
+
+                let (base_timestamp, base_clock_time) = *SHARED_TIMESTAMP_BASE
+                    .lock()
+                    .unwrap()
+                    .base
+                    .get_or_insert_with(|| {
+                        gst_debug!(
+                            CAT,
+                            obj: element,
+                            "Initializing shared timestamp base {} at clock time {}",
+                            timestamp,
+                            receive_time + base_time,
+                        );
+                        (timestamp, receive_time + base_time)
+                    });
+
+                let clock_time = (base_clock_time + timestamp).saturating_sub(base_timestamp);
+                (clock_time.saturating_sub(base_time), duration, false)
+            }
             TimestampMode::ReceiveTime => (receive_time, duration, false),
+            TimestampMode::Auto => unreachable!(),
         };
 
         gst_log!(
@@ -865,6 +1946,13 @@ impl Receiver {
                 .unwrap()
                 .set_flags(gst::BufferFlags::RESYNC);
         }
+        if self.0.settings.timing_meta {
+            crate::nditimingmeta::NdiTimingMeta::add(
+                buffer.get_mut().unwrap(),
+                video_frame.timecode(),
+                video_frame.timestamp(),
+            );
+        }
 
         gst_log!(CAT, obj: element, "Produced video buffer {:?}", buffer);
 
@@ -876,10 +1964,28 @@ impl Receiver {
         element: &gst_base::BaseSrc,
         video_frame: &VideoFrame,
     ) -> Option<(gst::ClockTime, Option<gst::ClockTime>, bool)> {
-        let duration = gst::ClockTime::SECOND.mul_div_floor(
-            video_frame.frame_rate().1 as u64,
-            video_frame.frame_rate().0 as u64,
-        );
+        // Separate fields are sent at twice the frame rate, with each field lasting half a frame
+        let fields = match video_frame.frame_format_type() {
+            ndisys::NDIlib_frame_format_type_e::NDIlib_frame_format_type_field_0
+            | ndisys::NDIlib_frame_format_type_e::NDIlib_frame_format_type_field_1 => 2,
+            _ => 1,
+        };
+
+        let (fps_n, fps_d) = video_frame.frame_rate();
+        let duration = if fps_n > 0 {
+            gst::ClockTime::SECOND.mul_div_floor(fps_d as u64, fields * fps_n as u64)
+        } else {
+            // Variable frame rate, estimate the duration from the distance to the previous frame
+            let timecode = video_frame.timecode();
+            let mut last_video_timecode = self.0.last_video_timecode.lock().unwrap();
+            let duration = last_video_timecode
+                .filter(|last_timecode| timecode > *last_timecode)
+                .map(|last_timecode| {
+                    gst::ClockTime::from_nseconds((timecode - last_timecode) as u64 * 100)
+                });
+            *last_video_timecode = Some(timecode);
+            duration
+        };
 
         self.calculate_timestamp(
             element,
@@ -889,6 +1995,28 @@ impl Receiver {
         )
     }
 
+    // Colorimetry from the override, from the HDR metadata of the frame or otherwise the usual
+    // colorimetry for the resolution
+    fn video_colorimetry(&self, video_frame: &VideoFrame) -> gst_video::VideoColorimetry {
+        if let Some(colorimetry) = self.0.settings.colorimetry {
+            return colorimetry;
+        }
+
+        #[cfg(feature = "advanced-sdk")]
+        if let Some(colorimetry) = video_frame
+            .metadata()
+            .and_then(|metadata| parse_colorimetry(&metadata))
+        {
+            return colorimetry;
+        }
+
+        if video_frame.yres() > 576 {
+            "bt709".parse().unwrap()
+        } else {
+            "bt601".parse().unwrap()
+        }
+    }
+
     fn create_video_info(
         &self,
         element: &gst_base::BaseSrc,
@@ -896,13 +2024,24 @@ impl Receiver {
     ) -> Result<VideoInfo, gst::FlowError> {
         let fourcc = video_frame.fourcc();
 
-        let par = gst::Fraction::approximate_f32(video_frame.picture_aspect_ratio())
-            .unwrap_or_else(|| gst::Fraction::new(1, 1))
-            * gst::Fraction::new(video_frame.yres(), video_frame.xres());
+        // A picture aspect ratio of 0 means square pixels
+        let picture_aspect_ratio = video_frame.picture_aspect_ratio();
+        let par = if picture_aspect_ratio > 0.0 && picture_aspect_ratio.is_finite() {
+            gst::Fraction::approximate_f32(picture_aspect_ratio)
+                .map(|dar| dar * gst::Fraction::new(video_frame.yres(), video_frame.xres()))
+                .unwrap_or_else(|| gst::Fraction::new(1, 1))
+        } else {
+            gst::Fraction::new(1, 1)
+        };
         let interlace_mode = match video_frame.frame_format_type() {
             ndisys::NDIlib_frame_format_type_e::NDIlib_frame_format_type_progressive => {
                 gst_video::VideoInterlaceMode::Progressive
             }
+            ndisys::NDIlib_frame_format_type_e::NDIlib_frame_format_type_interleaved
+                if self.0.settings.force_progressive =>
+            {
+                gst_video::VideoInterlaceMode::Progressive
+            }
             ndisys::NDIlib_frame_format_type_e::NDIlib_frame_format_type_interleaved => {
                 gst_video::VideoInterlaceMode::Interleaved
             }
@@ -913,7 +2052,8 @@ impl Receiver {
                 gst::element_error!(
                     element,
                     gst::StreamError::Format,
-                    ["Separate field interlacing not supported"]
+                    ["Separate field interlacing not supported"],
+                    details: ErrorCode::UnsupportedFormat.details()
                 );
                 return Err(gst::FlowError::NotNegotiated);
             }
@@ -922,21 +2062,31 @@ impl Receiver {
         if [
             ndisys::NDIlib_FourCC_video_type_UYVY,
             ndisys::NDIlib_FourCC_video_type_UYVA,
+            ndisys::NDIlib_FourCC_video_type_P216,
+            ndisys::NDIlib_FourCC_video_type_PA16,
             ndisys::NDIlib_FourCC_video_type_YV12,
             ndisys::NDIlib_FourCC_video_type_NV12,
             ndisys::NDIlib_FourCC_video_type_I420,
             ndisys::NDIlib_FourCC_video_type_BGRA,
             ndisys::NDIlib_FourCC_video_type_BGRX,
             ndisys::NDIlib_FourCC_video_type_RGBA,
-            ndisys::NDIlib_FourCC_video_type_BGRX,
+            ndisys::NDIlib_FourCC_video_type_RGBX,
         ]
         .contains(&fourcc)
         {
-            // YV12 and I420 are swapped in the NDI SDK compared to GStreamer
+            // YV12 and I420 are swapped in the NDI SDK compared to GStreamer.
+            //
+            // The SDK has no FourCC for packed 24 bit RGB/BGR or for alpha-first ARGB/ABGR, so
+            // these are never produced here and would need a videoconvert downstream.
             let format = match video_frame.fourcc() {
                 ndisys::NDIlib_FourCC_video_type_UYVY => gst_video::VideoFormat::Uyvy,
                 // FIXME: This drops the alpha plane!
                 ndisys::NDIlib_FourCC_video_type_UYVA => gst_video::VideoFormat::Uyvy,
+                // GStreamer has no 16 bit semi-planar 4:2:2 format, so the lines are
+                // deinterleaved and reduced to 12 bits when copying
+                ndisys::NDIlib_FourCC_video_type_P216 => gst_video::VideoFormat::I42212le,
+                // FIXME: This drops the alpha plane!
+                ndisys::NDIlib_FourCC_video_type_PA16 => gst_video::VideoFormat::I42212le,
                 ndisys::NDIlib_FourCC_video_type_YV12 => gst_video::VideoFormat::I420,
                 ndisys::NDIlib_FourCC_video_type_NV12 => gst_video::VideoFormat::Nv12,
                 ndisys::NDIlib_FourCC_video_type_I420 => gst_video::VideoFormat::Yv12,
@@ -948,28 +2098,65 @@ impl Receiver {
                     gst::element_error!(
                         element,
                         gst::StreamError::Format,
-                        ["Unsupported video fourcc {:08x}", video_frame.fourcc()]
+                        ["Unsupported video fourcc {:08x}", video_frame.fourcc()],
+                        details: ErrorCode::UnsupportedFormat.details()
                     );
 
                     return Err(gst::FlowError::NotNegotiated);
-                } // TODO: NDIlib_FourCC_video_type_P216 and NDIlib_FourCC_video_type_PA16 not
-                  // supported by GStreamer
+                }
             };
 
-            #[cfg(feature = "interlaced-fields")]
-            {
-                let mut builder = gst_video::VideoInfo::builder(
-                    format,
-                    video_frame.xres() as u32,
-                    video_frame.yres() as u32,
-                )
-                .fps(gst::Fraction::from(video_frame.frame_rate()))
-                .par(par)
-                .interlace_mode(interlace_mode);
-
-                if video_frame.frame_format_type()
-                    == ndisys::NDIlib_frame_format_type_e::NDIlib_frame_format_type_interleaved
+            // The SDK produces BGRA/RGBA for sources with alpha even with the color formats that
+            // otherwise produce BGRX/RGBX. As the memory layout is the same, the alpha is dropped
+            // by outputting the variant without alpha, if requested or if only that one is
+            // allowed by video-caps
+            let format = match opaque_video_format(format) {
+                Some(opaque) if self.0.settings.ignore_alpha => opaque,
+                Some(opaque)
+                    if matches!(
+                        self.0.settings.video_formats,
+                        Some(ref formats) if !formats.contains(&format) && formats.contains(&opaque)
+                    ) =>
                 {
+                    opaque
+                }
+                _ => format,
+            };
+
+            if let Some(ref video_formats) = self.0.settings.video_formats {
+                if !video_formats.contains(&format) {
+                    gst::element_error!(
+                        element,
+                        gst::StreamError::Format,
+                        [
+                            "Video format {} not allowed by video-caps {:?}",
+                            format,
+                            video_formats
+                        ],
+                        details: ErrorCode::UnsupportedFormat.details()
+                    );
+
+                    return Err(gst::FlowError::NotNegotiated);
+                }
+            }
+
+            #[cfg(feature = "interlaced-fields")]
+            {
+                let mut builder = gst_video::VideoInfo::builder(
+                    format,
+                    video_frame.xres() as u32,
+                    video_frame.yres() as u32,
+                )
+                .fps(gst::Fraction::from(video_frame.frame_rate()))
+                .par(par)
+                .interlace_mode(interlace_mode);
+
+                let colorimetry = self.video_colorimetry(video_frame);
+                if gst_video::VideoFormatInfo::from_format(format).is_yuv() {
+                    builder = builder.colorimetry(&colorimetry);
+                }
+
+                if interlace_mode == gst_video::VideoInterlaceMode::Interleaved {
                     builder = builder.field_order(gst_video::VideoFieldOrder::TopFieldFirst);
                 }
 
@@ -995,9 +2182,12 @@ impl Receiver {
                 .par(par)
                 .interlace_mode(interlace_mode);
 
-                if video_frame.frame_format_type()
-                    == ndisys::NDIlib_frame_format_type_e::NDIlib_frame_format_type_interleaved
-                {
+                let colorimetry = self.video_colorimetry(video_frame);
+                if gst_video::VideoFormatInfo::from_format(format).is_yuv() {
+                    builder = builder.colorimetry(&colorimetry);
+                }
+
+                if interlace_mode == gst_video::VideoInterlaceMode::Interleaved {
                     builder = builder.field_order(gst_video::VideoFieldOrder::TopFieldFirst);
                 }
 
@@ -1038,7 +2228,8 @@ impl Receiver {
                         [
                             "Unsupported SpeedHQ video fourcc {:08x}",
                             video_frame.fourcc()
-                        ]
+                        ],
+                        details: ErrorCode::UnsupportedFormat.details()
                     );
 
                     return Err(gst::FlowError::NotNegotiated);
@@ -1084,6 +2275,13 @@ impl Receiver {
                 return Err(gst::FlowError::Error);
             }
 
+            let (profile, level) = self
+                .codec_sps(false, compressed_packet.extra_data)
+                .and_then(|sps| h264_profile_level(&sps))
+                .map_or((None, None), |(profile, level)| {
+                    (Some(profile), Some(level))
+                });
+
             return Ok(VideoInfo::H264Info {
                 xres: video_frame.xres(),
                 yres: video_frame.yres(),
@@ -1092,6 +2290,8 @@ impl Receiver {
                 par_n: par.numer(),
                 par_d: par.denom(),
                 interlace_mode,
+                profile,
+                level,
             });
         }
 
@@ -1122,6 +2322,13 @@ impl Receiver {
                 return Err(gst::FlowError::Error);
             }
 
+            let (profile, tier, level) = self
+                .codec_sps(true, compressed_packet.extra_data)
+                .and_then(|sps| h265_profile_tier_level(&sps))
+                .map_or((None, None, None), |(profile, tier, level)| {
+                    (profile, Some(tier), Some(level))
+                });
+
             return Ok(VideoInfo::H265Info {
                 xres: video_frame.xres(),
                 yres: video_frame.yres(),
@@ -1130,17 +2337,37 @@ impl Receiver {
                 par_n: par.numer(),
                 par_d: par.denom(),
                 interlace_mode,
+                profile,
+                tier,
+                level,
             });
         }
 
         gst::element_error!(
             element,
             gst::StreamError::Format,
-            ["Unsupported video fourcc {:08x}", video_frame.fourcc()]
+            ["Unsupported video fourcc {:08x}", video_frame.fourcc()],
+            details: ErrorCode::UnsupportedFormat.details()
         );
         Err(gst::FlowError::NotNegotiated)
     }
 
+    // Returns the SPS from the codec configuration of the packet, or from the last one if the
+    // packet has none so that the caps don't change between keyframes
+    #[cfg(feature = "advanced-sdk")]
+    fn codec_sps(&self, is_h265: bool, extra_data: Option<&[u8]>) -> Option<Vec<u8>> {
+        match extra_data {
+            Some(extra_data) => sps_nal_unit(is_h265, extra_data),
+            None => self
+                .0
+                .last_extra_data
+                .lock()
+                .unwrap()
+                .as_deref()
+                .and_then(|extra_data| sps_nal_unit(is_h265, extra_data)),
+        }
+    }
+
     fn create_video_buffer(
         &self,
         element: &gst_base::BaseSrc,
@@ -1156,7 +2383,7 @@ impl Receiver {
             buffer.set_duration(duration);
 
             #[cfg(feature = "reference-timestamps")]
-            {
+            if self.0.settings.reference_timestamps {
                 gst::ReferenceTimestampMeta::add(
                     buffer,
                     &*TIMECODE_CAPS,
@@ -1176,6 +2403,8 @@ impl Receiver {
             #[cfg(feature = "interlaced-fields")]
             {
                 match video_frame.frame_format_type() {
+                    ndisys::NDIlib_frame_format_type_e::NDIlib_frame_format_type_interleaved
+                        if self.0.settings.force_progressive => {}
                     ndisys::NDIlib_frame_format_type_e::NDIlib_frame_format_type_interleaved => {
                         buffer.set_video_flags(
                             gst_video::VideoBufferFlags::INTERLACED
@@ -1202,6 +2431,7 @@ impl Receiver {
             {
                 if video_frame.frame_format_type()
                     == ndisys::NDIlib_frame_format_type_e::NDIlib_frame_format_type_interleaved
+                    && !self.0.settings.force_progressive
                 {
                     buffer.set_video_flags(
                         gst_video::VideoBufferFlags::INTERLACED | gst_video::VideoBufferFlags::TFF,
@@ -1215,16 +2445,59 @@ impl Receiver {
 
     fn copy_video_frame(
         &self,
-        #[allow(unused_variables)] element: &gst_base::BaseSrc,
+        element: &gst_base::BaseSrc,
         info: &VideoInfo,
         video_frame: &VideoFrame,
     ) -> Result<gst::Buffer, gst::FlowError> {
         match info {
             VideoInfo::VideoInfo(ref info) => {
-                let src = video_frame.data().ok_or(gst::FlowError::Error)?;
+                // Only returns data if the frame size calculated from the dimensions and stride
+                // is valid, so the copies below stay in bounds
+                let src = video_frame.data().ok_or_else(|| {
+                    gst::element_error!(
+                        element,
+                        gst::StreamError::Format,
+                        [
+                            "Invalid video frame {}x{} with line stride {}",
+                            video_frame.xres(),
+                            video_frame.yres(),
+                            video_frame.line_stride_or_data_size_in_bytes()
+                        ]
+                    );
+
+                    gst::FlowError::Error
+                })?;
+
+                if self.0.settings.preserve_stride {
+                    if let Some(buffer) = self.copy_video_frame_with_stride(info, video_frame, src)
+                    {
+                        return Ok(buffer);
+                    }
+                }
+
+                let buffer = gst::Buffer::with_size(info.size()).map_err(|_| {
+                    gst::element_error!(
+                        element,
+                        gst::ResourceError::NoSpaceLeft,
+                        ["Failed to allocate {} bytes for video frame", info.size()]
+                    );
 
-                let buffer = gst::Buffer::with_size(info.size()).unwrap();
-                let mut vframe = gst_video::VideoFrame::from_buffer_writable(buffer, info).unwrap();
+                    gst::FlowError::Error
+                })?;
+                let mut vframe = gst_video::VideoFrame::from_buffer_writable(buffer, info)
+                    .map_err(|_| {
+                        gst_error!(CAT, obj: element, "Failed to map video buffer");
+                        gst::FlowError::Error
+                    })?;
+
+                // Separate fields only contain every second line of the frame
+                let src_height = match video_frame.frame_format_type() {
+                    ndisys::NDIlib_frame_format_type_e::NDIlib_frame_format_type_field_0
+                    | ndisys::NDIlib_frame_format_type_e::NDIlib_frame_format_type_field_1 => {
+                        (video_frame.yres() as usize + 1) / 2
+                    }
+                    _ => video_frame.yres() as usize,
+                };
 
                 match info.format() {
                     gst_video::VideoFormat::Uyvy
@@ -1245,8 +2518,7 @@ impl Receiver {
                             .chunks_exact_mut(dest_stride)
                             .zip(src.chunks_exact(src_stride))
                         {
-                            dest.copy_from_slice(src);
-                            dest.copy_from_slice(&src[..line_bytes]);
+                            dest[..line_bytes].copy_from_slice(&src[..line_bytes]);
                         }
                     }
                     gst_video::VideoFormat::Nv12 => {
@@ -1262,24 +2534,25 @@ impl Receiver {
                                 .chunks_exact_mut(dest_stride)
                                 .zip(src.chunks_exact(src_stride))
                             {
-                                dest.copy_from_slice(&src[..line_bytes]);
+                                dest[..line_bytes].copy_from_slice(&src[..line_bytes]);
                             }
                         }
 
-                        // Second plane
+                        // Second plane: interleaved UV with half the height, every line
+                        // contains one UV pair per two pixels
                         {
-                            let line_bytes = vframe.width() as usize;
+                            let line_bytes = (vframe.width() as usize + 1) & !1;
                             let dest_stride = vframe.plane_stride()[1] as usize;
                             let dest = vframe.plane_data_mut(1).unwrap();
                             let src_stride =
                                 video_frame.line_stride_or_data_size_in_bytes() as usize;
-                            let src = &src[(video_frame.yres() as usize * src_stride)..];
+                            let src = &src[(src_height * src_stride)..];
 
                             for (dest, src) in dest
                                 .chunks_exact_mut(dest_stride)
                                 .zip(src.chunks_exact(src_stride))
                             {
-                                dest.copy_from_slice(&src[..line_bytes]);
+                                dest[..line_bytes].copy_from_slice(&src[..line_bytes]);
                             }
                         }
                     }
@@ -1296,7 +2569,7 @@ impl Receiver {
                                 .chunks_exact_mut(dest_stride)
                                 .zip(src.chunks_exact(src_stride))
                             {
-                                dest.copy_from_slice(&src[..line_bytes]);
+                                dest[..line_bytes].copy_from_slice(&src[..line_bytes]);
                             }
                         }
 
@@ -1309,13 +2582,13 @@ impl Receiver {
                                 video_frame.line_stride_or_data_size_in_bytes() as usize;
                             let src_stride1 =
                                 video_frame.line_stride_or_data_size_in_bytes() as usize / 2;
-                            let src = &src[(video_frame.yres() as usize * src_stride)..];
+                            let src = &src[(src_height * src_stride)..];
 
                             for (dest, src) in dest
                                 .chunks_exact_mut(dest_stride)
                                 .zip(src.chunks_exact(src_stride1))
                             {
-                                dest.copy_from_slice(&src[..line_bytes]);
+                                dest[..line_bytes].copy_from_slice(&src[..line_bytes]);
                             }
                         }
 
@@ -1328,14 +2601,59 @@ impl Receiver {
                                 video_frame.line_stride_or_data_size_in_bytes() as usize;
                             let src_stride1 =
                                 video_frame.line_stride_or_data_size_in_bytes() as usize / 2;
-                            let src = &src[(video_frame.yres() as usize * src_stride
-                                + (video_frame.yres() as usize + 1) / 2 * src_stride1)..];
+                            let src = &src
+                                [(src_height * src_stride + (src_height + 1) / 2 * src_stride1)..];
 
                             for (dest, src) in dest
                                 .chunks_exact_mut(dest_stride)
                                 .zip(src.chunks_exact(src_stride1))
                             {
-                                dest.copy_from_slice(&src[..line_bytes]);
+                                dest[..line_bytes].copy_from_slice(&src[..line_bytes]);
+                            }
+                        }
+                    }
+                    gst_video::VideoFormat::I42212le => {
+                        let width = vframe.width() as usize;
+                        let src_stride = video_frame.line_stride_or_data_size_in_bytes() as usize;
+
+                        // First plane: 16 bit luma
+                        {
+                            let dest_stride = vframe.plane_stride()[0] as usize;
+                            let dest = vframe.plane_data_mut(0).unwrap();
+
+                            for (dest, src) in dest
+                                .chunks_exact_mut(dest_stride)
+                                .zip(src.chunks_exact(src_stride))
+                            {
+                                for (dest, src) in dest[..2 * width]
+                                    .chunks_exact_mut(2)
+                                    .zip(src[..2 * width].chunks_exact(2))
+                                {
+                                    let y = u16::from_le_bytes([src[0], src[1]]) >> 4;
+                                    dest.copy_from_slice(&y.to_le_bytes());
+                                }
+                            }
+                        }
+
+                        // Second plane of the source: interleaved 16 bit UV with full height,
+                        // the U samples go into the second and the V samples into the third plane
+                        let src = &src[(src_height * src_stride)..];
+                        let chroma_bytes = (width + 1) & !1;
+                        for (plane, offset) in [(1, 0), (2, 2)] {
+                            let dest_stride = vframe.plane_stride()[plane] as usize;
+                            let dest = vframe.plane_data_mut(plane as u32).unwrap();
+
+                            for (dest, src) in dest
+                                .chunks_exact_mut(dest_stride)
+                                .zip(src.chunks_exact(src_stride))
+                            {
+                                for (dest, src) in dest[..chroma_bytes]
+                                    .chunks_exact_mut(2)
+                                    .zip(src[..2 * chroma_bytes].chunks_exact(4))
+                                {
+                                    let c = u16::from_le_bytes([src[offset], src[offset + 1]]) >> 4;
+                                    dest.copy_from_slice(&c.to_le_bytes());
+                                }
                             }
                         }
                     }
@@ -1361,6 +2679,7 @@ impl Receiver {
             }
             #[cfg(feature = "advanced-sdk")]
             VideoInfo::H264Info { .. } | VideoInfo::H265Info { .. } => {
+                let is_h265 = matches!(info, VideoInfo::H265Info { .. });
                 let compressed_packet = video_frame.compressed_packet().ok_or_else(|| {
                     gst_error!(
                         CAT,
@@ -1376,15 +2695,63 @@ impl Receiver {
                     gst::FlowError::Error
                 })?;
 
+                // Whether H.265 pictures are droppable depends on the number of sub-layers
+                let max_sub_layers = if is_h265 {
+                    self.codec_sps(is_h265, compressed_packet.extra_data)
+                        .and_then(|sps| h265_max_sub_layers(&sps))
+                } else {
+                    None
+                };
+                let (key_frame, non_reference) = match classify_compressed_packet(
+                    is_h265,
+                    max_sub_layers,
+                    compressed_packet.data,
+                ) {
+                    Some((key_frame, non_reference)) => (key_frame, non_reference),
+                    // No slices found, trust the flag from the SDK
+                    None => (compressed_packet.key_frame, false),
+                };
+
+                let prepend_extra_data = {
+                    let mut last_extra_data = self.0.last_extra_data.lock().unwrap();
+                    if last_extra_data.is_some()
+                        && compressed_packet.extra_data.is_some()
+                        && last_extra_data.as_deref() != compressed_packet.extra_data
+                    {
+                        if key_frame {
+                            gst_debug!(CAT, obj: element, "Codec configuration changed");
+                        } else {
+                            gst_warning!(
+                                CAT,
+                                obj: element,
+                                "Codec configuration changed on a non-keyframe"
+                            );
+                        }
+                    }
+
+                    update_extra_data(
+                        &mut last_extra_data,
+                        compressed_packet.extra_data,
+                        key_frame,
+                    )
+                };
+
                 let mut buffer = Vec::new();
-                if let Some(extra_data) = compressed_packet.extra_data {
+                if let Some(extra_data) =
+                    compressed_packet.extra_data.filter(|_| prepend_extra_data)
+                {
                     buffer.extend_from_slice(extra_data);
                 }
                 buffer.extend_from_slice(compressed_packet.data);
                 let mut buffer = gst::Buffer::from_mut_slice(buffer);
-                if !compressed_packet.key_frame {
+                {
                     let buffer = buffer.get_mut().unwrap();
-                    buffer.set_flags(gst::BufferFlags::DELTA_UNIT);
+                    if !key_frame {
+                        buffer.set_flags(gst::BufferFlags::DELTA_UNIT);
+                    }
+                    if non_reference {
+                        buffer.set_flags(gst::BufferFlags::DROPPABLE);
+                    }
                 }
 
                 Ok(buffer)
@@ -1392,6 +2759,70 @@ impl Receiver {
         }
     }
 
+    // Copies the frame in one go with the plane layout of the SDK and attaches a video meta
+    // describing its offsets and strides. Returns None for formats that need to be converted
+    // and for separate fields, which are repacked instead.
+    fn copy_video_frame_with_stride(
+        &self,
+        info: &gst_video::VideoInfo,
+        video_frame: &VideoFrame,
+        src: &[u8],
+    ) -> Option<gst::Buffer> {
+        if matches!(
+            video_frame.frame_format_type(),
+            ndisys::NDIlib_frame_format_type_e::NDIlib_frame_format_type_field_0
+                | ndisys::NDIlib_frame_format_type_e::NDIlib_frame_format_type_field_1
+        ) {
+            return None;
+        }
+
+        let height = video_frame.yres() as usize;
+        let chroma_height = (height + 1) / 2;
+        let stride = video_frame.line_stride_or_data_size_in_bytes() as usize;
+        let (offsets, strides, size) = match info.format() {
+            gst_video::VideoFormat::Uyvy
+            | gst_video::VideoFormat::Bgra
+            | gst_video::VideoFormat::Bgrx
+            | gst_video::VideoFormat::Rgba
+            | gst_video::VideoFormat::Rgbx => (vec![0], vec![stride], height * stride),
+            // Interleaved UV plane with half the height and the same stride
+            gst_video::VideoFormat::Nv12 => (
+                vec![0, height * stride],
+                vec![stride, stride],
+                height * stride + chroma_height * stride,
+            ),
+            // Two chroma planes with half the height and half the stride
+            gst_video::VideoFormat::Yv12 | gst_video::VideoFormat::I420 => {
+                let chroma_size = chroma_height * (stride / 2);
+                (
+                    vec![0, height * stride, height * stride + chroma_size],
+                    vec![stride, stride / 2, stride / 2],
+                    height * stride + 2 * chroma_size,
+                )
+            }
+            _ => return None,
+        };
+
+        if src.len() < size {
+            return None;
+        }
+
+        let mut buffer = gst::Buffer::from_mut_slice(Vec::from(&src[..size]));
+        let strides = strides.into_iter().map(|s| s as i32).collect::<Vec<_>>();
+        gst_video::VideoMeta::add_full(
+            buffer.get_mut().unwrap(),
+            gst_video::VideoFrameFlags::empty(),
+            info.format(),
+            info.width(),
+            info.height(),
+            &offsets,
+            &strides,
+        )
+        .ok()?;
+
+        Some(buffer)
+    }
+
     fn create_audio_buffer_and_info(
         &self,
         element: &gst_base::BaseSrc,
@@ -1415,12 +2846,86 @@ impl Receiver {
                 .unwrap()
                 .set_flags(gst::BufferFlags::RESYNC);
         }
+        if self.0.settings.timing_meta {
+            crate::nditimingmeta::NdiTimingMeta::add(
+                buffer.get_mut().unwrap(),
+                audio_frame.timecode(),
+                audio_frame.timestamp(),
+            );
+        }
+
+        if self.0.settings.fill_audio_gaps {
+            self.fill_audio_gap(element, &info, &mut buffer);
+        }
 
         gst_log!(CAT, obj: element, "Produced audio buffer {:?}", buffer);
 
         Ok(Buffer::Audio(buffer, info))
     }
 
+    // Queues silence buffers of the frame duration if there is a gap of at least one audio frame
+    // between the end of the previous audio buffer and the given buffer
+    fn fill_audio_gap(
+        &self,
+        element: &gst_base::BaseSrc,
+        info: &AudioInfo,
+        buffer: &mut gst::Buffer,
+    ) {
+        let (pts, duration) = match buffer.pts() {
+            Some(pts) => (pts, buffer.duration()),
+            None => return,
+        };
+
+        let mut next_audio_pts = self.0.next_audio_pts.lock().unwrap();
+        let expected_pts = next_audio_pts.replace(pts + duration.unwrap_or(gst::ClockTime::ZERO));
+
+        let (info, expected_pts, duration) = match (info, expected_pts, duration) {
+            (AudioInfo::AudioInfo(ref info), Some(expected_pts), Some(duration)) => {
+                (info, expected_pts, duration)
+            }
+            _ => return,
+        };
+
+        if pts < expected_pts + duration {
+            return;
+        }
+        drop(next_audio_pts);
+
+        if (pts - expected_pts).nseconds() > MAX_AUDIO_GAP {
+            gst_debug!(
+                CAT,
+                obj: element,
+                "Audio gap from {} to {} too long, marking discontinuity",
+                expected_pts,
+                pts
+            );
+            buffer.make_mut().set_flags(gst::BufferFlags::DISCONT);
+            return;
+        }
+
+        gst_debug!(
+            CAT,
+            obj: element,
+            "Audio gap from {} to {}, inserting silence",
+            expected_pts,
+            pts
+        );
+
+        let mut silence_pts = expected_pts;
+        while silence_pts < pts {
+            let silence_duration = cmp::min(duration, pts - silence_pts);
+            let buffer = match create_silence_buffer(info, silence_pts, silence_duration) {
+                Some(buffer) => buffer,
+                None => break,
+            };
+            self.queue_buffer(
+                element,
+                Buffer::Audio(buffer, AudioInfo::AudioInfo(info.clone())),
+            );
+            silence_pts += silence_duration;
+        }
+    }
+
     fn calculate_audio_timestamp(
         &self,
         element: &gst_base::BaseSrc,
@@ -1448,7 +2953,7 @@ impl Receiver {
 
         if [NDIlib_FourCC_audio_type_FLTp].contains(&fourcc) {
             let builder = gst_audio::AudioInfo::builder(
-                gst_audio::AUDIO_FORMAT_F32,
+                self.0.settings.audio_format,
                 audio_frame.sample_rate() as u32,
                 audio_frame.no_channels() as u32,
             );
@@ -1488,14 +2993,27 @@ impl Receiver {
                 return Err(gst::FlowError::Error);
             }
 
+            let codec_data: [u8; 2] = compressed_packet
+                .extra_data
+                .ok_or(gst::FlowError::NotNegotiated)?
+                .try_into()
+                .map_err(|_| gst::FlowError::NotNegotiated)?;
+
+            if self.0.settings.aac_adts && adts_header(&codec_data, 0).is_none() {
+                gst::element_error!(
+                    element,
+                    gst::StreamError::Format,
+                    ["AAC configuration can't be represented with ADTS headers"]
+                );
+
+                return Err(gst::FlowError::NotNegotiated);
+            }
+
             return Ok(AudioInfo::AacInfo {
                 sample_rate: audio_frame.sample_rate(),
                 no_channels: audio_frame.no_channels(),
-                codec_data: compressed_packet
-                    .extra_data
-                    .ok_or(gst::FlowError::NotNegotiated)?
-                    .try_into()
-                    .map_err(|_| gst::FlowError::NotNegotiated)?,
+                codec_data,
+                adts: self.0.settings.aac_adts,
             });
         }
 
@@ -1505,14 +3023,15 @@ impl Receiver {
         gst::element_error!(
             element,
             gst::StreamError::Format,
-            ["Unsupported audio fourcc {:08x}", audio_frame.fourcc()]
+            ["Unsupported audio fourcc {:08x}", audio_frame.fourcc()],
+            details: ErrorCode::UnsupportedFormat.details()
         );
         Err(gst::FlowError::NotNegotiated)
     }
 
     fn create_audio_buffer(
         &self,
-        #[allow(unused_variables)] element: &gst_base::BaseSrc,
+        element: &gst_base::BaseSrc,
         pts: gst::ClockTime,
         duration: Option<gst::ClockTime>,
         info: &AudioInfo,
@@ -1520,10 +3039,58 @@ impl Receiver {
     ) -> Result<gst::Buffer, gst::FlowError> {
         match info {
             AudioInfo::AudioInfo(ref info) => {
-                let src = audio_frame.data().ok_or(gst::FlowError::Error)?;
-                let buff_size = (audio_frame.no_samples() as u32 * info.bpf()) as usize;
+                let src = audio_frame.data().ok_or_else(|| {
+                    gst::element_error!(
+                        element,
+                        gst::StreamError::Format,
+                        [
+                            "Invalid audio frame with {} channels and channel stride {}",
+                            audio_frame.no_channels(),
+                            audio_frame.channel_stride_or_data_size_in_bytes()
+                        ]
+                    );
+
+                    gst::FlowError::Error
+                })?;
+                // Each channel is a plane of no_samples floats, followed by padding if the
+                // channel stride is larger than that
+                let no_samples = audio_frame.no_samples().max(0) as usize;
+                let no_channels = audio_frame.no_channels() as usize;
+                let channel_stride = audio_frame.channel_stride_or_data_size_in_bytes() as usize;
+                let buff_size = match (
+                    no_samples.checked_mul(info.bpf() as usize),
+                    no_samples.checked_mul(mem::size_of::<f32>()),
+                ) {
+                    (Some(buff_size), Some(min_stride)) if channel_stride >= min_stride => {
+                        buff_size
+                    }
+                    _ => {
+                        gst_error!(
+                            CAT,
+                            obj: element,
+                            "Audio channel stride {} too small for {} samples",
+                            channel_stride,
+                            no_samples,
+                        );
+                        gst::element_error!(
+                            element,
+                            gst::StreamError::Format,
+                            ["Invalid audio frame"]
+                        );
+
+                        return Err(gst::FlowError::Error);
+                    }
+                };
+
+                let mut buffer = gst::Buffer::with_size(buff_size).map_err(|_| {
+                    gst::element_error!(
+                        element,
+                        gst::ResourceError::NoSpaceLeft,
+                        ["Failed to allocate {} bytes for audio frame", buff_size]
+                    );
 
-                let mut buffer = gst::Buffer::with_size(buff_size).unwrap();
+                    gst::FlowError::Error
+                })?;
                 {
                     let buffer = buffer.get_mut().unwrap();
 
@@ -1531,7 +3098,7 @@ impl Receiver {
                     buffer.set_duration(duration);
 
                     #[cfg(feature = "reference-timestamps")]
-                    {
+                    if self.0.settings.reference_timestamps {
                         gst::ReferenceTimestampMeta::add(
                             buffer,
                             &*TIMECODE_CAPS,
@@ -1548,29 +3115,41 @@ impl Receiver {
                         }
                     }
 
-                    let mut dest = buffer.map_writable().unwrap();
-                    let dest = dest
-                        .as_mut_slice_of::<f32>()
-                        .map_err(|_| gst::FlowError::NotNegotiated)?;
-                    assert!(
-                        dest.len()
-                            == audio_frame.no_samples() as usize
-                                * audio_frame.no_channels() as usize
-                    );
+                    // Only the first no_samples floats of every channel are used, the padding is
+                    // skipped. The channels are not necessarily aligned to floats if the stride
+                    // is not a multiple of 4 bytes.
+                    let channel_samples = |channel: usize| {
+                        src[(channel * channel_stride)..][..(no_samples * mem::size_of::<f32>())]
+                            .chunks_exact(mem::size_of::<f32>())
+                            .map(|sample| f32::from_ne_bytes(sample.try_into().unwrap()))
+                    };
 
-                    for (channel, samples) in src
-                        .chunks_exact(audio_frame.channel_stride_or_data_size_in_bytes() as usize)
-                        .enumerate()
-                    {
-                        let samples = samples
-                            .as_slice_of::<f32>()
+                    let mut dest = buffer.map_writable().unwrap();
+                    if info.format() == gst_audio::AUDIO_FORMAT_S16 {
+                        let dest = dest
+                            .as_mut_slice_of::<i16>()
+                            .map_err(|_| gst::FlowError::NotNegotiated)?;
+                        assert!(dest.len() == no_samples * no_channels);
+
+                        // Inverse of the conversion in ndisink with the same reference level
+                        let scale =
+                            32768.0 / 10.0f32.powf(self.0.settings.reference_level as f32 / 20.0);
+                        for channel in 0..no_channels {
+                            for (i, sample) in channel_samples(channel).enumerate() {
+                                dest[i * no_channels + channel] =
+                                    (sample * scale).round().clamp(-32768.0, 32767.0) as i16;
+                            }
+                        }
+                    } else {
+                        let dest = dest
+                            .as_mut_slice_of::<f32>()
                             .map_err(|_| gst::FlowError::NotNegotiated)?;
+                        assert!(dest.len() == no_samples * no_channels);
 
-                        for (i, sample) in samples[..audio_frame.no_samples() as usize]
-                            .iter()
-                            .enumerate()
-                        {
-                            dest[i * (audio_frame.no_channels() as usize) + channel] = *sample;
+                        for channel in 0..no_channels {
+                            for (i, sample) in channel_samples(channel).enumerate() {
+                                dest[i * no_channels + channel] = sample;
+                            }
                         }
                     }
                 }
@@ -1593,7 +3172,9 @@ impl Receiver {
                 Ok(gst::Buffer::from_mut_slice(Vec::from(data)))
             }
             #[cfg(feature = "advanced-sdk")]
-            AudioInfo::AacInfo { .. } => {
+            AudioInfo::AacInfo {
+                codec_data, adts, ..
+            } => {
                 let compressed_packet = audio_frame.compressed_packet().ok_or_else(|| {
                     gst_error!(
                         CAT,
@@ -1609,10 +3190,1746 @@ impl Receiver {
                     gst::FlowError::Error
                 })?;
 
-                Ok(gst::Buffer::from_mut_slice(Vec::from(
-                    compressed_packet.data,
-                )))
+                if !adts {
+                    return Ok(gst::Buffer::from_mut_slice(Vec::from(
+                        compressed_packet.data,
+                    )));
+                }
+
+                let header =
+                    adts_header(codec_data, compressed_packet.data.len()).ok_or_else(|| {
+                        gst::element_error!(
+                            element,
+                            gst::StreamError::Format,
+                            ["AAC frame too large for ADTS"]
+                        );
+
+                        gst::FlowError::Error
+                    })?;
+                let mut buffer = Vec::with_capacity(header.len() + compressed_packet.data.len());
+                buffer.extend_from_slice(&header);
+                buffer.extend_from_slice(compressed_packet.data);
+
+                Ok(gst::Buffer::from_mut_slice(buffer))
+            }
+        }
+    }
+}
+
+// Variant without alpha and with the same memory layout of a video format with alpha
+fn opaque_video_format(format: gst_video::VideoFormat) -> Option<gst_video::VideoFormat> {
+    match format {
+        gst_video::VideoFormat::Bgra => Some(gst_video::VideoFormat::Bgrx),
+        gst_video::VideoFormat::Rgba => Some(gst_video::VideoFormat::Rgbx),
+        _ => None,
+    }
+}
+
+// Whether the metadata is a KVM message, i.e. its root element is <ndi_kvm>
+#[cfg(feature = "kvm")]
+fn is_kvm_metadata(metadata: &str) -> bool {
+    let next = metadata
+        .trim_start()
+        .strip_prefix("<ndi_kvm")
+        .and_then(|rest| rest.chars().next());
+
+    matches!(next, Some(c) if c.is_whitespace() || c == '/' || c == '>')
+}
+
+// Parses the product information a source sends as part of its connection metadata, e.g.
+// <ndi_product long_name="..." short_name="..." manufacturer="..." version="..."
+//              model_name="..." serial="..." session="..."/>
+fn parse_source_info(metadata: &str) -> Option<gst::Structure> {
+    let mut s = gst::Structure::new_empty("ndi-source-info");
+    for (name, value) in parse_xml_attributes(metadata, "ndi_product")? {
+        let field = match name {
+            "long_name" => Some("product-name"),
+            "short_name" => Some("short-name"),
+            "manufacturer" => Some("manufacturer"),
+            "model_name" => Some("model"),
+            "version" => Some("version"),
+            "serial" => Some("serial"),
+            _ => None,
+        };
+        if let Some(field) = field {
+            s.set(field, value);
+        }
+    }
+
+    Some(s)
+}
+
+// Parses the colorimetry an HDR source signals in the frame metadata, e.g.
+// <ndi_color_info transfer="bt_2100_hlg" matrix="bt_2020" primaries="bt_2020"/>
+#[cfg(feature = "advanced-sdk")]
+fn parse_colorimetry(metadata: &str) -> Option<gst_video::VideoColorimetry> {
+    let mut transfer = gst_video::VideoTransferFunction::Bt709;
+    let mut matrix = gst_video::VideoColorMatrix::Bt709;
+    let mut primaries = gst_video::VideoColorPrimaries::Bt709;
+
+    for (name, value) in parse_xml_attributes(metadata, "ndi_color_info")? {
+        match (name, value) {
+            ("transfer", "bt_709") => transfer = gst_video::VideoTransferFunction::Bt709,
+            ("transfer", "bt_2020") => transfer = gst_video::VideoTransferFunction::Bt202010,
+            ("transfer", "bt_2100_hlg") => transfer = gst_video::VideoTransferFunction::AribStdB67,
+            ("transfer", "bt_2100_pq") => transfer = gst_video::VideoTransferFunction::Smpte2084,
+            ("matrix", "bt_601") => matrix = gst_video::VideoColorMatrix::Bt601,
+            ("matrix", "bt_709") => matrix = gst_video::VideoColorMatrix::Bt709,
+            ("matrix", "bt_2020") | ("matrix", "bt_2100") => {
+                matrix = gst_video::VideoColorMatrix::Bt2020
+            }
+            ("primaries", "bt_601") => primaries = gst_video::VideoColorPrimaries::Smpte170m,
+            ("primaries", "bt_709") => primaries = gst_video::VideoColorPrimaries::Bt709,
+            ("primaries", "bt_2020") | ("primaries", "bt_2100") => {
+                primaries = gst_video::VideoColorPrimaries::Bt2020
+            }
+            _ => (),
+        }
+    }
+
+    Some(gst_video::VideoColorimetry::new(
+        gst_video::VideoColorRange::Range16_235,
+        matrix,
+        transfer,
+        primaries,
+    ))
+}
+
+// Returns the attributes of the first XML element with the given name in the metadata
+pub(crate) fn parse_xml_attributes<'a>(
+    metadata: &'a str,
+    element: &str,
+) -> Option<Vec<(&'a str, &'a str)>> {
+    let tag = format!("<{}", element);
+    let start = metadata.find(&tag)? + tag.len();
+    let end = start + metadata[start..].find('>')?;
+    let mut attributes = metadata[start..end].trim_end_matches('/');
+
+    let mut res = Vec::new();
+    while let Some(eq) = attributes.find('=') {
+        let name = attributes[..eq].trim();
+        let value = attributes[eq + 1..].trim_start();
+        let quote = value.chars().next()?;
+        if quote != '"' && quote != '\'' {
+            return None;
+        }
+        let value = &value[1..];
+        let value_end = value.find(quote)?;
+
+        res.push((name, &value[..value_end]));
+        attributes = &value[value_end + 1..];
+    }
+
+    Some(res)
+}
+
+// Returns None if the duration is shorter than a single sample
+fn create_silence_buffer(
+    info: &gst_audio::AudioInfo,
+    pts: gst::ClockTime,
+    duration: gst::ClockTime,
+) -> Option<gst::Buffer> {
+    let no_samples = duration
+        .mul_div_floor(info.rate() as u64, gst::ClockTime::SECOND.nseconds())?
+        .nseconds();
+    if no_samples == 0 {
+        return None;
+    }
+
+    // All zeroes is silence for F32 and S16 audio
+    let mut buffer =
+        gst::Buffer::from_mut_slice(vec![0u8; no_samples as usize * info.bpf() as usize]);
+    {
+        let buffer = buffer.get_mut().unwrap();
+        buffer.set_pts(pts);
+        buffer.set_duration(duration);
+        buffer.set_flags(gst::BufferFlags::GAP);
+    }
+
+    Some(buffer)
+}
+
+// Returns the first two bytes of the header of every NAL unit in an Annex B byte-stream, the
+// second one is 0 if the data ends after the first
+#[cfg(feature = "advanced-sdk")]
+fn nal_unit_headers(data: &[u8]) -> impl Iterator<Item = (u8, u8)> + '_ {
+    data.windows(3)
+        .enumerate()
+        .filter(|(_, start_code)| *start_code == [0, 0, 1])
+        .filter_map(move |(idx, _)| {
+            let header = data.get(idx + 3)?;
+            Some((*header, data.get(idx + 4).copied().unwrap_or(0)))
+        })
+}
+
+// Checks the slices of a compressed H.264/H.265 access unit and returns if it is a keyframe
+// (IDR/IRAP) and if none of the slices is used as reference by other frames, or None if the
+// access unit contains no slices at all.
+//
+// H.265 sub-layer non-reference pictures can still be referenced by pictures of higher
+// sub-layers, so they are only considered non-reference in the highest sub-layer, which requires
+// the number of sub-layers from the SPS.
+#[cfg(feature = "advanced-sdk")]
+fn classify_compressed_packet(
+    is_h265: bool,
+    max_sub_layers: Option<u8>,
+    data: &[u8],
+) -> Option<(bool, bool)> {
+    let mut have_slices = false;
+    let mut key_frame = false;
+    let mut non_reference = true;
+
+    for (header, header2) in nal_unit_headers(data) {
+        if is_h265 {
+            let nal_type = (header >> 1) & 0x3f;
+            // VCL NAL units
+            if nal_type <= 31 {
+                have_slices = true;
+                // BLA, IDR and CRA
+                key_frame |= (16..=23).contains(&nal_type);
+                // Sub-layer non-reference pictures have even types up to 14
+                let temporal_id_plus1 = header2 & 0x7;
+                non_reference &= nal_type <= 14
+                    && nal_type % 2 == 0
+                    && Some(temporal_id_plus1) == max_sub_layers;
+            }
+        } else {
+            let nal_type = header & 0x1f;
+            // Non-IDR and IDR slices
+            if nal_type == 1 || nal_type == 5 {
+                have_slices = true;
+                key_frame |= nal_type == 5;
+                non_reference &= (header >> 5) & 0x3 == 0;
             }
         }
     }
+
+    if have_slices {
+        Some((key_frame, non_reference))
+    } else {
+        None
+    }
+}
+
+// Remembers the codec configuration of a packet and returns if it has to be prepended to the
+// packet, which is only the case for keyframes and whenever it changes
+#[cfg(feature = "advanced-sdk")]
+fn update_extra_data(
+    last_extra_data: &mut Option<Vec<u8>>,
+    extra_data: Option<&[u8]>,
+    key_frame: bool,
+) -> bool {
+    let extra_data = match extra_data {
+        Some(extra_data) => extra_data,
+        None => return false,
+    };
+
+    if last_extra_data.as_deref() == Some(extra_data) {
+        return key_frame;
+    }
+
+    *last_extra_data = Some(Vec::from(extra_data));
+    true
+}
+
+// Returns the first SPS NAL unit of an H.264/H.265 byte-stream, including its header and with
+// the emulation prevention bytes removed
+#[cfg(feature = "advanced-sdk")]
+fn sps_nal_unit(is_h265: bool, data: &[u8]) -> Option<Vec<u8>> {
+    let starts = data
+        .windows(3)
+        .enumerate()
+        .filter(|(_, start_code)| *start_code == [0, 0, 1])
+        .map(|(idx, _)| idx + 3)
+        .collect::<Vec<_>>();
+
+    for (n, &start) in starts.iter().enumerate() {
+        let header = *data.get(start)?;
+        let is_sps = if is_h265 {
+            (header >> 1) & 0x3f == 33
+        } else {
+            header & 0x1f == 7
+        };
+        if !is_sps {
+            continue;
+        }
+
+        let end = starts.get(n + 1).map_or(data.len(), |next| next - 3);
+        let mut nal = Vec::with_capacity(end - start);
+        let mut zeros = 0;
+        for &byte in &data[start..end] {
+            if zeros >= 2 && byte == 0x03 {
+                zeros = 0;
+                continue;
+            }
+            zeros = if byte == 0 { zeros + 1 } else { 0 };
+            nal.push(byte);
+        }
+
+        return Some(nal);
+    }
+
+    None
+}
+
+// Returns the maximum number of temporal sub-layers of an H.265 SPS
+#[cfg(feature = "advanced-sdk")]
+fn h265_max_sub_layers(sps: &[u8]) -> Option<u8> {
+    // The NAL header is followed by the 4 bit VPS id and the 3 bit sps_max_sub_layers_minus1
+    Some(((sps.get(2)? >> 1) & 0x7) + 1)
+}
+
+// Returns the caps profile and level of an H.264 SPS
+#[cfg(feature = "advanced-sdk")]
+fn h264_profile_level(sps: &[u8]) -> Option<(&'static str, String)> {
+    // The NAL header is followed by profile_idc, the constraint set flags and level_idc
+    let profile_idc = *sps.get(1)?;
+    let constraint_set1 = sps.get(2)? & 0x40 != 0;
+    let constraint_set3 = sps.get(2)? & 0x10 != 0;
+    let level_idc = *sps.get(3)?;
+
+    let profile = match profile_idc {
+        66 if constraint_set1 => "constrained-baseline",
+        66 => "baseline",
+        77 => "main",
+        88 => "extended",
+        100 => "high",
+        110 if constraint_set3 => "high-10-intra",
+        110 => "high-10",
+        122 if constraint_set3 => "high-4:2:2-intra",
+        122 => "high-4:2:2",
+        244 if constraint_set3 => "high-4:4:4-intra",
+        244 => "high-4:4:4",
+        44 => "cavlc-4:4:4-intra",
+        _ => return None,
+    };
+
+    let level = match level_idc {
+        9 => String::from("1b"),
+        11 if constraint_set3 && matches!(profile_idc, 66 | 77 | 88) => String::from("1b"),
+        _ if level_idc % 10 == 0 => format!("{}", level_idc / 10),
+        _ => format!("{}.{}", level_idc / 10, level_idc % 10),
+    };
+
+    Some((profile, level))
+}
+
+// Returns the caps profile, tier and level of an H.265 SPS. The profile is None for profiles
+// that can only be distinguished by their constraint flags, e.g. the range extensions
+#[cfg(feature = "advanced-sdk")]
+fn h265_profile_tier_level(sps: &[u8]) -> Option<(Option<&'static str>, &'static str, String)> {
+    // The two byte NAL header and the VPS id / sub-layer byte are followed by the general
+    // profile space, tier and profile_idc, 32 compatibility flags, 48 constraint flags and
+    // general_level_idc
+    let profile_tier = *sps.get(3)?;
+    let level_idc = *sps.get(14)?;
+
+    let profile = match profile_tier & 0x1f {
+        1 => Some("main"),
+        2 => Some("main-10"),
+        3 => Some("main-still-picture"),
+        _ => None,
+    };
+    let tier = if profile_tier & 0x20 != 0 {
+        "high"
+    } else {
+        "main"
+    };
+    let level = if level_idc % 30 == 0 {
+        format!("{}", level_idc / 30)
+    } else {
+        format!("{}.{}", level_idc / 30, (level_idc % 30) / 3)
+    };
+
+    Some((profile, tier, level))
+}
+
+// Builds an ADTS header for an AAC frame of the given size from the AudioSpecificConfig, or
+// returns None if the configuration can't be represented in an ADTS header
+#[cfg(feature = "advanced-sdk")]
+fn adts_header(codec_data: &[u8; 2], frame_size: usize) -> Option<[u8; 7]> {
+    let object_type = codec_data[0] >> 3;
+    let frequency_index = ((codec_data[0] & 0x07) << 1) | (codec_data[1] >> 7);
+    let channel_config = (codec_data[1] >> 3) & 0x0f;
+
+    // ADTS only supports the first four object types, the explicit sample rates of the
+    // frequency table and 13 bits for the frame length including the header
+    let frame_length = frame_size + 7;
+    if !(1..=4).contains(&object_type)
+        || frequency_index > 12
+        || channel_config > 7
+        || frame_length > 0x1fff
+    {
+        return None;
+    }
+
+    Some([
+        0xff,
+        // MPEG-4, layer 0, no CRC
+        0xf1,
+        ((object_type - 1) << 6) | (frequency_index << 2) | (channel_config >> 2),
+        ((channel_config & 0x03) << 6) | (frame_length >> 11) as u8,
+        (frame_length >> 3) as u8,
+        ((frame_length & 0x07) << 5) as u8 | 0x1f,
+        // Buffer fullness 0x7ff for VBR, one raw data block
+        0xfc,
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::init;
+
+    use std::ptr;
+
+    enum FakeFrame {
+        Video(NDIlib_video_frame_v2_t, Vec<u8>),
+        Audio(NDIlib_audio_frame_v3_t, Vec<f32>),
+    }
+
+    impl FakeFrame {
+        // Progressive 30fps frame with square pixels and the given line stride
+        fn video(
+            fourcc: NDIlib_FourCC_video_type_e,
+            xres: i32,
+            yres: i32,
+            line_stride: i32,
+            data: Vec<u8>,
+        ) -> Self {
+            FakeFrame::Video(
+                NDIlib_video_frame_v2_t {
+                    xres,
+                    yres,
+                    FourCC: fourcc,
+                    frame_rate_N: 30,
+                    frame_rate_D: 1,
+                    picture_aspect_ratio: xres as f32 / yres as f32,
+                    frame_format_type:
+                        NDIlib_frame_format_type_e::NDIlib_frame_format_type_progressive,
+                    timecode: 0,
+                    p_data: ptr::null(),
+                    line_stride_or_data_size_in_bytes: line_stride,
+                    p_metadata: ptr::null(),
+                    timestamp: NDIlib_recv_timestamp_undefined,
+                },
+                data,
+            )
+        }
+
+        // 48kHz planar float frame with the given channel stride in samples
+        fn audio(no_channels: i32, no_samples: i32, channel_stride: i32, data: Vec<f32>) -> Self {
+            FakeFrame::Audio(
+                NDIlib_audio_frame_v3_t {
+                    sample_rate: 48_000,
+                    no_channels,
+                    no_samples,
+                    timecode: 0,
+                    FourCC: NDIlib_FourCC_audio_type_FLTp,
+                    p_data: ptr::null(),
+                    channel_stride_or_data_size_in_bytes: channel_stride
+                        * mem::size_of::<f32>() as i32,
+                    p_metadata: ptr::null(),
+                    timestamp: NDIlib_recv_timestamp_undefined,
+                },
+                data,
+            )
+        }
+
+        fn with_video(mut self, f: impl FnOnce(&mut NDIlib_video_frame_v2_t)) -> Self {
+            match self {
+                FakeFrame::Video(ref mut frame, _) => f(frame),
+                _ => unreachable!(),
+            }
+            self
+        }
+
+        fn into_frame(self) -> Frame<'static> {
+            match self {
+                FakeFrame::Video(mut frame, data) => {
+                    frame.p_data = data.as_ptr() as *const _;
+                    Frame::Video(VideoFrame::Owned(frame, data))
+                }
+                FakeFrame::Audio(mut frame, data) => {
+                    frame.p_data = data.as_ptr();
+                    Frame::Audio(AudioFrame::Owned(frame, None, Some(data)))
+                }
+            }
+        }
+
+        fn into_video_frame(self) -> VideoFrame<'static> {
+            match self.into_frame() {
+                Frame::Video(frame) => frame,
+                _ => unreachable!(),
+            }
+        }
+
+        fn into_audio_frame(self) -> AudioFrame<'static> {
+            match self.into_frame() {
+                Frame::Audio(frame) => frame,
+                _ => unreachable!(),
+            }
+        }
+    }
+
+    // Replays canned frames and afterwards behaves like a source that doesn't send anything
+    struct FakeSource(Mutex<VecDeque<FakeFrame>>);
+
+    // The frames only point into their own data
+    unsafe impl Send for FakeSource {}
+
+    impl FrameSource for FakeSource {
+        fn capture(&self, timeout_in_ms: u32) -> Result<Option<Frame<'_>>, ()> {
+            match self.0.lock().unwrap().pop_front() {
+                Some(frame) => Ok(Some(frame.into_frame())),
+                None => {
+                    thread::sleep(time::Duration::from_millis(timeout_in_ms as u64));
+                    Ok(None)
+                }
+            }
+        }
+
+        fn get_queue(&self) -> Queue {
+            Queue::default()
+        }
+
+        fn ptz_is_supported(&self) -> bool {
+            false
+        }
+
+        fn web_control(&self) -> Option<String> {
+            None
+        }
+    }
+
+    fn element() -> gst_base::BaseSrc {
+        init();
+
+        let element = glib::Object::new::<crate::ndisrc::NdiSrc>(&[])
+            .unwrap()
+            .upcast::<gst_base::BaseSrc>();
+        let clock = gst::SystemClock::obtain();
+        element.set_base_time(clock.time().unwrap());
+        element.set_clock(Some(&clock)).unwrap();
+        element
+    }
+
+    // Same as the ndisrc defaults
+    fn settings() -> ReceiverSettings {
+        ReceiverSettings {
+            receiver_ndi_name: String::from("test"),
+            bandwidth: NDIlib_recv_bandwidth_highest,
+            color_format: NDIlib_recv_color_format_UYVY_BGRA,
+            allow_video_fields: true,
+            force_progressive: false,
+            hwaccel: false,
+            timestamp_mode: TimestampMode::ReceiveTimeTimecode,
+            reference_timestamps: false,
+            timing_meta: false,
+            freeze_on_loss: false,
+            fill_audio_gaps: false,
+            capture_retries: 5,
+            connect_retries: 0,
+            connect_retry_interval: 1000,
+            aac_adts: false,
+            strict_metadata: false,
+            colorimetry: None,
+            video_formats: None,
+            preserve_stride: false,
+            ignore_alpha: false,
+            audio_format: gst_audio::AUDIO_FORMAT_F32,
+            reference_level: 0,
+            timeout: 5000,
+            frame_timeout: 0,
+            connect_timeout: 10000,
+            max_queue_length: 10,
+        }
+    }
+
+    fn receiver(
+        element: &gst_base::BaseSrc,
+        settings: &ReceiverSettings,
+        frames: Vec<FakeFrame>,
+    ) -> Receiver {
+        let receiver = Receiver::new(
+            FakeSource(Mutex::new(frames.into())),
+            Some("Fake Source"),
+            None,
+            settings,
+            None,
+            element,
+        );
+        receiver.set_playing(true);
+        receiver
+    }
+
+    #[allow(irrefutable_let_patterns)]
+    fn raw_video_info(info: VideoInfo) -> gst_video::VideoInfo {
+        if let VideoInfo::VideoInfo(info) = info {
+            info
+        } else {
+            panic!("Expected raw video info, got {:?}", info);
+        }
+    }
+
+    #[test]
+    fn create_video_info_uyvy() {
+        let element = element();
+        let receiver = receiver(&element, &settings(), vec![]);
+
+        let frame = FakeFrame::video(
+            NDIlib_FourCC_video_type_UYVY,
+            1920,
+            1080,
+            3840,
+            vec![0; 3840 * 1080],
+        )
+        .into_video_frame();
+        let info = raw_video_info(receiver.create_video_info(&element, &frame).unwrap());
+
+        assert_eq!(info.format(), gst_video::VideoFormat::Uyvy);
+        assert_eq!((info.width(), info.height()), (1920, 1080));
+        assert_eq!(info.fps(), gst::Fraction::new(30, 1));
+        assert_eq!(info.par(), gst::Fraction::new(1, 1));
+        assert_eq!(
+            info.interlace_mode(),
+            gst_video::VideoInterlaceMode::Progressive
+        );
+        assert_eq!(info.colorimetry(), "bt709".parse().unwrap());
+    }
+
+    #[test]
+    fn create_video_info_bgra() {
+        let element = element();
+        let receiver = receiver(&element, &settings(), vec![]);
+
+        let frame = FakeFrame::video(
+            NDIlib_FourCC_video_type_BGRA,
+            640,
+            480,
+            2560,
+            vec![0; 2560 * 480],
+        )
+        .into_video_frame();
+        let info = raw_video_info(receiver.create_video_info(&element, &frame).unwrap());
+
+        assert_eq!(info.format(), gst_video::VideoFormat::Bgra);
+        assert_eq!((info.width(), info.height()), (640, 480));
+    }
+
+    // Format of the video info created for a 16x16 frame of the given FourCC
+    fn video_format(
+        settings: &ReceiverSettings,
+        fourcc: NDIlib_FourCC_video_type_e,
+    ) -> Option<gst_video::VideoFormat> {
+        let element = element();
+        let receiver = receiver(&element, settings, vec![]);
+
+        let frame = FakeFrame::video(fourcc, 16, 16, 64, vec![0; 64 * 16]).into_video_frame();
+        receiver
+            .create_video_info(&element, &frame)
+            .ok()
+            .map(|info| raw_video_info(info).format())
+    }
+
+    #[test]
+    fn create_video_info_ignore_alpha() {
+        use gst_video::VideoFormat;
+
+        let ignore_alpha = ReceiverSettings {
+            ignore_alpha: true,
+            ..settings()
+        };
+
+        assert_eq!(
+            video_format(&ignore_alpha, NDIlib_FourCC_video_type_BGRA),
+            Some(VideoFormat::Bgrx)
+        );
+        assert_eq!(
+            video_format(&ignore_alpha, NDIlib_FourCC_video_type_RGBA),
+            Some(VideoFormat::Rgbx)
+        );
+        // Formats without alpha are not affected
+        assert_eq!(
+            video_format(&ignore_alpha, NDIlib_FourCC_video_type_BGRX),
+            Some(VideoFormat::Bgrx)
+        );
+        // Without ignore-alpha the alpha is kept
+        assert_eq!(
+            video_format(&settings(), NDIlib_FourCC_video_type_BGRA),
+            Some(VideoFormat::Bgra)
+        );
+    }
+
+    #[test]
+    fn create_video_info_opaque_video_caps() {
+        use gst_video::VideoFormat;
+
+        // Only the variant without alpha is allowed by video-caps
+        let opaque_only = ReceiverSettings {
+            video_formats: Some(vec![VideoFormat::Bgrx, VideoFormat::Rgbx]),
+            ..settings()
+        };
+        assert_eq!(
+            video_format(&opaque_only, NDIlib_FourCC_video_type_BGRA),
+            Some(VideoFormat::Bgrx)
+        );
+        assert_eq!(
+            video_format(&opaque_only, NDIlib_FourCC_video_type_RGBA),
+            Some(VideoFormat::Rgbx)
+        );
+
+        // The format with alpha is kept if allowed
+        let with_alpha = ReceiverSettings {
+            video_formats: Some(vec![VideoFormat::Bgra, VideoFormat::Bgrx]),
+            ..settings()
+        };
+        assert_eq!(
+            video_format(&with_alpha, NDIlib_FourCC_video_type_BGRA),
+            Some(VideoFormat::Bgra)
+        );
+
+        // Formats that are not allowed at all fail
+        let bgrx_only = ReceiverSettings {
+            video_formats: Some(vec![VideoFormat::Bgrx]),
+            ..settings()
+        };
+        assert_eq!(
+            video_format(&bgrx_only, NDIlib_FourCC_video_type_RGBA),
+            None
+        );
+    }
+
+    // Colorimetry of the video info created for a UYVY frame of the given height
+    fn colorimetry(
+        settings: &ReceiverSettings,
+        yres: i32,
+        metadata: Option<&'static [u8]>,
+    ) -> gst_video::VideoColorimetry {
+        let element = element();
+        let receiver = receiver(&element, settings, vec![]);
+
+        let frame = FakeFrame::video(
+            NDIlib_FourCC_video_type_UYVY,
+            16,
+            yres,
+            32,
+            vec![0; 32 * yres as usize],
+        )
+        .with_video(|frame| {
+            if let Some(metadata) = metadata {
+                frame.p_metadata = metadata.as_ptr() as *const _;
+            }
+        })
+        .into_video_frame();
+
+        raw_video_info(receiver.create_video_info(&element, &frame).unwrap()).colorimetry()
+    }
+
+    #[test]
+    fn video_colorimetry_defaults() {
+        assert_eq!(
+            colorimetry(&settings(), 480, None),
+            "bt601".parse().unwrap()
+        );
+        assert_eq!(
+            colorimetry(&settings(), 576, None),
+            "bt601".parse().unwrap()
+        );
+        assert_eq!(
+            colorimetry(&settings(), 720, None),
+            "bt709".parse().unwrap()
+        );
+        assert_eq!(
+            colorimetry(&settings(), 2160, None),
+            "bt709".parse().unwrap()
+        );
+    }
+
+    #[test]
+    fn video_colorimetry_override() {
+        let settings = ReceiverSettings {
+            colorimetry: Some("bt2020".parse().unwrap()),
+            ..settings()
+        };
+
+        assert_eq!(colorimetry(&settings, 576, None), "bt2020".parse().unwrap());
+        assert_eq!(
+            colorimetry(&settings, 1080, None),
+            "bt2020".parse().unwrap()
+        );
+    }
+
+    #[cfg(feature = "advanced-sdk")]
+    #[test]
+    fn video_colorimetry_hdr_metadata() {
+        const HLG: &[u8] =
+            b"<ndi_color_info transfer=\"bt_2100_hlg\" matrix=\"bt_2020\" primaries=\"bt_2020\"/>\0";
+
+        let hlg = colorimetry(&settings(), 2160, Some(HLG));
+        assert_eq!(hlg.range(), gst_video::VideoColorRange::Range16_235);
+        assert_eq!(hlg.matrix(), gst_video::VideoColorMatrix::Bt2020);
+        assert_eq!(hlg.transfer(), gst_video::VideoTransferFunction::AribStdB67);
+        assert_eq!(hlg.primaries(), gst_video::VideoColorPrimaries::Bt2020);
+
+        // Unrelated metadata doesn't change the default
+        assert_eq!(
+            colorimetry(
+                &settings(),
+                2160,
+                Some(b"<ndi_tally on_program=\"true\"/>\0")
+            ),
+            "bt709".parse().unwrap()
+        );
+
+        // The override takes precedence over the metadata
+        let settings = ReceiverSettings {
+            colorimetry: Some("bt709".parse().unwrap()),
+            ..settings()
+        };
+        assert_eq!(
+            colorimetry(&settings, 2160, Some(HLG)),
+            "bt709".parse().unwrap()
+        );
+    }
+
+    #[test]
+    fn timeout_signals_eos() {
+        let element = element();
+        let settings = ReceiverSettings {
+            connect_timeout: 100,
+            timeout: 100,
+            ..settings()
+        };
+        let receiver = receiver(&element, &settings, vec![]);
+
+        assert!(matches!(receiver.capture(), ReceiverItem::Timeout));
+        assert!(!receiver.is_connected());
+    }
+
+    fn audio_frames(count: usize) -> Vec<FakeFrame> {
+        (0..count)
+            .map(|_| FakeFrame::audio(1, 480, 480, vec![0.0; 480]))
+            .collect()
+    }
+
+    #[test]
+    fn timeout_after_queued_buffers() {
+        let element = element();
+        let settings = ReceiverSettings {
+            timeout: 100,
+            ..settings()
+        };
+        let receiver = receiver(&element, &settings, audio_frames(3));
+
+        // Times out while the buffers are still queued
+        thread::sleep(time::Duration::from_millis(500));
+        assert!((receiver.0.queue.0).0.lock().unwrap().timeout);
+
+        for _ in 0..3 {
+            assert!(matches!(
+                receiver.capture(),
+                ReceiverItem::Buffer(Buffer::Audio(..))
+            ));
+        }
+        assert!(matches!(receiver.capture(), ReceiverItem::Timeout));
+    }
+
+    #[test]
+    fn shutdown_with_queued_buffers() {
+        let element = element();
+        let receiver = receiver(&element, &settings(), audio_frames(3));
+
+        thread::sleep(time::Duration::from_millis(200));
+        assert_eq!((receiver.0.queue.0).0.lock().unwrap().buffer_queue.len(), 3);
+
+        // Queued buffers are not returned anymore after shutdown
+        receiver.receiver_control_handle().shutdown();
+        assert!(matches!(receiver.capture(), ReceiverItem::Flushing));
+        assert!(matches!(receiver.capture(), ReceiverItem::Flushing));
+    }
+
+    #[test]
+    fn flushing_drops_queued_buffers() {
+        let element = element();
+        let settings = ReceiverSettings {
+            timeout: 300,
+            ..settings()
+        };
+        let receiver = receiver(&element, &settings, audio_frames(3));
+
+        thread::sleep(time::Duration::from_millis(200));
+        assert_eq!((receiver.0.queue.0).0.lock().unwrap().buffer_queue.len(), 3);
+
+        receiver.set_flushing(true);
+        assert!(matches!(receiver.capture(), ReceiverItem::Flushing));
+        receiver.set_flushing(false);
+
+        // Nothing is received after the flush, so the source times out without any of the
+        // buffers from before
+        assert!(matches!(receiver.capture(), ReceiverItem::Timeout));
+    }
+
+    #[test]
+    fn flushing_drops_queued_gap() {
+        let element = element();
+        let receiver = receiver(&element, &settings(), vec![]);
+
+        receiver.queue_gap(gst::ClockTime::ZERO, gst::ClockTime::from_seconds(1));
+        receiver.set_flushing(true);
+        receiver.set_flushing(false);
+
+        // Waits for new data instead of returning the gap until shut down
+        let handle = receiver.receiver_control_handle();
+        let shutdown = thread::spawn(move || {
+            thread::sleep(time::Duration::from_millis(100));
+            handle.shutdown();
+        });
+        assert!(matches!(receiver.capture(), ReceiverItem::Flushing));
+        shutdown.join().unwrap();
+    }
+
+    #[test]
+    fn flushing_interrupts_connect_retry() {
+        let element = element();
+        let receiver = receiver(&element, &settings(), vec![]);
+
+        let flushing = receiver.clone();
+        let flush = thread::spawn(move || {
+            thread::sleep(time::Duration::from_millis(100));
+            flushing.set_flushing(true);
+        });
+        let start = time::Instant::now();
+        receiver
+            .0
+            .queue
+            .wait_for_retry(time::Duration::from_secs(10));
+        assert!(start.elapsed() < time::Duration::from_secs(5));
+        flush.join().unwrap();
+    }
+
+    #[test]
+    fn freeze_on_loss_times_out() {
+        let element = element();
+        let settings = ReceiverSettings {
+            freeze_on_loss: true,
+            timeout: 300,
+            ..settings()
+        };
+        let receiver = receiver(
+            &element,
+            &settings,
+            vec![FakeFrame::audio(1, 480, 480, vec![0.0; 480])],
+        );
+
+        // The frame is followed by silence until the source times out
+        let mut gaps = 0;
+        loop {
+            match receiver.capture() {
+                ReceiverItem::Buffer(Buffer::Audio(buffer, _)) => {
+                    if buffer.flags().contains(gst::BufferFlags::GAP) {
+                        gaps += 1;
+                    }
+                }
+                ReceiverItem::Timeout => break,
+                _ => unreachable!(),
+            }
+        }
+        assert!(gaps > 0);
+    }
+
+    #[test]
+    fn shutdown_while_waiting() {
+        let element = element();
+        let receiver = receiver(&element, &settings(), vec![]);
+
+        let handle = receiver.receiver_control_handle();
+        let shutdown = thread::spawn(move || {
+            thread::sleep(time::Duration::from_millis(100));
+            handle.shutdown();
+        });
+
+        assert!(matches!(receiver.capture(), ReceiverItem::Flushing));
+        shutdown.join().unwrap();
+    }
+
+    const FRAME: u64 = 40_000_000;
+
+    // Feeds (remote time, local time) pairs to a new estimator and returns all estimations
+    fn estimate(times: impl IntoIterator<Item = (u64, u64)>) -> Vec<Estimation> {
+        let mut observations = ObservationsInner::default();
+        times
+            .into_iter()
+            .map(|(remote_time, local_time)| observations.process(remote_time, local_time))
+            .collect()
+    }
+
+    fn estimated_time(estimation: &Estimation) -> u64 {
+        match *estimation {
+            Estimation::Estimated { time, .. } => time,
+            _ => panic!("Expected estimated time, got {:?}", estimation),
+        }
+    }
+
+    #[test]
+    fn observations_monotonic() {
+        // Constant network latency of 10ms
+        let estimations = estimate((0..1000).map(|i| (i * FRAME, 10_000_000 + i * FRAME)));
+
+        assert_eq!(estimations[0], Estimation::Initialized);
+        for (i, estimation) in estimations.iter().enumerate().skip(1) {
+            assert_eq!(estimated_time(estimation), 10_000_000 + i as u64 * FRAME);
+        }
+    }
+
+    #[test]
+    fn observations_jitter() {
+        // Up to 8ms of additional latency for every frame, never less than the first one
+        let jitter = |i: u64| (i * 2_654_435_761) % 8_000_000;
+        let estimations = estimate((0..1000).map(|i| (i * FRAME, i * FRAME + jitter(i))));
+
+        assert_eq!(estimations[0], Estimation::Initialized);
+        let mut last_time = 0;
+        for (i, estimation) in estimations.iter().enumerate().skip(1) {
+            let time = estimated_time(estimation);
+            let remote_time = i as u64 * FRAME;
+
+            // The jitter is filtered out, so the output increases with the remote time and
+            // stays within the range of the latency
+            assert!(time > last_time);
+            assert!(time >= remote_time && time < remote_time + 8_000_000);
+            last_time = time;
+        }
+
+        // Once the window is filled, the minimum delta is used as skew and the output follows
+        // the remote time closely
+        let time = estimated_time(&estimations[999]);
+        assert!(time - 999 * FRAME < 1_000_000);
+    }
+
+    #[test]
+    fn observations_slope_reset() {
+        // After 10 frames the remote time jumps ahead by a second
+        let estimations = estimate((0..20).map(|i| {
+            let remote_time = i * FRAME + if i >= 10 { 1_000_000_000 } else { 0 };
+            (remote_time, i * FRAME)
+        }));
+
+        assert_eq!(estimations[0], Estimation::Initialized);
+        assert!(matches!(
+            estimations[10],
+            Estimation::ResetSlope { discont: true, .. }
+        ));
+        // Estimation continues from the new base time
+        for (i, estimation) in estimations.iter().enumerate().skip(11) {
+            assert_eq!(estimated_time(estimation), i as u64 * FRAME);
+        }
+    }
+
+    #[test]
+    fn observations_delta_reset() {
+        // After 30s the local time jumps ahead by 1.5s. The slope between both clocks is still
+        // plausible at that point, but the delta is too far from the skew.
+        let jump = 750;
+        let estimations = estimate((0..jump + 10).map(|i| {
+            let local_time = i * FRAME + if i >= jump { 1_500_000_000 } else { 0 };
+            (i * FRAME, local_time)
+        }));
+
+        assert_eq!(estimations[0], Estimation::Initialized);
+        match estimations[jump as usize] {
+            Estimation::ResetDelta {
+                delta,
+                skew,
+                discont,
+            } => {
+                assert_eq!(delta, 1_500_000_000);
+                assert_eq!(skew, 0);
+                assert!(discont);
+            }
+            ref estimation => panic!("Expected delta reset, got {:?}", estimation),
+        }
+        for (i, estimation) in estimations.iter().enumerate().skip(jump as usize + 1) {
+            assert_eq!(estimated_time(estimation), i as u64 * FRAME + 1_500_000_000);
+        }
+    }
+
+    #[test]
+    fn timecode_wraparound() {
+        let element = element();
+        let tracker = TimecodeTracker::new();
+        let frame = gst::ClockTime::from_nseconds(FRAME);
+        let receive_time = gst::ClockTime::from_seconds(5);
+
+        // Timecodes up to one frame before midnight, then starting again from zero
+        let midnight = gst::ClockTime::from_seconds(24 * 60 * 60);
+        let timecodes = (1..=3)
+            .rev()
+            .map(|i| midnight - i * frame)
+            .chain((0..3).map(|i| i * frame))
+            .collect::<Vec<_>>();
+
+        let results = timecodes
+            .iter()
+            .map(|timecode| tracker.process(&element, *timecode, receive_time, Some(frame)))
+            .collect::<Vec<_>>();
+
+        // The PTS continues monotonically across the wrap and only the first buffer after it is
+        // marked as discontinuous
+        for (i, (pts, duration, discont)) in results.iter().enumerate() {
+            assert_eq!(*pts, receive_time + i as u64 * frame);
+            assert_eq!(*duration, Some(frame));
+            assert_eq!(*discont, i == 3);
+        }
+    }
+
+    #[test]
+    fn timecode_reordering() {
+        let element = element();
+        let tracker = TimecodeTracker::new();
+        let frame = gst::ClockTime::from_nseconds(FRAME);
+        let receive_time = gst::ClockTime::from_seconds(5);
+        let base = gst::ClockTime::from_seconds(100);
+
+        let (pts, _, _) = tracker.process(&element, base + frame, receive_time, Some(frame));
+        assert_eq!(pts, receive_time);
+
+        // Audio slightly before the video it belongs to is not a wraparound
+        let (pts, _, discont) = tracker.process(&element, base, receive_time, Some(frame));
+        assert_eq!(pts, receive_time - frame);
+        assert!(!discont);
+
+        let (pts, _, discont) =
+            tracker.process(&element, base + 2 * frame, receive_time, Some(frame));
+        assert_eq!(pts, receive_time + frame);
+        assert!(!discont);
+    }
+
+    #[cfg(feature = "advanced-sdk")]
+    #[test]
+    fn repeated_extra_data() {
+        let sps_pps = [0, 0, 0, 1, 0x67, 0x42, 0, 0, 0, 1, 0x68, 0xce];
+        let mut last_extra_data = None;
+
+        // The first packet always gets the configuration, afterwards only keyframes as long as it
+        // stays the same
+        assert!(update_extra_data(
+            &mut last_extra_data,
+            Some(&sps_pps),
+            true
+        ));
+        for _ in 0..10 {
+            assert!(!update_extra_data(
+                &mut last_extra_data,
+                Some(&sps_pps),
+                false
+            ));
+        }
+        assert!(update_extra_data(
+            &mut last_extra_data,
+            Some(&sps_pps),
+            true
+        ));
+        assert!(!update_extra_data(&mut last_extra_data, None, false));
+
+        // A changed configuration is prepended right away, even without keyframe
+        let changed = [0, 0, 0, 1, 0x67, 0x4d, 0, 0, 0, 1, 0x68, 0xce];
+        assert!(update_extra_data(
+            &mut last_extra_data,
+            Some(&changed),
+            false
+        ));
+        assert!(!update_extra_data(
+            &mut last_extra_data,
+            Some(&changed),
+            false
+        ));
+        assert_eq!(last_extra_data.as_deref(), Some(&changed[..]));
+    }
+
+    #[test]
+    fn copy_nv12_frame() {
+        let element = element();
+        let receiver = receiver(&element, &settings(), vec![]);
+
+        // Odd height and a line stride larger than the width, so the UV plane has (height + 1) / 2
+        // lines and every line has padding at its end
+        let (width, height, chroma_height, stride) = (62, 35, 18, 64);
+        let data = (0..(height + chroma_height) * stride)
+            .map(|i| (i % 251) as u8)
+            .collect::<Vec<_>>();
+        let frame = FakeFrame::video(
+            NDIlib_FourCC_video_type_NV12,
+            width as i32,
+            height as i32,
+            stride as i32,
+            data.clone(),
+        )
+        .into_video_frame();
+
+        let info = receiver.create_video_info(&element, &frame).unwrap();
+        let buffer = receiver.copy_video_frame(&element, &info, &frame).unwrap();
+        let info = raw_video_info(info);
+        assert_eq!(info.format(), gst_video::VideoFormat::Nv12);
+
+        let vframe =
+            gst_video::VideoFrameRef::from_buffer_ref_readable(buffer.as_ref(), &info).unwrap();
+        let (luma, chroma) = data.split_at(height * stride);
+        for (plane, src, lines) in [(0, luma, height), (1, chroma, chroma_height)] {
+            let dest_stride = vframe.plane_stride()[plane] as usize;
+            let dest = vframe.plane_data(plane as u32).unwrap();
+            assert!(dest.len() >= (lines - 1) * dest_stride + width);
+
+            for line in 0..lines {
+                assert_eq!(
+                    &dest[line * dest_stride..][..width],
+                    &src[line * stride..][..width],
+                    "Plane {} line {}",
+                    plane,
+                    line
+                );
+            }
+        }
+    }
+
+    // Copies a field of a 6x9 frame and checks that every plane of the field, given as offset and
+    // line stride in the source data, number of lines and bytes per line, ends up in the output
+    #[cfg(feature = "interlaced-fields")]
+    fn check_field_copy(
+        fourcc: NDIlib_FourCC_video_type_e,
+        stride: usize,
+        planes: &[(usize, usize, usize, usize)],
+    ) {
+        let element = element();
+        let receiver = receiver(&element, &settings(), vec![]);
+
+        for field in [
+            NDIlib_frame_format_type_e::NDIlib_frame_format_type_field_0,
+            NDIlib_frame_format_type_e::NDIlib_frame_format_type_field_1,
+        ] {
+            let size = planes
+                .iter()
+                .map(|(offset, stride, lines, _)| offset + stride * lines)
+                .max()
+                .unwrap();
+            let data = (0..size).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+            let frame = FakeFrame::video(fourcc, 6, 9, stride as i32, data.clone())
+                .with_video(|frame| frame.frame_format_type = field)
+                .into_video_frame();
+
+            // Fields only contain every second line
+            assert_eq!(frame.data().unwrap().len(), size);
+
+            let info = receiver.create_video_info(&element, &frame).unwrap();
+            let buffer = receiver.copy_video_frame(&element, &info, &frame).unwrap();
+            let info = raw_video_info(info);
+            assert_eq!(
+                info.interlace_mode(),
+                gst_video::VideoInterlaceMode::Alternate
+            );
+
+            let vframe =
+                gst_video::VideoFrameRef::from_buffer_ref_readable(buffer.as_ref(), &info).unwrap();
+            for (plane, &(offset, src_stride, lines, line_bytes)) in planes.iter().enumerate() {
+                let dest_stride = vframe.plane_stride()[plane] as usize;
+                let dest = vframe.plane_data(plane as u32).unwrap();
+
+                for line in 0..lines {
+                    assert_eq!(
+                        &dest[line * dest_stride..][..line_bytes],
+                        &data[offset + line * src_stride..][..line_bytes],
+                        "{:?} plane {} line {}",
+                        field,
+                        plane,
+                        line
+                    );
+                }
+            }
+        }
+    }
+
+    #[cfg(feature = "interlaced-fields")]
+    #[test]
+    fn copy_uyvy_field() {
+        check_field_copy(NDIlib_FourCC_video_type_UYVY, 16, &[(0, 16, 5, 12)]);
+    }
+
+    #[cfg(feature = "interlaced-fields")]
+    #[test]
+    fn copy_nv12_field() {
+        // The UV plane has half the lines of the field
+        check_field_copy(
+            NDIlib_FourCC_video_type_NV12,
+            8,
+            &[(0, 8, 5, 6), (40, 8, 3, 6)],
+        );
+    }
+
+    #[cfg(feature = "interlaced-fields")]
+    #[test]
+    fn copy_i420_field() {
+        // The chroma planes have half the lines of the field and half the line stride
+        check_field_copy(
+            NDIlib_FourCC_video_type_I420,
+            8,
+            &[(0, 8, 5, 6), (40, 4, 3, 3), (52, 4, 3, 3)],
+        );
+    }
+
+    #[test]
+    fn field_duration() {
+        let element = element();
+        let settings = ReceiverSettings {
+            timestamp_mode: TimestampMode::Timecode,
+            ..settings()
+        };
+        let receiver = receiver(&element, &settings, vec![]);
+
+        let duration = |frame_format_type| {
+            let frame = FakeFrame::video(NDIlib_FourCC_video_type_UYVY, 2, 2, 4, vec![0; 8])
+                .with_video(|frame| frame.frame_format_type = frame_format_type)
+                .into_video_frame();
+            receiver
+                .calculate_video_timestamp(&element, &frame)
+                .unwrap()
+                .1
+        };
+
+        // Fields are sent at twice the frame rate and last half a frame
+        assert_eq!(
+            duration(NDIlib_frame_format_type_e::NDIlib_frame_format_type_progressive),
+            Some(gst::ClockTime::from_nseconds(33_333_333))
+        );
+        assert_eq!(
+            duration(NDIlib_frame_format_type_e::NDIlib_frame_format_type_interleaved),
+            Some(gst::ClockTime::from_nseconds(33_333_333))
+        );
+        assert_eq!(
+            duration(NDIlib_frame_format_type_e::NDIlib_frame_format_type_field_0),
+            Some(gst::ClockTime::from_nseconds(16_666_666))
+        );
+        assert_eq!(
+            duration(NDIlib_frame_format_type_e::NDIlib_frame_format_type_field_1),
+            Some(gst::ClockTime::from_nseconds(16_666_666))
+        );
+    }
+
+    #[test]
+    fn variable_frame_rate() {
+        let element = element();
+        let receiver = receiver(&element, &settings(), vec![]);
+
+        let frame = |timecode| {
+            FakeFrame::video(NDIlib_FourCC_video_type_UYVY, 2, 2, 4, vec![0; 8])
+                .with_video(|frame| {
+                    frame.frame_rate_N = 0;
+                    frame.frame_rate_D = 0;
+                    frame.timecode = timecode;
+                })
+                .into_video_frame()
+        };
+
+        let first = frame(1_000_000);
+        assert_eq!(first.frame_rate(), (0, 1));
+
+        // The duration is only known from the distance to the previous frame
+        let (_, duration, _) = receiver
+            .calculate_video_timestamp(&element, &first)
+            .unwrap();
+        assert_eq!(duration, None);
+
+        let (_, duration, _) = receiver
+            .calculate_video_timestamp(&element, &frame(1_400_000))
+            .unwrap();
+        assert_eq!(duration, Some(gst::ClockTime::from_mseconds(40)));
+
+        let (_, duration, _) = receiver
+            .calculate_video_timestamp(&element, &frame(1_900_000))
+            .unwrap();
+        assert_eq!(duration, Some(gst::ClockTime::from_mseconds(50)));
+    }
+
+    #[test]
+    fn variable_frame_rate_caps() {
+        let element = element();
+        let receiver = receiver(&element, &settings(), vec![]);
+
+        let frame = FakeFrame::video(NDIlib_FourCC_video_type_UYVY, 2, 2, 4, vec![0; 8])
+            .with_video(|frame| frame.frame_rate_N = 0)
+            .into_video_frame();
+        let info = raw_video_info(receiver.create_video_info(&element, &frame).unwrap());
+        assert_eq!(info.fps(), gst::Fraction::new(0, 1));
+    }
+
+    #[test]
+    fn audio_channel_stride_padding() {
+        let element = element();
+        let receiver = receiver(&element, &settings(), vec![]);
+
+        // Two channels with 3 samples each, padded to a stride of 5 samples
+        let frame = FakeFrame::audio(
+            2,
+            3,
+            5,
+            vec![
+                0.1, 0.2, 0.3, -1.0, -1.0, //
+                0.4, 0.5, 0.6, -1.0, -1.0,
+            ],
+        )
+        .into_audio_frame();
+
+        let info = receiver.create_audio_info(&element, &frame).unwrap();
+        let buffer = receiver
+            .create_audio_buffer(&element, gst::ClockTime::ZERO, None, &info, &frame)
+            .unwrap();
+
+        let map = buffer.map_readable().unwrap();
+        assert_eq!(
+            map.as_slice_of::<f32>().unwrap(),
+            &[0.1, 0.4, 0.2, 0.5, 0.3, 0.6]
+        );
+    }
+
+    #[test]
+    fn s16_conversion_clamps() {
+        let element = element();
+        let settings = ReceiverSettings {
+            audio_format: gst_audio::AUDIO_FORMAT_S16,
+            ..settings()
+        };
+        let receiver = receiver(&element, &settings, vec![]);
+
+        // Full range corresponds to 1.0, everything beyond is clamped
+        let frame = FakeFrame::audio(1, 7, 7, vec![0.0, 0.5, -0.5, 1.0, -1.0, 1.5, -2.0])
+            .into_audio_frame();
+        let info = receiver.create_audio_info(&element, &frame).unwrap();
+        let buffer = receiver
+            .create_audio_buffer(&element, gst::ClockTime::ZERO, None, &info, &frame)
+            .unwrap();
+
+        let map = buffer.map_readable().unwrap();
+        assert_eq!(
+            map.as_slice_of::<i16>().unwrap(),
+            &[0, 16384, -16384, 32767, -32768, 32767, -32768]
+        );
+    }
+
+    #[test]
+    fn s16_reference_level_round_trip() {
+        let element = element();
+        let settings = ReceiverSettings {
+            audio_format: gst_audio::AUDIO_FORMAT_S16,
+            reference_level: 10,
+            ..settings()
+        };
+        let receiver = receiver(&element, &settings, vec![]);
+
+        let samples: [i16; 6] = [0, 1000, -1000, 16384, 32767, -32768];
+        let info = gst_audio::AudioInfo::builder(gst_audio::AUDIO_FORMAT_S16, 48_000, 2)
+            .build()
+            .unwrap();
+        let mut buffer = gst::Buffer::with_size(samples.len() * 2).unwrap();
+        buffer
+            .get_mut()
+            .unwrap()
+            .map_writable()
+            .unwrap()
+            .as_mut_slice_of::<i16>()
+            .unwrap()
+            .copy_from_slice(&samples);
+
+        // What ndisink sends for the same reference level, 10 dB above full range is below 1.0
+        let sent = AudioFrame::try_from_buffer(&info, &buffer, 0, 10).unwrap();
+        let scale = 10.0f32.powf(0.5) / 32768.0;
+        let data = sent.data().unwrap().as_slice_of::<f32>().unwrap().to_vec();
+        assert!((data[2] - 16384.0 * scale).abs() < 1e-6);
+
+        let frame = FakeFrame::audio(2, 3, 3, data).into_audio_frame();
+        let info = receiver.create_audio_info(&element, &frame).unwrap();
+        let received = receiver
+            .create_audio_buffer(&element, gst::ClockTime::ZERO, None, &info, &frame)
+            .unwrap();
+
+        let map = received.map_readable().unwrap();
+        assert_eq!(map.as_slice_of::<i16>().unwrap(), &samples);
+    }
+
+    #[test]
+    fn create_video_info_par() {
+        let element = element();
+        let receiver = receiver(&element, &settings(), vec![]);
+
+        let par = |picture_aspect_ratio| {
+            let frame = FakeFrame::video(
+                NDIlib_FourCC_video_type_UYVY,
+                720,
+                576,
+                1440,
+                vec![0; 1440 * 576],
+            )
+            .with_video(|frame| frame.picture_aspect_ratio = picture_aspect_ratio)
+            .into_video_frame();
+            raw_video_info(receiver.create_video_info(&element, &frame).unwrap()).par()
+        };
+
+        // 0 and invalid values mean square pixels
+        assert_eq!(par(0.0), gst::Fraction::new(1, 1));
+        assert_eq!(par(-1.0), gst::Fraction::new(1, 1));
+        assert_eq!(par(f32::NAN), gst::Fraction::new(1, 1));
+        assert_eq!(par(720.0 / 576.0), gst::Fraction::new(1, 1));
+        assert_eq!(par(16.0 / 9.0), gst::Fraction::new(64, 45));
+    }
+
+    #[test]
+    fn auto_timestamp_mode() {
+        let element = element();
+        let settings = ReceiverSettings {
+            timestamp_mode: TimestampMode::Auto,
+            ..settings()
+        };
+        let selected = |receiver: &Receiver| *receiver.0.auto_timestamp_mode.lock().unwrap();
+
+        let with_timestamp = receiver(&element, &settings, vec![]);
+        assert_eq!(selected(&with_timestamp), None);
+        assert!(with_timestamp
+            .calculate_timestamp(&element, 10_000_000, 0, None)
+            .is_some());
+        assert_eq!(
+            selected(&with_timestamp),
+            Some(TimestampMode::ReceiveTimeTimestamp)
+        );
+
+        // Selected once based on the first frame only
+        with_timestamp.calculate_timestamp(
+            &element,
+            NDIlib_recv_timestamp_undefined,
+            333_333,
+            None,
+        );
+        assert_eq!(
+            selected(&with_timestamp),
+            Some(TimestampMode::ReceiveTimeTimestamp)
+        );
+
+        let without_timestamp = receiver(&element, &settings, vec![]);
+        assert!(without_timestamp
+            .calculate_timestamp(&element, NDIlib_recv_timestamp_undefined, 0, None)
+            .is_some());
+        assert_eq!(
+            selected(&without_timestamp),
+            Some(TimestampMode::ReceiveTimeTimecode)
+        );
+
+        without_timestamp.calculate_timestamp(&element, 10_000_000, 333_333, None);
+        assert_eq!(
+            selected(&without_timestamp),
+            Some(TimestampMode::ReceiveTimeTimecode)
+        );
+    }
+
+    #[test]
+    fn first_buffers_discont() {
+        let element = element();
+        let settings = ReceiverSettings {
+            timestamp_mode: TimestampMode::Timecode,
+            ..settings()
+        };
+
+        let video = |timecode| {
+            FakeFrame::video(NDIlib_FourCC_video_type_UYVY, 2, 2, 4, vec![0; 8])
+                .with_video(|frame| frame.timecode = timecode)
+        };
+        let audio = |timecode| {
+            let mut frame = FakeFrame::audio(1, 480, 480, vec![0.0; 480]);
+            if let FakeFrame::Audio(ref mut frame, _) = frame {
+                frame.timecode = timecode;
+            }
+            frame
+        };
+        let receiver = receiver(
+            &element,
+            &settings,
+            vec![
+                video(0),
+                audio(0),
+                video(333_333),
+                audio(100_000),
+                video(666_666),
+            ],
+        );
+
+        let mut video_flags = vec![];
+        let mut audio_flags = vec![];
+        while video_flags.len() + audio_flags.len() < 5 {
+            match receiver.capture() {
+                ReceiverItem::Buffer(Buffer::Video(buffer, _)) => video_flags.push(buffer.flags()),
+                ReceiverItem::Buffer(Buffer::Audio(buffer, _)) => audio_flags.push(buffer.flags()),
+                item => panic!("Unexpected item {:?}", item),
+            }
+        }
+
+        // Only the first buffer of each stream starts a new stream
+        let discont = |flags: &[gst::BufferFlags]| {
+            flags
+                .iter()
+                .map(|flags| flags.contains(gst::BufferFlags::DISCONT))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(discont(&video_flags), [true, false, false]);
+        assert_eq!(discont(&audio_flags), [true, false]);
+    }
+
+    #[test]
+    fn fill_audio_gaps() {
+        let element = element();
+        let settings = ReceiverSettings {
+            fill_audio_gaps: true,
+            ..settings()
+        };
+        let receiver = receiver(&element, &settings, vec![]);
+
+        let frame = FakeFrame::audio(1, 480, 480, vec![0.0; 480]).into_audio_frame();
+        let info = receiver.create_audio_info(&element, &frame).unwrap();
+        let buffer = |pts| {
+            let mut buffer = gst::Buffer::new();
+            {
+                let buffer = buffer.get_mut().unwrap();
+                buffer.set_pts(gst::ClockTime::from_mseconds(pts));
+                buffer.set_duration(gst::ClockTime::from_mseconds(10));
+            }
+            buffer
+        };
+
+        let mut first = buffer(0);
+        receiver.fill_audio_gap(&element, &info, &mut first);
+
+        // 35ms are missing and filled with silence of at most the frame duration
+        let mut second = buffer(45);
+        receiver.fill_audio_gap(&element, &info, &mut second);
+        assert!(!second.flags().contains(gst::BufferFlags::DISCONT));
+
+        let mut silence = vec![];
+        while let Some(Buffer::Audio(buffer, _)) = (receiver.0.queue.0)
+            .0
+            .lock()
+            .unwrap()
+            .buffer_queue
+            .pop_front()
+        {
+            assert!(buffer.flags().contains(gst::BufferFlags::GAP));
+            silence.push((
+                buffer.pts().unwrap().mseconds(),
+                buffer.duration().unwrap().mseconds(),
+                buffer.size(),
+            ));
+        }
+        assert_eq!(
+            silence,
+            [(10, 10, 1920), (20, 10, 1920), (30, 10, 1920), (40, 5, 960)]
+        );
+
+        // Gaps of more than a second are only marked as discontinuity
+        let mut third = buffer(2000);
+        receiver.fill_audio_gap(&element, &info, &mut third);
+        assert!(third.flags().contains(gst::BufferFlags::DISCONT));
+        assert!((receiver.0.queue.0)
+            .0
+            .lock()
+            .unwrap()
+            .buffer_queue
+            .is_empty());
+    }
+
+    #[cfg(feature = "advanced-sdk")]
+    #[test]
+    fn droppable_h265_pictures() {
+        // SPS with sps_max_sub_layers_minus1 = 1
+        assert_eq!(h265_max_sub_layers(&[0x42, 0x01, 0x03]), Some(2));
+
+        // TRAIL_N in sub-layer 0 and 1, and TRAIL_R in sub-layer 1
+        let trail_n_0 = [0, 0, 1, 0x00, 0x01, 0xaf];
+        let trail_n_1 = [0, 0, 1, 0x00, 0x02, 0xaf];
+        let trail_r_1 = [0, 0, 1, 0x02, 0x02, 0xaf];
+
+        // Only non-reference pictures of the highest sub-layer are droppable
+        assert_eq!(
+            classify_compressed_packet(true, Some(2), &trail_n_0),
+            Some((false, false))
+        );
+        assert_eq!(
+            classify_compressed_packet(true, Some(2), &trail_n_1),
+            Some((false, true))
+        );
+        assert_eq!(
+            classify_compressed_packet(true, Some(2), &trail_r_1),
+            Some((false, false))
+        );
+        assert_eq!(
+            classify_compressed_packet(true, Some(1), &trail_n_0),
+            Some((false, true))
+        );
+        // Without SPS nothing is known about the sub-layers
+        assert_eq!(
+            classify_compressed_packet(true, None, &trail_n_1),
+            Some((false, false))
+        );
+    }
+
+    #[cfg(feature = "advanced-sdk")]
+    #[test]
+    fn droppable_h264_pictures() {
+        // Non-IDR slices with nal_ref_idc 0 and 1, and an IDR slice
+        assert_eq!(
+            classify_compressed_packet(false, None, &[0, 0, 1, 0x01, 0x88]),
+            Some((false, true))
+        );
+        assert_eq!(
+            classify_compressed_packet(false, None, &[0, 0, 1, 0x21, 0x88]),
+            Some((false, false))
+        );
+        assert_eq!(
+            classify_compressed_packet(false, None, &[0, 0, 1, 0x65, 0x88]),
+            Some((true, false))
+        );
+        // SPS only
+        assert_eq!(
+            classify_compressed_packet(false, None, &[0, 0, 1, 0x67, 0x42]),
+            None
+        );
+    }
+
+    #[test]
+    fn shared_timestamp_base_reset() {
+        let element = element();
+        let settings = ReceiverSettings {
+            timestamp_mode: TimestampMode::SharedTimestamp,
+            ..settings()
+        };
+
+        let frame = |timestamp| {
+            FakeFrame::video(NDIlib_FourCC_video_type_UYVY, 2, 2, 4, vec![0; 8])
+                .with_video(|frame| frame.timestamp = timestamp)
+                .into_video_frame()
+        };
+
+        let first = receiver(&element, &settings, vec![]);
+        let second = receiver(&element, &settings, vec![]);
+        first
+            .calculate_video_timestamp(&element, &frame(1_000_000))
+            .unwrap();
+        assert!(SHARED_TIMESTAMP_BASE.lock().unwrap().base.is_some());
+
+        // The base stays while any receiver is left
+        drop(first);
+        assert!(SHARED_TIMESTAMP_BASE.lock().unwrap().base.is_some());
+
+        // The receive thread might still hold a reference for a moment
+        drop(second);
+        let start = time::Instant::now();
+        while SHARED_TIMESTAMP_BASE.lock().unwrap().base.is_some() {
+            assert!(start.elapsed() < time::Duration::from_secs(5));
+            thread::sleep(time::Duration::from_millis(10));
+        }
+        assert_eq!(SHARED_TIMESTAMP_BASE.lock().unwrap().receivers, 0);
+    }
 }
@@ -0,0 +1,179 @@
+// Fake NDI backends shared by the unit tests of all elements. They stand in for the find and send
+// instances of the SDK so that discovery and sending can be tested without the NDI runtime, and
+// record everything that goes through them.
+
+use crate::ndi::*;
+use crate::ndisys::*;
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time;
+
+pub fn init() {
+    use std::sync::Once;
+    static INIT: Once = Once::new();
+
+    INIT.call_once(|| {
+        gst::init().unwrap();
+    });
+}
+
+#[derive(Debug, Default)]
+struct FakeFindState {
+    sources: Vec<(String, String)>,
+    changed: bool,
+}
+
+// Discovers the sources set by the test. Clones share the same sources so that the test can
+// change them while the find is in use.
+#[derive(Clone, Debug, Default)]
+pub struct FakeFind(Arc<(Mutex<FakeFindState>, Condvar)>);
+
+impl FakeFind {
+    pub fn new(sources: &[(&str, &str)]) -> Self {
+        let find = FakeFind::default();
+        find.set_sources(sources);
+        find
+    }
+
+    // Replaces the discovered sources by the given NDI names and URLs/addresses
+    pub fn set_sources(&self, sources: &[(&str, &str)]) {
+        let (lock, cond) = &*self.0;
+        let mut state = lock.lock().unwrap();
+        state.sources = sources
+            .iter()
+            .map(|(ndi_name, url_address)| (ndi_name.to_string(), url_address.to_string()))
+            .collect();
+        state.changed = true;
+        cond.notify_all();
+    }
+}
+
+impl FindSource for FakeFind {
+    fn wait_for_sources(&mut self, timeout_in_ms: u32) -> bool {
+        let (lock, cond) = &*self.0;
+        let state = lock.lock().unwrap();
+        let (mut state, _) = cond
+            .wait_timeout_while(
+                state,
+                time::Duration::from_millis(timeout_in_ms as u64),
+                |state| !state.changed,
+            )
+            .unwrap();
+
+        std::mem::replace(&mut state.changed, false)
+    }
+
+    fn current_sources(&mut self) -> Vec<Source<'static>> {
+        let (lock, _) = &*self.0;
+        lock.lock()
+            .unwrap()
+            .sources
+            .iter()
+            .filter_map(|(ndi_name, url_address)| Source::new(ndi_name, url_address))
+            .collect()
+    }
+}
+
+// Copy of a frame passed to FakeSender, with the fields the sink fills in
+#[derive(Clone, Debug, PartialEq)]
+pub enum SentFrame {
+    Video {
+        fourcc: NDIlib_FourCC_video_type_e,
+        xres: i32,
+        yres: i32,
+        frame_format_type: NDIlib_frame_format_type_e,
+        line_stride: i32,
+        timecode: i64,
+        data: Vec<u8>,
+    },
+    Audio {
+        sample_rate: i32,
+        no_channels: i32,
+        no_samples: i32,
+        channel_stride: i32,
+        timecode: i64,
+        data: Vec<f32>,
+    },
+    Metadata(String),
+}
+
+#[derive(Debug, Default)]
+struct FakeSenderState {
+    sent: Vec<SentFrame>,
+    connection_metadata: Vec<String>,
+    requests: VecDeque<String>,
+}
+
+// Records all frames sent through it and hands out the metadata queued by the test as if it was
+// sent by receivers. Clones share the same state so that the test can inspect it.
+#[derive(Clone, Debug, Default)]
+pub struct FakeSender(Arc<Mutex<FakeSenderState>>);
+
+impl FakeSender {
+    pub fn sent(&self) -> Vec<SentFrame> {
+        self.0.lock().unwrap().sent.clone()
+    }
+
+    pub fn connection_metadata(&self) -> Vec<String> {
+        self.0.lock().unwrap().connection_metadata.clone()
+    }
+
+    // Queues metadata to be returned by capture_metadata(), e.g. a PTZ request
+    pub fn push_request(&self, metadata: &str) {
+        self.0
+            .lock()
+            .unwrap()
+            .requests
+            .push_back(metadata.to_string());
+    }
+}
+
+impl FrameSink for FakeSender {
+    fn send_video(&mut self, frame: &VideoFrame) {
+        self.0.lock().unwrap().sent.push(SentFrame::Video {
+            fourcc: frame.fourcc(),
+            xres: frame.xres(),
+            yres: frame.yres(),
+            frame_format_type: frame.frame_format_type(),
+            line_stride: frame.line_stride_or_data_size_in_bytes(),
+            timecode: frame.timecode(),
+            data: frame.data().map(|data| data.to_vec()).unwrap_or_default(),
+        });
+    }
+
+    fn send_audio(&mut self, frame: &AudioFrame) {
+        use byte_slice_cast::*;
+
+        self.0.lock().unwrap().sent.push(SentFrame::Audio {
+            sample_rate: frame.sample_rate(),
+            no_channels: frame.no_channels(),
+            no_samples: frame.no_samples(),
+            channel_stride: frame.channel_stride_or_data_size_in_bytes(),
+            timecode: frame.timecode(),
+            data: frame
+                .data()
+                .and_then(|data| data.as_slice_of::<f32>().ok())
+                .map(|data| data.to_vec())
+                .unwrap_or_default(),
+        });
+    }
+
+    fn send_metadata(&mut self, metadata: &MetadataFrame) {
+        let metadata = metadata.metadata().unwrap_or_default().into_owned();
+        self.0
+            .lock()
+            .unwrap()
+            .sent
+            .push(SentFrame::Metadata(metadata));
+    }
+
+    fn add_connection_metadata(&mut self, metadata: &MetadataFrame) {
+        let metadata = metadata.metadata().unwrap_or_default().into_owned();
+        self.0.lock().unwrap().connection_metadata.push(metadata);
+    }
+
+    fn capture_metadata(&mut self, _timeout_in_ms: u32) -> Option<String> {
+        self.0.lock().unwrap().requests.pop_front()
+    }
+}